@@ -175,6 +175,31 @@ class Thermostat(ThermalDevCtrl):
 		job = (len(self.jobs),  dict(j))
 		self.jobs.append(job)
 
+	# Sets the setpoints and deadband at once, so they are never inconsistent during a tick
+	def setSetpoints(self, heating, cooling, temperatureMin, temperatureMax, deadband):
+		if heating > cooling:
+			return (False, "Heating setpoint must not be above the cooling setpoint!")
+		if temperatureMin > temperatureMax:
+			return (False, "Minimum temperature must not be above the maximum temperature!")
+		if len(deadband) != 4 or deadband[0] > deadband[1] or deadband[2] > deadband[3]:
+			return (False, "Deadband must be four ascending offsets per setpoint!")
+
+		self.temperatureSetpointHeating = float(heating)
+		self.temperatureSetpointCooling = float(cooling)
+		self.temperatureMin = float(temperatureMin)
+		self.temperatureMax = float(temperatureMax)
+		self.temperatureDeadband = [float(offset) for offset in deadband]
+		self.setpointChanged = True
+
+		return (True, "")
+
+	# Drops the heating intervals, e.g. when an external schedule sets the setpoints instead
+	def clearJobs(self):
+		self.jobs = []
+		self.currentJobIdx = -1
+
+		return (True, "")
+
 	def initializePredictors(self):
 		time = self.host.time() - ( 4*7*24*60*60)
 
@@ -289,7 +314,13 @@ class Thermostat(ThermalDevCtrl):
 		r = dict()
 
 		# Populate the result dict
+		r['devtype'] = self.devtype
+		r['zone'] = self.dev.name
+		r['heat_demand'] = self.heatDemand
 		r['min_target_temp'] = self.temperatureSetpointHeating
 		r['max_target_temp'] = self.temperatureSetpointCooling
+		r['temperature_min'] = self.temperatureMin
+		r['temperature_max'] = self.temperatureMax
+		r['temperature_deadband'] = list(self.temperatureDeadband)
 
 		return r
\ No newline at end of file
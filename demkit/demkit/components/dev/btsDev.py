@@ -17,6 +17,7 @@ from demkit.components.dev.device import Device
 from data.evTypes import evTypes
 
 import math
+import datetime
 
 class BtsDev(Device):	
 	def __init__(self,  name,  host):
@@ -211,6 +212,83 @@ class BtsDev(Device):
 		return r
 
 
+	def startSession(self, departureOffset: int, requiredEnergy: float):
+		startTime = self.host.time() + self.timeOffset
+		endTime = startTime + departureOffset
+
+		if departureOffset <= 0:
+			return (False, "Departure must be in the future!")
+		if requiredEnergy <= 0:
+			return (False, "Required energy must be positive!")
+		if self.available:
+			return (False, "A session is already active!")
+
+		successFlag, msg = self.addJob(startTime, endTime, requiredEnergy)
+
+		if successFlag:
+			msg = "Starting session until " + str(datetime.datetime.fromtimestamp(endTime - self.timeOffset))
+			self.logMsg(msg)
+
+		return (successFlag, msg)
+
+	def setDeparture(self, departureTime: int):
+		self.lockState.acquire()
+
+		if self.available:
+			job = self.currentJob
+		elif self.currentJobIdx+1 < len(self.jobs):
+			job = self.jobs[self.currentJobIdx+1][1]
+		else:
+			self.lockState.release()
+			return (False, "No session to update!")
+
+		if departureTime <= max(self.host.time(), job['startTime']):
+			self.lockState.release()
+			return (False, "Departure must be after the start of the session and in the future!")
+
+		job['endTime'] = departureTime
+		if self.available:
+			self.timeTillDeadline = departureTime - self.host.time()
+
+		self.lockState.release()
+
+		if self.smartOperation and self.controller is not None and self.available:
+			self.zCast(self.controller, 'triggerEvent', "stateUpdate")
+
+		return (True, "")
+
+	def setMaxChargingPower(self, power: float):
+		if power <= 0:
+			return (False, "Charging power must be positive!")
+
+		self.lockState.acquire()
+
+		self.chargingPowers = [0.0, float(power)]
+		self.evseChargingPowers = list(self.chargingPowers)
+		for job in self.jobs[self.currentJobIdx+1:]:
+			job[1]['chargingPowers'] = self.chargingPowers
+
+		self.lockState.release()
+
+		if self.smartOperation and self.controller is not None and self.available:
+			self.zCast(self.controller, 'triggerEvent', "stateUpdate")
+
+		return (True, "")
+
+	def endSession(self):
+		self.lockState.acquire()
+
+		if not self.available:
+			self.lockState.release()
+			return (False, "No active session!")
+
+		self.available = False
+		self.currentJob['endTime'] = self.host.time()
+		self.timeTillDeadline = 0
+
+		self.lockState.release()
+		return (True, "")
+
 #### LOCAL HELPERS
 	def addJob(self, startTime, endTime, charge, ev=None):
 		self.lockState.acquire()
@@ -258,6 +336,8 @@ class BtsDev(Device):
 
 		self.lockState.release()
 
+		return (not errorFlag, "Inconsistent job specification!" if errorFlag else "")
+
 
 	def setProperties(self, evType):
 		if evType is not None:
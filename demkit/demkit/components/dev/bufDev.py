@@ -31,6 +31,11 @@ class BufDev(Device):
 
 		self.targetSoC = None
 
+		# Manual control, overrides the control modes below while set
+		self.powerSetpoint = None
+		self.schedule = [] # list of [time, power], sorted by time
+		self.scheduleEnd = None
+
 		self.prevConsumption = 0.0
 		
 		#define 2 entries for a continuous range
@@ -196,7 +201,15 @@ class BufDev(Device):
 		self.prunePlan()
 		oldConsumption = copy.deepcopy(self.consumption)
 
-		if self.parent is not None:
+		setpoint = self.activeSetpoint(self.host.time())
+		if setpoint is not None:
+			# Manual control, the checks below keep the SoC within bounds
+			self.lockState.acquire()
+			for c in self.commodities:
+				power = max(self.chargingPowers[0], min(setpoint, self.chargingPowers[-1]))
+				self.consumption[c] = complex(power / len(self.commodities), 0.0)
+
+		elif self.parent is not None:
 			# Online control / fill level mode
 			# Perform online control algorithms
 			self.onlineControl()
@@ -341,6 +354,7 @@ class BufDev(Device):
 		self.lockState.acquire()
 		# Populate the result dict
 		r['soc'] = self.soc
+		r['initialSoC'] = self.initialSoC
 		r['cop'] = self.cop
 		r['capacity'] = self.capacity
 		r['chargingPowers'] = self.chargingPowers
@@ -350,6 +364,9 @@ class BufDev(Device):
 		r['discrete'] = self.discrete
 		r['useInefficiency'] = self.useInefficiency
 		r['targetSoC'] = int(self.targetSoC) if self.targetSoC is not None else None
+		r['powerSetpoint'] = self.powerSetpoint
+		r['schedule'] = [list(entry) for entry in self.schedule]
+		r['scheduleEnd'] = self.scheduleEnd
 
 		r['restrictedCapacity'] = self.restrictedCapacity
 		r['restrictedChargingPowers'] = self.restrictedChargingPowers
@@ -360,6 +377,46 @@ class BufDev(Device):
 		return r
 
 
+#### Manual control
+	def setPowerSetpoint(self, power):
+		self.lockState.acquire()
+		self.powerSetpoint = None if power is None else float(power)
+		self.schedule = []
+		self.scheduleEnd = None
+		self.lockState.release()
+
+		return (True, "")
+
+	def setSchedule(self, entries, endTime):
+		times = [entry[0] for entry in entries]
+		if len(entries) == 0 or times != sorted(times) or len(set(times)) != len(times):
+			return (False, "Schedule entries must have increasing times!")
+		if endTime <= times[-1]:
+			return (False, "Schedule must end after its last entry!")
+
+		self.lockState.acquire()
+		self.schedule = [[int(time), float(power)] for time, power in entries]
+		self.scheduleEnd = int(endTime)
+		self.powerSetpoint = None
+		self.lockState.release()
+
+		self.logMsg("Following a schedule of " + str(len(entries)) + " entries until " + str(endTime))
+		return (True, "")
+
+	def activeSetpoint(self, time):
+		# The schedule entry in effect, or the setpoint when no schedule is running
+		if self.scheduleEnd is not None and time < self.scheduleEnd:
+			power = None
+			for entryTime, entryPower in self.schedule:
+				if entryTime <= time:
+					power = entryPower
+				else:
+					break
+			if power is not None:
+				return power
+
+		return self.powerSetpoint
+
 #### Online control system
 	def localControl(self, time):
 		self.timeTick(time)
@@ -41,6 +41,15 @@ class SolarPanelDev(CurtDev):
 		# Apply local control, e.g. droop control based on voltage
 		self.enableLocalControl = False
 
+		# Curtailment, e.g. to limit export to the grid
+		self.curtailLevel = None			# Share of the potential production that may be produced, 0 to 1
+		self.curtailLimit = None			# Maximum production in W
+
+		# Produced energy in Wh, today is in local time
+		self.energyToday = 0.0
+		self.energyTotal = 0.0
+		self.energyDay = None
+
 	def startup(self):
 		CurtDev.startup(self)
 
@@ -69,6 +78,11 @@ class SolarPanelDev(CurtDev):
 	def timeTick(self, time, deltatime=0):
 		CurtDev.timeTick(self, time)
 
+		self.lockState.acquire()
+		self.applyCurtailment()
+		self.accountEnergy(time)
+		self.lockState.release()
+
 		if self.enableLocalControl:
 			self.localControl(time)
 
@@ -84,6 +98,10 @@ class SolarPanelDev(CurtDev):
 		r['azimuth'] = self.azimuth
 		r['onOffDevice'] = self.onOffDevice
 		r['originalConsumption'] = self.originalConsumption
+		r['curtailLevel'] = self.curtailLevel
+		r['curtailLimit'] = self.curtailLimit
+		r['energyToday'] = self.energyToday
+		r['energyTotal'] = self.energyTotal
 		if self.wattPeak is not None:
 			r['wattPeak'] = self.wattPeak
 		if self.panels is not None:
@@ -92,7 +110,46 @@ class SolarPanelDev(CurtDev):
 
 		return r
 
+	def setCurtailment(self, level=None, limit=None):
+		if level is not None and (level < 0 or level > 1):
+			return (False, "Curtailment level must be between 0 and 1!")
+		if limit is not None and limit < 0:
+			return (False, "Curtailment limit must not be negative!")
+
+		self.lockState.acquire()
+		self.curtailLevel = None if level is None else float(level)
+		self.curtailLimit = None if limit is None else float(limit)
+		self.lockState.release()
+
+		return (True, "")
+
 # HELPERS
+	def applyCurtailment(self):
+		# Production is negative consumption, curtailing scales it towards zero
+		for c in self.commodities:
+			production = -self.consumption[c].real
+			if production <= 0:
+				continue
+
+			allowed = production
+			if self.curtailLevel is not None:
+				allowed *= self.curtailLevel
+			if self.curtailLimit is not None:
+				allowed = min(allowed, self.curtailLimit / len(self.commodities))
+
+			self.consumption[c] = self.consumption[c] * (allowed / production)
+
+	def accountEnergy(self, time):
+		day = self.host.timeObject(time).astimezone(self.host.timezone).date()
+		if day != self.energyDay:
+			self.energyDay = day
+			self.energyToday = 0.0
+
+		production = -sum(self.consumption[c].real for c in self.commodities)
+		energy = max(0.0, production) * (self.host.timeBase / 3600.0)
+		self.energyToday += energy
+		self.energyTotal += energy
+
 	def calculateProduction(self, time = None):
 		# Do something with the irradiation here
 		if time is None:
@@ -36,6 +36,11 @@ class ThermalBufConvDev(HeatSourceDev, BufConvDev):
 
 		self.producingTemperatures = [0, 60.0]	# Output power of the source
 
+		# Remote control, scales the rated producing powers
+		self.enabled = True
+		self.powerLevel = 1.0
+		self.ratedPowers = None
+
 	def startup(self):
 		BufConvDev.startup(self)
 
@@ -111,6 +116,9 @@ class ThermalBufConvDev(HeatSourceDev, BufConvDev):
 		else:
 			if not self.discrete:
 				self.heatProduction = -1 * self.consumption['HEAT']
+				# Only clamp once the output is limited remotely, so unlimited sources behave as before
+				if self.ratedPowers is not None:
+					self.heatProduction = min(self.producingPowers[-1], max(self.heatProduction, self.producingPowers[0]))
 			else:
 				self.heatProduction = 0
 				demand = -1 * self.consumption['HEAT']
@@ -185,6 +193,45 @@ class ThermalBufConvDev(HeatSourceDev, BufConvDev):
 	def shutdown(self):
 		pass
 
+#### REMOTE CONTROL
+	def setEnabled(self, enabled: bool):
+		self.lockState.acquire()
+		self.enabled = bool(enabled)
+		self.applyPowerLimits()
+		self.lockState.release()
+
+		return (True, "")
+
+	def setPowerLevel(self, level: float):
+		if level < 0 or level > 1:
+			return (False, "Power level must be between 0 and 1!")
+
+		self.lockState.acquire()
+		self.powerLevel = float(level)
+		self.applyPowerLimits()
+		self.lockState.release()
+
+		return (True, "")
+
+	def setSupplyTemperature(self, temperature: float):
+		if temperature <= self.producingTemperatures[0]:
+			return (False, "Supply temperature must be above " + str(self.producingTemperatures[0]) + "!")
+
+		self.lockState.acquire()
+		self.producingTemperatures[-1] = float(temperature)
+		self.temperature = float(temperature)
+		self.lockState.release()
+
+		return (True, "")
+
+	def applyPowerLimits(self):
+		# The composer sets the producing powers after construction, so the rated ones are kept on first use
+		if self.ratedPowers is None:
+			self.ratedPowers = list(self.producingPowers)
+
+		scale = self.powerLevel if self.enabled else 0.0
+		self.producingPowers = [power * scale for power in self.ratedPowers]
+
 #### INTERFACING
 	def getProperties(self):
 		r = BufConvDev.getProperties(self) 	# Get the properties of the overall Device class, which already includes global properties
@@ -220,6 +220,8 @@ class ZoneDev2R2C(ThermalDevice):
 		r = ThermalDevice.getProperties(self) 	# Get the properties of the overall Device class, which already includes global properties
 		self.lockState.acquire()
 		r['valveHeat'] = self.valveHeat
+		r['maxHeat'] = self.maxHeat
+		r['minHeat'] = self.minHeat
 		self.lockState.release()
 		return r
 
@@ -50,15 +50,20 @@ class TsDev(Device):
 		self.available = False
 		self.jobProgress = 0
 
+		# Profile of jobs without their own, and the time base job profiles are given in
+		self.defaultProfile = self.profile
+		self.profileTimeBase = self.timeBase
+
 		# TouchTable tests
 		self.timeTillDeadline = 0
 
 		#other
 		self.jobs = []
+		self.nextJobId = 0 # IDs stay with their job, cancelled jobs are kept in the list
 
 		# persistence
 		if self.persistence != None:
-			self.watchlist += ["jobs", "currentJobIdx", "currentJob", "available", "jobProgress"]
+			self.watchlist += ["jobs", "nextJobId", "currentJobIdx", "currentJob", "available", "jobProgress"]
 			self.persistence.setWatchlist(self.watchlist)
 
 	def startup(self):
@@ -80,9 +85,12 @@ class TsDev(Device):
 		assert(len(self.commodities)==1) #
 		self.commodity = self.commodities[0]
 
+		# Profiles of jobs are given in the time base the device was composed with
+		self.profileTimeBase = self.timeBase
 		if self.host.timeBase != self.timeBase:
 			self.profile = util.helpers.interpolatetb(self.profile, self.timeBase, self.host.timeBase)
 			self.timeBase = self.host.timeBase
+		self.defaultProfile = self.profile
 
 		self.lockState.release()
 
@@ -99,16 +107,22 @@ class TsDev(Device):
 			assert(self.host.timeBase % self.timeBase == 0)
 			self.jobProgress += math.ceil(self.host.timeBase / self.timeBase)
 			self.jobProgress = min(self.jobProgress, len(self.profile))
+			self.currentJob['energy'] = self.currentJob.get('energy', 0.0) + self.consumption[self.commodity].real * self.host.timeBase / 3600.0
 
 
 		#now check if we need to update the state
 		if not self.available:
+			#cancelled jobs never start
+			while self.currentJobIdx+1 < len(self.jobs) and self.jobs[self.currentJobIdx+1][1].get('cancelled', False):
+				self.currentJobIdx += 1
+
 			if self.currentJobIdx+1 < len(self.jobs):
 				if self.jobs[self.currentJobIdx+1][1]['startTime'] <= self.host.time():
 					#new job to be triggered:
 					self.currentJobIdx += 1
 					self.currentJob = self.jobs[self.currentJobIdx][1]
 					self.jobProgress = 0
+					self.profile = self.currentJob.get('profile', self.defaultProfile)
 
 					self.logMsg("New job started at " + str(datetime.datetime.fromtimestamp(self.currentJob['startTime'])))
 
@@ -128,6 +142,7 @@ class TsDev(Device):
 
 			if self.jobProgress == len(self.profile) or self.currentJob['endTime'] <= self.host.time():
 				self.available = False
+				self.currentJob['finishedAt'] = self.host.time()
 
 		self.lockState.release()
 
@@ -139,8 +154,12 @@ class TsDev(Device):
 		#NOTE: Currently no preemption is supported, but a forced shutdown is!
 		if self.available and self.jobProgress < len(self.profile):
 
+			#a start time chosen through the API overrules the planning
+			if self.jobProgress == 0 and self.host.time() < self.currentJob.get('startAt', 0):
+				self.consumption[self.commodity] = complex(0.0, 0.0)
+
 			#first check if we made progress already. If so, let it run as we cannot preemt the device
-			if self.jobProgress > 0 and self.jobProgress < len(self.profile):
+			elif self.jobProgress > 0 and self.jobProgress < len(self.profile):
 				if self.strictComfort:
 					self.consumption[self.commodity] = self.profile[self.jobProgress]
 				elif self.smartOperation and c in self.plan and len(self.plan[c]) > 0:
@@ -167,6 +186,9 @@ class TsDev(Device):
 		else:
 			self.consumption[self.commodity] = complex(0.0, 0.0)
 
+		if self.available and self.jobProgress == 0 and self.consumption[self.commodity].real > 0 and 'startedAt' not in self.currentJob:
+			self.currentJob['startedAt'] = self.host.time()
+
 		self.lockState.release()
 
 	def logStats(self, time):
@@ -211,13 +233,25 @@ class TsDev(Device):
 		r['currentJobIdx'] = self.currentJobIdx
 		r['currentJob'] = self.currentJob
 		r['jobProgress'] = self.jobProgress
+		r['plannedStartTime'] = self.plannedStartTime()
 		self.lockState.release()
 
 		return r
 
 
+	def plannedStartTime(self):
+		# Start of the current job as planned by the controller, until the job starts
+		if not self.available or self.jobProgress > 0 or 'startAt' in self.currentJob:
+			return None
+
+		for (time, power) in self.plan.get(self.commodities[0], []):
+			if power.real >= 1:
+				return time
+
+		return None
+
 #### LOCAL HELPERS
-	def addJob(self, startTime, endTime):
+	def addJob(self, startTime, endTime, profile=None, startAt=None):
 		self.lockState.acquire()
 		errorFlag = False
 		errorMsg = ""
@@ -229,24 +263,41 @@ class TsDev(Device):
 		assert(startTime < endTime)
 		# assert(endTime >= startTime + len(self.profile)*self.timeBase)
 
+		if profile is not None and self.profileTimeBase != self.timeBase:
+			profile = util.helpers.interpolatetb(profile, self.profileTimeBase, self.timeBase)
+		jobLength = len(profile if profile is not None else self.profile) * self.timeBase
 
-		if endTime < startTime + len(self.profile) * self.timeBase:
+		if endTime < startTime + jobLength:
 			errorFlag = True
-			errorMsg = f"Minimum job length of {len(self.profile)*self.timeBase} not met! Not adding job."
+			errorMsg = f"Minimum job length of {jobLength} not met! Not adding job."
 			self.logWarning(errorMsg)
 
 		j['startTime'] = startTime
 		j['endTime'] = min(endTime,  startTime + 24*3600)
 
-		if len(self.jobs) > 0 and j['startTime'] <= self.jobs[-1][1]['endTime']:
+		if profile is not None:
+			j['profile'] = profile
+
+		if startAt is not None:
+			startAt -= self.timeOffset
+			if startAt < j['startTime'] or startAt + jobLength > j['endTime']:
+				errorFlag = True
+				errorMsg = "Start time does not leave the job enough time before its deadline! Not adding job."
+				self.logWarning(errorMsg)
+			j['startAt'] = startAt
+
+		pending = [job for (_, job) in self.jobs if not job.get('cancelled', False)]
+		if len(pending) > 0 and j['startTime'] <= pending[-1]['endTime']:
 			errorFlag = True
 			errorMsg = "Job overlaps with previous job! Not adding job."
 			self.logWarning(errorMsg)
 
-		job = (len(self.jobs),  dict(j))
+		job = (self.nextJobId,  dict(j))
 
 		if not errorFlag:
 			self.jobs.append(job)
+			self.nextJobId += 1
+			errorMsg = job[0] # callers get the ID of the new job instead of a message
 
 		self.lockState.release()
 
@@ -262,36 +313,79 @@ class TsDev(Device):
 		successFlag, msg = self.addJob(startTime, endTime)
 
 		if successFlag:
-			msg = "Scheduling job from " + str(startTimeDate) + " to " + str(endTimeDate)
-			self.logMsg(msg)
+			self.logMsg("Scheduling job " + str(msg) + " from " + str(startTimeDate) + " to " + str(endTimeDate))
 			return (True, msg)
 		else:
 			self.logWarning("Failed to schedule job from " + str(startTimeDate) + " to " + str(endTimeDate))
 			return (False, msg)
 
+	def addFlexibleJob(self, earliestStart: int, latestEnd: int, profile: list = None, startAt: int = None):
+		# The job may run anywhere between earliestStart and latestEnd. Without startAt the
+		# controller of the device plans the start, or the job starts right away without one.
+		if earliestStart >= latestEnd:
+			return (False, "Earliest start must be before the latest end!")
+		if profile is not None:
+			if len(profile) == 0:
+				return (False, "Profile must not be empty!")
+			profile = [complex(item["re"], item["im"]) for item in profile]
+		if startAt is not None:
+			startAt += self.timeOffset
+
+		successFlag, result = self.addJob(earliestStart + self.timeOffset, latestEnd + self.timeOffset, profile, startAt)
+
+		if successFlag:
+			self.logMsg(f"Added flexible job {result} between {earliestStart} and {latestEnd}")
+		return (successFlag, result)
+
+	def setJobStart(self, idx: int, startAt: int = None):
+		# Picks the start of a job that has not started yet, None hands it back to the controller
+		self.lockState.acquire()
+		job = next((job for (jobIdx, job) in self.jobs if jobIdx == idx), None)
+		if job is None:
+			self.lockState.release()
+			return (False, "Job not found!")
+		if 'startedAt' in job:
+			self.lockState.release()
+			return (False, "Job already started!")
+
+		if startAt is None:
+			job.pop('startAt', None)
+		else:
+			jobLength = len(job.get('profile', self.defaultProfile)) * self.timeBase
+			if startAt < job['startTime'] or startAt + jobLength > job['endTime']:
+				self.lockState.release()
+				return (False, "Start time does not leave the job enough time before its deadline!")
+			job['startAt'] = startAt
+
+		self.lockState.release()
+		return (True, "")
+
 	def cancelJob(self, idx: int):
-		msg = ""
-		errorFlag = False
 		self.logMsg(f"Cancelling job {idx}")
 		self.lockState.acquire()
-		if len(self.jobs) == 0:
-			msg = "No jobs scheduled!"
-			self.logWarning(msg)
-			errorFlag = True
-
-		if len(self.jobs) > 0 and idx < len(self.jobs):
-			self.jobs.pop(idx)
-		else:
+		job = next((job for (jobIdx, job) in self.jobs if jobIdx == idx), None)
+		if job is None:
 			msg = "Job not found!"
-			self.logWarning(msg)
-			errorFlag = True
+		elif job.get('cancelled', False) or 'finishedAt' in job:
+			msg = "Job already finished!"
+		else:
+			msg = ""
+			job['cancelled'] = True
+			# a running job stops right away
+			if self.available and job is self.currentJob:
+				self.available = False
+				self.jobProgress = 0
 
+		if msg:
+			self.logWarning(msg)
 		self.lockState.release()
 
-		return (not errorFlag, msg)
+		return (not msg, msg)
 
 	def forceShutdown(self):
 		self.lockState.acquire()
+		if self.available:
+			self.currentJob['cancelled'] = True
 		self.available = False
 		self.jobProgress = 0
 		self.lockState.release()
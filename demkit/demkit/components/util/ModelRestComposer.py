@@ -46,7 +46,7 @@ class ModelRestComposer:
                 self.entities.remove(entity)
                 if isinstance(entity, MeterEntity):
                     self.meters.remove(entity)
-                elif isinstance(entity, RestHost):
+                elif isinstance(entity, HostEntity):
                     self.host = None
                 return True
         else:
@@ -10,6 +10,7 @@ from util.entities.HouseEntities import (
     BatteryEntity,
     CurtEntity,
     DhwEntity,
+    EvEntity,
     HeatPumpEntity,
     HeatSourceEntity,
     MeterEntity,
@@ -30,6 +31,7 @@ entity_types: dict[str, ModelRestEntity] = {
     "solar_panel": SolarPanelEntity,
     "timeshiftable": TimeShiftableEntity,
     "battery": BatteryEntity,
+    "ev": EvEntity,
     "zone": ZoneEntity,
     "thermostat": ThermostatEntity,
     "dhw": DhwEntity,
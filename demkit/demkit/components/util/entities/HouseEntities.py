@@ -1,5 +1,6 @@
 from typing import Self
 from ctrl.thermal.thermostat import Thermostat
+from dev.btsDev import BtsDev
 from dev.bufDev import BufDev
 from dev.curtDev import CurtDev
 from dev.electricity.solarPanelDev import SolarPanelDev
@@ -87,9 +88,31 @@ import util.alpg as alpg
 
 
 class SolarPanelEntity(ModelRestEntity):
-    def __init__(self, name: str):
+    def __init__(
+        self,
+        name: str,
+        size: float = None,
+        efficiency: float = None,
+        inclination: float = None,
+        azimuth: float = None,
+    ):
         super().__init__(name)
 
+        if size is not None and size <= 0:
+            raise ValueError("size must be positive")
+        if efficiency is not None and not 0 < efficiency <= 100:
+            raise ValueError("efficiency must be between 0 and 100")
+        if inclination is not None and not 0 <= inclination <= 90:
+            raise ValueError("inclination must be between 0 and 90")
+        if azimuth is not None and not 0 <= azimuth < 360:
+            raise ValueError("azimuth must be between 0 and 360")
+
+        # Unset parameters are taken from the PV settings of the house
+        self.size = size
+        self.efficiency = efficiency
+        self.inclination = inclination
+        self.azimuth = azimuth
+
     def load(
         self,
         host: HostEntity,
@@ -114,6 +137,15 @@ class SolarPanelEntity(ModelRestEntity):
         pv.azimuth = pv_specs[idx][1]  # in degrees, 0=north, 90 is east
         pv.inclination = pv_specs[idx][0]  # angle
 
+        if self.size is not None:
+            pv.size = self.size
+        if self.efficiency is not None:
+            pv.efficiency = self.efficiency
+        if self.azimuth is not None:
+            pv.azimuth = self.azimuth
+        if self.inclination is not None:
+            pv.inclination = self.inclination
+
         pv.strictComfort = not params.get("useIslanding", False)
 
         sm = next(
@@ -173,9 +205,43 @@ class BatteryEntity(ModelRestEntity):
     def __init__(
         self,
         name: str,
+        capacity: float = None,
+        initialSoC: float = None,
+        chargingPowers: list[float] = None,
+        chargingEfficiency: list[float] = None,
+        selfConsumption: float = None,
+        discrete: bool = None,
     ):
         super().__init__(name)
 
+        if capacity is not None and capacity <= 0:
+            raise ValueError("capacity must be positive")
+        if initialSoC is not None and (
+            initialSoC < 0 or (capacity is not None and initialSoC > capacity)
+        ):
+            raise ValueError("initialSoC must be between 0 and the capacity")
+        if chargingPowers is not None and len(chargingPowers) < 2:
+            raise ValueError("chargingPowers needs at least two powers")
+        if chargingEfficiency is not None:
+            if chargingPowers is None or len(chargingEfficiency) != len(
+                chargingPowers
+            ):
+                raise ValueError(
+                    "chargingEfficiency needs one efficiency per charging power"
+                )
+            if any(eff <= 0 or eff > 1 for eff in chargingEfficiency):
+                raise ValueError("chargingEfficiency must be between 0 and 1")
+        if selfConsumption is not None and selfConsumption < 0:
+            raise ValueError("selfConsumption must not be negative")
+
+        # Unset parameters are taken from the battery settings of the house
+        self.capacity = capacity
+        self.initialSoC = initialSoC
+        self.chargingPowers = chargingPowers
+        self.chargingEfficiency = chargingEfficiency
+        self.selfConsumption = selfConsumption
+        self.discrete = discrete
+
     def load(
         self,
         host: HostEntity,
@@ -204,6 +270,20 @@ class BatteryEntity(ModelRestEntity):
         buf.capacity = bat_specs[idx][1]
         buf.initialSoC = bat_specs[idx][2]
 
+        if self.chargingPowers is not None:
+            # efficiencies belong to the powers in the given order, sort them together
+            efficiency = self.chargingEfficiency or [1] * len(self.chargingPowers)
+            pairs = sorted(zip(self.chargingPowers, efficiency))
+            buf.chargingPowers = [power for power, _ in pairs]
+            if self.chargingEfficiency is not None:
+                buf.chargingEfficiency = [eff for _, eff in pairs]
+        if self.capacity is not None:
+            buf.capacity = self.capacity
+        if self.initialSoC is not None:
+            buf.initialSoC = self.initialSoC
+        if self.selfConsumption is not None:
+            buf.lossOverTime = self.selfConsumption
+
         buf.soc = buf.initialSoC
 
         sm = next(
@@ -216,7 +296,7 @@ class BatteryEntity(ModelRestEntity):
             None,
         )
         buf.commodities = sm.commodities
-        buf.discrete = False
+        buf.discrete = bool(self.discrete)
 
         # Marks to spawn events
         buf.highMark = buf.capacity * 0.8
@@ -230,6 +310,59 @@ class BatteryEntity(ModelRestEntity):
             buf.meter = sm.inner
 
 
+class EvEntity(ModelRestEntity):
+    def __init__(
+        self,
+        name: str,
+        capacity: float = 40000,
+        maxChargePower: float = 7400,
+        arrivalTime: int = None,
+        departureTime: int = None,
+        requiredEnergy: float = None,
+    ):
+        super().__init__(name)
+
+        if capacity <= 0 or maxChargePower <= 0:
+            raise ValueError("capacity and maxChargePower must be positive")
+
+        session = [arrivalTime, departureTime, requiredEnergy]
+        if any(item is not None for item in session) and None in session:
+            raise ValueError(
+                "arrivalTime, departureTime and requiredEnergy must be given together"
+            )
+
+        self.capacity = capacity
+        self.maxChargePower = maxChargePower
+        self.arrivalTime = arrivalTime
+        self.departureTime = departureTime
+        self.requiredEnergy = requiredEnergy
+
+    def load(
+        self,
+        host: HostEntity,
+        meters: list[MeterEntity],
+        params: dict[str, any],
+        entities: list[ModelRestEntity],
+    ):
+        super().load()
+        ev = BtsDev(self.name, host.inner)
+
+        ev.capacity = self.capacity
+        ev.chargingPowers = [0.0, self.maxChargePower]
+        ev.discrete = False
+        ev.strictComfort = not params.get("useIslanding", False)
+
+        if self.arrivalTime is not None:
+            ev.addJob(self.arrivalTime, self.departureTime, self.requiredEnergy)
+
+        for entity in meters:
+            if "ELECTRICITY" in entity.commodities:
+                entity.inner.addDevice(ev)
+                break
+        else:
+            raise ValueError("No meter found with ELECTRICITY commodity")
+
+
 class ZoneEntity(ModelRestEntity):
     def __init__(
         self,
@@ -239,14 +372,25 @@ class ZoneEntity(ModelRestEntity):
         cFloor: float = 5100 * 3600,
         cZone: float = 21100 * 3600,
         initialTemperature: float = 18.5,
+        maxHeat: float = 7500,
+        minHeat: float = -7500,
     ):
         super().__init__(name)
+
+        if maxHeat < 0 or minHeat > 0:
+            raise ValueError(
+                "maxHeat must not be negative and minHeat must not be positive"
+            )
+
         self.inner: ZoneDev2R2C = None
         self.rFloor = rFloor
         self.rEnvelope = rEnvelope
         self.cFloor = cFloor
         self.cZone = cZone
         self.initialTemperature = initialTemperature
+        # Capacity of the heat emitter of the zone, in W
+        self.maxHeat = maxHeat
+        self.minHeat = minHeat
 
     def load(
         self,
@@ -285,6 +429,8 @@ class ZoneEntity(ModelRestEntity):
         zone.cFloor = self.cFloor  # J/K
         zone.cZone = self.cZone  # J/K
         zone.initialTemperature = self.initialTemperature  # C
+        zone.maxHeat = self.maxHeat  # W
+        zone.minHeat = self.minHeat  # W
 
         zone.gainFile = params.get("gainFile")
         zone.ventilationFile = params.get("ventilationFile")
@@ -305,9 +451,12 @@ class ThermostatEntity(ModelRestEntity):
         temperatureMax: float = 23.0,
         temperatureDeadband: list[float] = [-0.1, 0.0, 0.5, 0.6],
         preheatingTime: float = 3600,
+        zone: str = None,
     ):
         super().__init__(name)
         self.inner: Thermostat = None
+        # Name of the zone to control, the first zone of the house if not set
+        self.zone = zone
         self.temperatureSetpointHeating = temperatureSetpointHeating
         self.temperatureSetpointCooling = temperatureSetpointCooling
         self.temperatureMin = temperatureMin
@@ -324,11 +473,18 @@ class ThermostatEntity(ModelRestEntity):
     ):
         super().load()
         zone = next(
-            (entity.inner for entity in entities if isinstance(entity, ZoneEntity)),
+            (
+                entity.inner
+                for entity in entities
+                if isinstance(entity, ZoneEntity)
+                and (self.zone is None or entity.name == self.zone)
+            ),
             None,
         )
         if zone is None:
-            raise ValueError("No zone found")
+            raise ValueError(
+                f"No zone found named {self.zone}" if self.zone else "No zone found"
+            )
 
         thermostat = Thermostat(self.name, zone, None, host.inner)
         thermostat.temperatureSetpointHeating = self.temperatureSetpointHeating
@@ -426,26 +582,23 @@ class HeatSourceEntity(ModelRestEntity):
 
         self.inner = heatsource
 
-        zone = next(
-            (entity.inner for entity in entities if isinstance(entity, ZoneEntity)),
-            None,
-        )
-        if zone is None:
+        zones = [
+            entity.inner for entity in entities if isinstance(entity, ZoneEntity)
+        ]
+        if not zones:
             raise ValueError("No zone found")
 
-        thermostat = next(
-            (
-                entity.inner
-                for entity in entities
-                if isinstance(entity, ThermostatEntity)
-            ),
-            None,
-        )
-        if thermostat is None:
+        thermostats = [
+            entity.inner for entity in entities if isinstance(entity, ThermostatEntity)
+        ]
+        if not thermostats:
             raise ValueError("No thermostat found")
 
-        heatsource.addZone(zone)
-        heatsource.addThermostat(thermostat)
+        # The heat is split over the zones by the demand of their thermostats
+        for zone in zones:
+            heatsource.addZone(zone)
+        for thermostat in thermostats:
+            heatsource.addThermostat(thermostat)
 
         sm = next(
             (
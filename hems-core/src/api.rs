@@ -1,18 +1,9 @@
-use actix_web::get;
-
+pub mod debug;
 pub mod demkit;
 pub mod ha;
 pub mod docs;
-
-#[utoipa::path(
-    get,
-    description = "Health check endpoint",
-    path = "/healthz",
-    responses(
-        (status = OK, description = "Health check OK"),
-    )
-)]
-#[get("/healthz")]
-pub async fn health() -> impl actix_web::Responder {
-    actix_web::HttpResponse::Ok().body("OK")
-}
\ No newline at end of file
+pub mod error;
+pub mod export;
+pub mod health;
+pub mod history;
+pub mod upstream;
@@ -0,0 +1,22 @@
+use actix_web::{get, http::StatusCode, web, HttpResponse, Responder};
+
+use super::error::ErrorBody;
+use super::ha::backend::{HomeAssistantBackend, HomeAssistantCall};
+
+#[utoipa::path(
+    get,
+    description = "Latest 1000 calls received by the in-memory Home Assistant stand-in, oldest first, to check what a scenario sent to Home Assistant.",
+    path = "/debug/home-assistant/calls",
+    responses(
+        (status = OK, description = "Recorded calls", body = Vec<HomeAssistantCall>),
+        (status = NOT_FOUND, description = "Home Assistant is not the in-memory stand-in", body = ErrorBody),
+    )
+)]
+#[get("/debug/home-assistant/calls")]
+pub async fn home_assistant_calls(ha: web::Data<dyn HomeAssistantBackend>) -> impl Responder {
+    match ha.calls() {
+        Some(calls) => HttpResponse::Ok().json(calls),
+        None => ErrorBody::new("not_found", "Only the in-memory Home Assistant records its calls", None)
+            .response(StatusCode::NOT_FOUND),
+    }
+}
@@ -1,13 +1,19 @@
-use std::{num::ParseFloatError, str::FromStr, sync::OnceLock};
+use std::str::FromStr;
 
-use num_complex::{Complex, ParseComplexError};
-use once_cell::sync::Lazy;
-use reqwest;
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use num_complex::Complex;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+use super::error::{request_status, ErrorBody};
+use super::upstream::UpstreamError;
+
 
 pub mod battery;
+pub mod ev;
+pub mod heatpump;
+pub mod heating_schedule;
+pub mod dhw;
 pub mod meter;
 pub mod solar;
 pub mod thermal;
@@ -16,16 +22,12 @@ pub mod devices;
 pub mod ha_entity;
 pub mod env;
 pub mod sim;
+pub mod registry;
+pub mod backend;
+pub mod http;
+pub mod fake;
 
-static BASE_URL: Lazy<String> = Lazy::new(|| {
-    std::env::var("DEMKIT_URL").expect("DEMKIT_URL is not set")
-});
-
-static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
-
-pub fn init() -> reqwest::Client {
-    reqwest::Client::new()
-}
+use backend::DemkitBackend;
 
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct Measurement {
@@ -48,51 +50,164 @@ pub struct Commodities {
     pub electricity: Option<Commodity>,
     #[serde(rename = "HEAT")]
     pub heat: Option<Commodity>,
+    #[serde(rename = "NATGAS")]
+    pub natgas: Option<Commodity>,
+}
+
+impl Commodities {
+    /// Electricity consumption of `entity`, which must report the commodity
+    pub fn electricity(&self, entity: &str) -> Result<Complex<f64>, ApiError> {
+        match &self.electricity {
+            Some(commodity) => parse_complex_str(commodity),
+            None => Err(ApiError::MissingCommodity(entity.to_string(), "ELECTRICITY")),
+        }
+    }
+
+    /// Heat consumption of `entity`, which must report the commodity
+    pub fn heat(&self, entity: &str) -> Result<Complex<f64>, ApiError> {
+        match &self.heat {
+            Some(commodity) => parse_complex_str(commodity),
+            None => Err(ApiError::MissingCommodity(entity.to_string(), "HEAT")),
+        }
+    }
+
+    /// Natural gas consumption of `entity` as chemical power, which must report the commodity
+    pub fn natgas(&self, entity: &str) -> Result<Complex<f64>, ApiError> {
+        match &self.natgas {
+            Some(commodity) => parse_complex_str(commodity),
+            None => Err(ApiError::MissingCommodity(entity.to_string(), "NATGAS")),
+        }
+    }
 }
 
+#[allow(clippy::enum_variant_names)]
 #[derive(thiserror::Error, Debug)]
 pub enum ApiError {
     #[error("Request failed: {0}")]
     ReqwestError(#[from] reqwest::Error),
     #[error("Serde error: {0}")]
     SerdeError(#[from] serde_json::Error),
-    #[error("Parse error: {0}")]
-    ParseError(#[from] ParseComplexError<ParseFloatError>),
+    #[error("Malformed complex number: {0}")]
+    MalformedComplex(String),
+    #[error("{0} does not report the {1} commodity")]
+    MissingCommodity(String, &'static str),
     #[error("DEMKIT API error: {0}")]
     DemkitError(String),
+    #[error("House conflict: {0}")]
+    HouseConflict(String),
+    #[error("Invalid request: {0}")]
+    Validation(String),
+    #[error("Unknown entity: {0}")]
+    UnknownEntity(String),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Circuit open: {0}")]
+    CircuitOpen(String),
 }
 
-fn parse_complex_str(
-    input: &Commodity,
-) -> Result<Complex<f64>, ParseComplexError<ParseFloatError>> {
-    match input {
-        Commodity::Complex(input) => {
-            Complex::from_str(&input[2..].replace("(", "").replace(")", ""))
+impl From<UpstreamError> for ApiError {
+    fn from(error: UpstreamError) -> Self {
+        match error {
+            UpstreamError::Request(e) => ApiError::ReqwestError(e),
+            e @ UpstreamError::CircuitOpen(..) => ApiError::CircuitOpen(e.to_string()),
         }
-        Commodity::Real(input) => Ok(Complex::new(*input, 0.0)),
     }
 }
 
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::ReqwestError(e) => request_status(e),
+            ApiError::SerdeError(_)
+            | ApiError::MalformedComplex(_)
+            | ApiError::MissingCommodity(..)
+            | ApiError::DemkitError(_) => StatusCode::BAD_GATEWAY,
+            ApiError::HouseConflict(_) => StatusCode::CONFLICT,
+            ApiError::Validation(_) => StatusCode::BAD_REQUEST,
+            ApiError::UnknownEntity(_) | ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::CircuitOpen(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
 
-pub async fn get_time() -> u64 {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/time", *BASE_URL);
-
-    let response = client.get(url).send().await.unwrap();
+    fn error_response(&self) -> HttpResponse {
+        let body = match self {
+            ApiError::ReqwestError(e) if e.is_timeout() => {
+                ErrorBody::new("upstream_timeout", "DEMKit did not respond in time", Some(e.to_string()))
+            }
+            ApiError::ReqwestError(e) => {
+                ErrorBody::new("upstream_unavailable", "DEMKit request failed", Some(e.to_string()))
+            }
+            ApiError::SerdeError(e) => ErrorBody::new(
+                "upstream_invalid_response",
+                "DEMKit returned an unexpected response",
+                Some(e.to_string()),
+            ),
+            ApiError::MalformedComplex(input) => ErrorBody::new(
+                "upstream_invalid_response",
+                "DEMKit returned a malformed complex number",
+                Some(input.clone()),
+            ),
+            ApiError::MissingCommodity(..) => ErrorBody::new(
+                "upstream_invalid_response",
+                "DEMKit response is missing a commodity",
+                Some(self.to_string()),
+            ),
+            ApiError::DemkitError(detail) => {
+                ErrorBody::new("upstream_error", "DEMKit reported an error", Some(detail.clone()))
+            }
+            ApiError::HouseConflict(message) => ErrorBody::new("house_conflict", message, None),
+            ApiError::Validation(message) => ErrorBody::new("validation_error", message, None),
+            ApiError::UnknownEntity(detail) => {
+                ErrorBody::new("unknown_entity", "Unknown DEMKit entity", Some(detail.clone()))
+            }
+            ApiError::NotFound(message) => ErrorBody::new("not_found", message, None),
+            ApiError::CircuitOpen(detail) => ErrorBody::new(
+                "upstream_circuit_open",
+                "DEMKit is unavailable, try again later",
+                Some(detail.clone()),
+            ),
+        };
+
+        body.response(self.status_code())
+    }
+}
 
-    response.json::<u64>().await.unwrap()
+/// Parses DEMKit's `__(re+imj)` notation, real commodities have no imaginary part
+fn parse_complex_str(input: &Commodity) -> Result<Complex<f64>, ApiError> {
+    match input {
+        Commodity::Complex(input) => input
+            .strip_prefix("__")
+            .and_then(|complex| Complex::from_str(&complex.replace("(", "").replace(")", "")).ok())
+            .ok_or_else(|| ApiError::MalformedComplex(input.clone())),
+        Commodity::Real(input) => Ok(Complex::new(*input, 0.0)),
+    }
 }
 
+/// Checks DEMKit's `(success, message)` answer to a device function, DEMKit answers `null`
+/// when the entity does not exist
+fn check_response(entity: &str, response: serde_json::Value, action: &str) -> Result<(), ApiError> {
+    if response.is_null() {
+        return Err(ApiError::UnknownEntity(entity.to_string()));
+    }
 
-pub async fn list_entities() -> Vec<String> {
-    let client = CLIENT.get_or_init(init);
+    let (success, message) = serde_json::from_value::<(bool, String)>(response)?;
 
-    let url = format!("{}/list", *BASE_URL);
+    match success {
+        true => Ok(()),
+        false => Err(ApiError::Validation(format!("Failed to {action}: {message}"))),
+    }
+}
+
+pub async fn get_time(backend: &dyn DemkitBackend) -> Result<u64, ApiError> {
+    backend.get_time().await
+}
 
-    let response = client.get(url).send().await.unwrap();
 
-    let response_body = response.json::<Vec<String>>().await.unwrap();
+pub async fn list_entities(backend: &dyn DemkitBackend, house_id: u32) -> Result<Vec<String>, ApiError> {
+    let response_body = backend.list().await?;
 
-    response_body
-}
\ No newline at end of file
+    Ok(response_body
+        .into_iter()
+        .filter(|entity| registry::is_house_entity(house_id, entity))
+        .collect())
+}
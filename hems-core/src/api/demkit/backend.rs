@@ -0,0 +1,108 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use super::{
+    env::{Entity, SimConfig},
+    sim::Time,
+    ApiError,
+};
+use crate::api::upstream::CircuitBreaker;
+
+#[derive(Clone, Copy, Debug)]
+pub enum SimulationAction {
+    Pause,
+    Resume,
+    Stop,
+}
+
+impl SimulationAction {
+    pub fn as_str(&self) -> &str {
+        match self {
+            SimulationAction::Pause => "pause",
+            SimulationAction::Resume => "resume",
+            SimulationAction::Stop => "stop",
+        }
+    }
+}
+
+/// State of the DEMKit composer, `INACTIVE`, `LOADED` or `ACTIVE` in DEMKit
+#[derive(Serialize, ToSchema, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ComposerStatus {
+    /// No simulation is loaded, entities can be composed
+    Inactive,
+    /// The composed entities are loaded, the simulation is not started yet
+    Loaded,
+    /// The simulation is running
+    Active,
+}
+
+impl TryFrom<&str> for ComposerStatus {
+    type Error = ApiError;
+
+    fn try_from(status: &str) -> Result<Self, Self::Error> {
+        match status.trim() {
+            "INACTIVE" => Ok(ComposerStatus::Inactive),
+            "LOADED" => Ok(ComposerStatus::Loaded),
+            "ACTIVE" => Ok(ComposerStatus::Active),
+            status => Err(ApiError::DemkitError(format!("Unknown composer status: {status}"))),
+        }
+    }
+}
+
+/// A DEMKit instance, as seen through its REST API.
+///
+/// Entity names are the full DEMKit names, e.g. `Battery-House-1`. Values passed to `set`
+/// use DEMKit's string conventions (`True`, `False`, `None`).
+#[async_trait]
+pub trait DemkitBackend: Send + Sync {
+    /// Identifies the instance, houses sharing a URL share a DEMKit instance
+    fn url(&self) -> &str;
+
+    /// Circuit breaker guarding the calls, for backends reached over the network
+    fn breaker(&self) -> Option<&CircuitBreaker> {
+        None
+    }
+
+    async fn composer_status(&self) -> Result<ComposerStatus, ApiError>;
+
+    async fn get_time(&self) -> Result<u64, ApiError>;
+
+    async fn set_time(&self, time: &Time) -> Result<(), ApiError>;
+
+    async fn list(&self) -> Result<Vec<String>, ApiError>;
+
+    async fn get(&self, entity: &str, property: &str) -> Result<Value, ApiError>;
+
+    async fn set(&self, entity: &str, property: &str, value: &str) -> Result<(), ApiError>;
+
+    async fn call(&self, entity: &str, function: &str) -> Result<Value, ApiError>;
+
+    async fn callp(&self, entity: &str, function: &str, args: Value) -> Result<Value, ApiError>;
+
+    /// Properties of an entity, DEMKit answers `null` for entities it does not know
+    async fn properties(&self, entity: &str) -> Result<Value, ApiError> {
+        match self.call(entity, "getProperties").await? {
+            Value::Null => Err(ApiError::UnknownEntity(entity.to_string())),
+            properties => Ok(properties),
+        }
+    }
+
+    async fn add_entity(&self, entity: &Entity) -> Result<(), ApiError>;
+
+    async fn remove_entity(&self, entity: &str) -> Result<(), ApiError>;
+
+    async fn set_config(&self, config: &SimConfig) -> Result<(), ApiError>;
+
+    async fn load(&self) -> Result<(), ApiError>;
+
+    async fn start(&self) -> Result<(), ApiError>;
+
+    async fn reset(&self) -> Result<(), ApiError>;
+
+    async fn simulation(&self, action: SimulationAction) -> Result<(), ApiError>;
+
+    async fn add_ha_entity(&self, entity_id: &str) -> Result<(), ApiError>;
+}
@@ -1,7 +1,15 @@
 use num_complex::Complex;
 use serde::Deserialize;
+use serde_json::json;
+use utoipa::ToSchema;
 
-use super::{init, parse_complex_str, ApiError, Commodities, BASE_URL, CLIENT};
+use super::{check_response, env, ApiError, Commodities, DemkitBackend};
+
+/// Slack on the charging power bounds and capacity, DEMKit rounds powers to whole watts
+const TOLERANCE: f64 = 1.0;
+
+/// Longest interval of an uploaded schedule, one day in seconds
+const MAX_SCHEDULE_INTERVAL: u64 = 86_400;
 
 #[allow(dead_code)]
 #[derive(Deserialize, Debug)]
@@ -17,6 +25,8 @@ pub struct BatteryProperties {
     pub strict_comfort: bool,
     pub consumption: Commodities,
     pub soc: f64,
+    #[serde(rename = "initialSoC")]
+    pub initial_soc: f64,
     #[serde(rename = "targetSoC")]
     pub target_soc: Option<f64>,
     pub cop: f64,
@@ -32,43 +42,154 @@ pub struct BatteryProperties {
     pub discrete: bool,
     #[serde(rename = "useInefficiency")]
     pub use_inefficiency: bool,
-
-    pub electricity_consumption: Option<Complex<f64>>,
+    /// Power the battery is held at, positive when charging
+    #[serde(rename = "powerSetpoint", default)]
+    pub power_setpoint: Option<f64>,
+    /// Scheduled powers as `(time, power)`, each in effect until the next one
+    #[serde(default)]
+    pub schedule: Vec<(u64, f64)>,
+    #[serde(rename = "scheduleEnd", default)]
+    pub schedule_end: Option<u64>,
+
+    #[serde(skip)]
+    pub electricity_consumption: Complex<f64>,
 }
 
-pub async fn get_battery_properties(house_id: u32) -> Result<BatteryProperties, ApiError> {
-    let client = CLIENT.get_or_init(init);
+pub async fn get_battery_properties(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+) -> Result<BatteryProperties, ApiError> {
+    let entity = format!("{}-House-{house_id}", env::BATTERY_NAME);
+    let response = backend.properties(&entity).await?;
 
-    let url = format!("{}/call/Battery-House-{house_id}/getProperties", *BASE_URL);
+    let mut response_body = serde_json::from_value::<BatteryProperties>(response)?;
 
-    let response = client.get(url).send().await?;
-
-    let mut response_body = response.json::<BatteryProperties>().await?;
-
-    response_body.electricity_consumption =
-        Some(parse_complex_str(&response_body.consumption.electricity.clone().expect("Electricity commodity not found"))?);
+    response_body.electricity_consumption = response_body.consumption.electricity(&entity)?;
 
     Ok(response_body)
 }
 
-pub async fn set_target_soc(house_id: u32, soc: Option<u32>) -> Result<BatteryProperties, ApiError> {
-    let client = CLIENT.get_or_init(init);
-
+pub async fn set_target_soc(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    soc: Option<u32>,
+) -> Result<BatteryProperties, ApiError> {
     let soc = match soc {
         Some(soc) => soc.to_string(),
         None => "None".to_string(),
     };
 
-    let url = format!("{}/set/Battery-House-{house_id}/targetSoC/{soc}", *BASE_URL);
+    backend
+        .set(&format!("{}-House-{house_id}", env::BATTERY_NAME), "targetSoC", &soc)
+        .await?;
 
-    let response = client.get(url).send().await?;
+    get_battery_properties(backend, house_id).await
+}
 
-    let success = response.json::<bool>().await?;
+/// Powers for consecutive intervals, starting at `start_time`
+#[derive(Deserialize, ToSchema)]
+pub struct PowerSchedule {
+    /// Unix time of the first interval, the current simulation time when not given
+    pub start_time: Option<u64>,
+    /// Length of each interval in seconds, at most a day
+    pub interval: u64,
+    /// Power per interval in W, positive when charging
+    pub powers: Vec<f64>,
+}
 
-    if success {
-        get_battery_properties(house_id).await
-    } else {
-        Err(ApiError::DemkitError("Failed to set target SoC".to_string()))
+impl BatteryProperties {
+    /// Checks `power` against the charging powers, snapping it to the nearest one for discrete
+    /// batteries
+    fn checked_power(&self, power: f64) -> Result<f64, ApiError> {
+        let (min, max) = match (self.charging_powers.first(), self.charging_powers.last()) {
+            (Some(min), Some(max)) => (*min, *max),
+            _ => return Err(ApiError::DemkitError(format!("{} has no charging powers", self.name))),
+        };
+
+        if !power.is_finite() || power < min - TOLERANCE || power > max + TOLERANCE {
+            return Err(ApiError::Validation(format!(
+                "Invalid power: {power}. Power must be between {min} and {max} W"
+            )));
+        }
+
+        match self.discrete {
+            true => Ok(self
+                .charging_powers
+                .iter()
+                .copied()
+                .min_by(|a, b| (a - power).abs().total_cmp(&(b - power).abs()))
+                .unwrap_or(power)),
+            false => Ok(power.clamp(min, max)),
+        }
     }
+}
+
+pub async fn set_power_setpoint(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    power: Option<f64>,
+) -> Result<BatteryProperties, ApiError> {
+    let battery = get_battery_properties(backend, house_id).await?;
+
+    let power = match power {
+        Some(power) => Some(battery.checked_power(power)?),
+        None => None,
+    };
 
-}
\ No newline at end of file
+    let response = backend.callp(&battery.name, "setPowerSetpoint", json!([power])).await?;
+    check_response(&battery.name, response, "set power setpoint")?;
+
+    get_battery_properties(backend, house_id).await
+}
+
+pub async fn set_schedule(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    schedule: PowerSchedule,
+) -> Result<BatteryProperties, ApiError> {
+    if schedule.powers.is_empty() || schedule.interval == 0 {
+        return Err(ApiError::Validation(
+            "Invalid schedule: it needs at least one power and a positive interval".to_string(),
+        ));
+    }
+    if schedule.interval > MAX_SCHEDULE_INTERVAL {
+        return Err(ApiError::Validation(format!(
+            "Invalid schedule: the interval must be at most {MAX_SCHEDULE_INTERVAL} seconds"
+        )));
+    }
+
+    let battery = get_battery_properties(backend, house_id).await?;
+    let start_time = match schedule.start_time {
+        Some(start_time) => start_time,
+        None => super::get_time(backend).await?,
+    };
+    let end_time = u64::try_from(schedule.powers.len())
+        .ok()
+        .and_then(|count| count.checked_mul(schedule.interval))
+        .and_then(|length| start_time.checked_add(length))
+        .ok_or_else(|| ApiError::Validation("Invalid schedule: it ends too far in the future".to_string()))?;
+
+    // assumes the battery still holds its current charge when the schedule starts
+    let mut soc = battery.soc;
+    let mut entries = Vec::with_capacity(schedule.powers.len());
+    // cannot overflow, every interval ends at or before `end_time`
+    for (time, power) in (start_time..end_time).step_by(schedule.interval as usize).zip(&schedule.powers) {
+        let power = battery.checked_power(*power)?;
+
+        soc += power * schedule.interval as f64 / 3600.0;
+        if soc < -TOLERANCE || soc > battery.capacity + TOLERANCE {
+            return Err(ApiError::Validation(format!(
+                "Invalid schedule: the state of charge reaches {soc:.0} Wh at {}, outside 0 to {} Wh",
+                time + schedule.interval,
+                battery.capacity
+            )));
+        }
+
+        entries.push((time, power));
+    }
+
+    let response = backend.callp(&battery.name, "setSchedule", json!([entries, end_time])).await?;
+    check_response(&battery.name, response, "set schedule")?;
+
+    get_battery_properties(backend, house_id).await
+}
@@ -1,21 +1,23 @@
 use serde::Deserialize;
 
-use super::{init, parse_complex_str, ApiError, Commodities, Measurement, BASE_URL, CLIENT};
+use super::{parse_complex_str, ApiError, Commodities, DemkitBackend, Measurement};
 
+#[allow(dead_code)]
 pub async fn get_device_consumption(
-    _house_id: u32,
+    backend: &dyn DemkitBackend,
     device_name: &str,
 ) -> Result<Commodities, ApiError> {
-    let consumption = get_device_property::<Commodities>(device_name, "consumption").await?;
+    let consumption = get_device_property::<Commodities>(backend, device_name, "consumption").await?;
 
     Ok(consumption)
 }
 
+#[allow(dead_code)]
 pub async fn get_device_electricity_consumption(
-    house_id: u32,
+    backend: &dyn DemkitBackend,
     device_name: &str,
 ) -> Result<Measurement, ApiError> {
-    let consumption = get_device_consumption(house_id, device_name).await?;
+    let consumption = get_device_consumption(backend, device_name).await?;
 
     match consumption.electricity {
         Some(power) => {
@@ -34,17 +36,17 @@ pub async fn get_device_electricity_consumption(
     }
 }
 
-pub async fn get_device_property<T>(device_name: &str, property: &str) -> Result<T, ApiError>
+pub async fn get_device_property<T>(
+    backend: &dyn DemkitBackend,
+    device_name: &str,
+    property: &str,
+) -> Result<T, ApiError>
 where
     T: for<'a> Deserialize<'a>,
 {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/get/{device_name}/{property}", *BASE_URL);
-
-    let response = client.get(url).send().await?;
+    let response = backend.get(device_name, property).await?;
 
-    let response_body = response.json::<T>().await?;
+    let response_body = serde_json::from_value::<T>(response)?;
 
     Ok(response_body)
 }
\ No newline at end of file
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use num_complex::Complex;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::devices::get_device_property;
+use super::heatpump::{get_heat_source_properties, heat_source_entity, HeatSourceProperties};
+use super::{check_response, ApiError, Commodities, DemkitBackend};
+
+const MIN_SETPOINT: f64 = 35.0;
+const MAX_SETPOINT: f64 = 80.0;
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct DhwProperties {
+    pub name: String,
+    #[serde(rename = "consumption")]
+    _consumption: Commodities,
+    /// Hot water demand of the taps
+    #[serde(skip)]
+    pub heat_demand: Complex<f64>,
+    /// Demanded hot water temperature in Celsius
+    pub temperature: f64,
+
+    /// Heat delivered to the taps
+    #[serde(skip)]
+    pub heat_supply: f64,
+    /// Temperature of the hot water delivered to the taps in Celsius
+    #[serde(skip)]
+    pub supply_temperature: f64,
+}
+
+pub async fn get_dhw_properties(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+) -> Result<DhwProperties, ApiError> {
+    let entity = format!("DomesticHotWater-House-{house_id}");
+    let response = backend.properties(&entity).await?;
+
+    let mut response_body = serde_json::from_value::<DhwProperties>(response)?;
+
+    response_body.heat_demand = response_body._consumption.heat(&entity)?;
+
+    // set by the heat source every tick, keyed by commodity
+    let heat_supply = get_device_property::<HashMap<String, f64>>(backend, &entity, "heatSupply").await?;
+    let heat_temperature =
+        get_device_property::<HashMap<String, f64>>(backend, &entity, "heatTemperature").await?;
+    response_body.heat_supply = heat_supply.get("HEAT").copied().unwrap_or_default();
+    response_body.supply_temperature = heat_temperature.get("HEAT").copied().unwrap_or_default();
+
+    Ok(response_body)
+}
+
+/// Source heating the hot water: a dedicated boiler when the house heats with a heat pump,
+/// otherwise the heat source itself
+pub async fn get_dhw_heater(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+) -> Result<HeatSourceProperties, ApiError> {
+    let boiler = format!("DomesticHotWaterControllerBoiler-House-{house_id}");
+
+    match get_heat_source_properties(backend, &boiler).await {
+        Err(ApiError::UnknownEntity(_)) => get_heat_source_properties(backend, &heat_source_entity(house_id)).await,
+        result => result,
+    }
+}
+
+pub async fn set_setpoint(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    temperature: f64,
+) -> Result<(), ApiError> {
+    if !(MIN_SETPOINT..=MAX_SETPOINT).contains(&temperature) {
+        return Err(ApiError::Validation(format!(
+            "Invalid Temperature: {temperature}. Hot water setpoint must be between {MIN_SETPOINT} and {MAX_SETPOINT} degrees Celsius"
+        )));
+    }
+
+    let heater = get_dhw_heater(backend, house_id).await?;
+
+    let response = backend.callp(&heater.name, "setSupplyTemperature", json!([temperature])).await?;
+    check_response(&heater.name, response, "set the hot water setpoint")?;
+
+    backend
+        .set(&format!("DomesticHotWater-House-{house_id}"), "temperature", &temperature.to_string())
+        .await
+}
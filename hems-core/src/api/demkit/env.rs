@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use utoipa::ToSchema;
 
-use super::{init, ApiError, BASE_URL, CLIENT};
+use super::{ApiError, DemkitBackend};
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -45,17 +45,17 @@ pub struct SimConfig {
     pub thermostat_setpoints: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct HostEntityParams {
     pub name: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct WeatherEntityParams {
     pub name: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct SunEntityParams {
     pub name: String,
 }
@@ -84,26 +84,80 @@ pub struct TimeShifterEntityParams {
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct BatteryEntityParams {
     pub name: String,
+    /// Storage capacity in Wh
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<f64>,
+    /// Energy stored when the simulation starts in Wh
+    #[serde(rename = "initialSoC", default, skip_serializing_if = "Option::is_none")]
+    pub initial_soc: Option<f64>,
+    /// Power levels the battery can charge (positive) and discharge (negative) at in W
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub charging_powers: Option<Vec<f64>>,
+    /// Efficiency of each charging power, between 0 and 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub charging_efficiency: Option<Vec<f64>>,
+    /// Standby losses in W
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub self_consumption: Option<f64>,
+    /// Whether the battery only runs at its charging powers rather than anywhere between them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discrete: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EvEntityParams {
+    pub name: String,
+    /// Battery capacity of the vehicle in Wh
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<f64>,
+    /// Maximum charging power of the charging point in W
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_charge_power: Option<f64>,
+    /// Unix time the vehicle plugs in, together with the departure time and required energy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arrival_time: Option<u64>,
+    /// Unix time the vehicle leaves
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub departure_time: Option<u64>,
+    /// Energy to charge before departure in Wh
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub required_energy: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct SolarEntityParams {
     pub name: String,
+    /// Panel area in m²
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<f64>,
+    /// Conversion efficiency in percentage
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub efficiency: Option<f64>,
+    /// Tilt in degrees from the horizontal plane
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inclination: Option<f64>,
+    /// Orientation in degrees, 0 is north, 90 is east, 180 is south
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub azimuth: Option<f64>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CurtEntityParams {
     pub name: String,
     pub filename: String,
     pub filename_reactive: String,
-    pub column: u64,
+    /// Column of the load files to read, the house ID if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<u64>,
     pub time_base: u64,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ZoneEntityParams {
     pub name: String,
@@ -112,9 +166,15 @@ pub struct ZoneEntityParams {
     pub c_floor: f64,
     pub c_zone: f64,
     pub initial_temperature: f64,
+    /// Maximum heating power of the heat emitter in the zone in W
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_heat: Option<f64>,
+    /// Maximum cooling power of the heat emitter in the zone in W, negative
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_heat: Option<f64>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct MeterEntityParams {
     pub name: String,
@@ -122,7 +182,7 @@ pub struct MeterEntityParams {
     pub weights: Vec<(String, f64)>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ThermostatEntityParams {
     pub name: String,
@@ -132,21 +192,24 @@ pub struct ThermostatEntityParams {
     pub temperature_max: f64,
     pub temperature_deadband: Vec<f64>,
     pub preheating_time: f64,
+    /// Name of the zone the thermostat controls, the first zone of the house if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zone: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct DhwEntityParams {
     pub name: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct HeatSourceEntityParams {
     pub name: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct HeatPumpEntityParams {
     pub name: String,
@@ -154,23 +217,87 @@ pub struct HeatPumpEntityParams {
     pub producing_powers: Vec<f64>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[serde(tag = "type")]
 pub enum EntityParams {
+    #[serde(rename = "host")]
     Host(HostEntityParams),
+    #[serde(rename = "weather")]
     Weather(WeatherEntityParams),
+    #[serde(rename = "sun")]
     Sun(SunEntityParams),
+    #[serde(rename = "timeshiftable")]
     TimeShifter(TimeShifterEntityParams),
+    #[serde(rename = "battery")]
     Battery(BatteryEntityParams),
+    #[serde(rename = "ev")]
+    Ev(EvEntityParams),
+    #[serde(rename = "solar_panel")]
     Solar(SolarEntityParams),
+    #[serde(rename = "curt")]
     Curt(CurtEntityParams),
+    #[serde(rename = "zone")]
     Zone(ZoneEntityParams),
+    #[serde(rename = "meter")]
     Meter(MeterEntityParams),
+    #[serde(rename = "thermostat")]
     Thermostat(ThermostatEntityParams),
+    #[serde(rename = "dhw")]
     Dhw(DhwEntityParams),
+    #[serde(rename = "heat_source")]
     HeatSource(HeatSourceEntityParams),
+    #[serde(rename = "heat_pump")]
     HeatPump(HeatPumpEntityParams),
 }
 
+impl EntityParams {
+    pub fn name(&self) -> &str {
+        match self {
+            EntityParams::Host(params) => &params.name,
+            EntityParams::Weather(params) => &params.name,
+            EntityParams::Sun(params) => &params.name,
+            EntityParams::TimeShifter(params) => &params.name,
+            EntityParams::Battery(params) => &params.name,
+            EntityParams::Ev(params) => &params.name,
+            EntityParams::Solar(params) => &params.name,
+            EntityParams::Curt(params) => &params.name,
+            EntityParams::Zone(params) => &params.name,
+            EntityParams::Meter(params) => &params.name,
+            EntityParams::Thermostat(params) => &params.name,
+            EntityParams::Dhw(params) => &params.name,
+            EntityParams::HeatSource(params) => &params.name,
+            EntityParams::HeatPump(params) => &params.name,
+        }
+    }
+
+    /// Name of the entity in DEMKit once composed into the given house
+    pub fn house_entity_name(&self, house_id: u32) -> String {
+        match self {
+            EntityParams::Host(_) => format!("House-{house_id}"),
+            _ => format!("{}-House-{house_id}", self.name()),
+        }
+    }
+
+    fn set_name(&mut self, name: String) {
+        match self {
+            EntityParams::Host(params) => params.name = name,
+            EntityParams::Weather(params) => params.name = name,
+            EntityParams::Sun(params) => params.name = name,
+            EntityParams::TimeShifter(params) => params.name = name,
+            EntityParams::Battery(params) => params.name = name,
+            EntityParams::Ev(params) => params.name = name,
+            EntityParams::Solar(params) => params.name = name,
+            EntityParams::Curt(params) => params.name = name,
+            EntityParams::Zone(params) => params.name = name,
+            EntityParams::Meter(params) => params.name = name,
+            EntityParams::Thermostat(params) => params.name = name,
+            EntityParams::Dhw(params) => params.name = name,
+            EntityParams::HeatSource(params) => params.name = name,
+            EntityParams::HeatPump(params) => params.name = name,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Entity {
     pub r#type: String,
@@ -185,6 +312,7 @@ impl Entity {
             EntityParams::Sun(params) => ("sun", serde_json::to_value(params).unwrap()),
             EntityParams::TimeShifter(params) => ("timeshiftable", serde_json::to_value(params).unwrap()),
             EntityParams::Battery(params) => ("battery", serde_json::to_value(params).unwrap()),
+            EntityParams::Ev(params) => ("ev", serde_json::to_value(params).unwrap()),
             EntityParams::Solar(params) => ("solar_panel", serde_json::to_value(params).unwrap()),
             EntityParams::Curt(params) => ("curt", serde_json::to_value(params).unwrap()),
             EntityParams::Zone(params) => ("zone", serde_json::to_value(params).unwrap()),
@@ -202,391 +330,121 @@ impl Entity {
     }
 }
 
-pub async fn add_host(house_id: u32) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/composer/entities", *BASE_URL);
-
-    let inner = HostEntityParams {
-        name: format!("House-{house_id}"),
-    };
-    let entity = Entity::new(EntityParams::Host(inner));
-
-    let response = client.put(url).json(&entity).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to set host: {}",
-            error_message
-        )))
-    }
-}
-
-pub async fn add_weather(house_id: u32) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/composer/entities", *BASE_URL);
-
-    let inner = WeatherEntityParams {
-        name: format!("Weather-House-{house_id}"),
+pub async fn add_entity(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    mut params: EntityParams,
+) -> Result<String, ApiError> {
+    let name = params.house_entity_name(house_id);
+    let timeshifter = match &params {
+        EntityParams::TimeShifter(params) => Some(params.name.clone()),
+        _ => None,
     };
-    let entity = Entity::new(EntityParams::Weather(inner));
-
-    let response = client.put(url).json(&entity).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to add weather: {}",
-            error_message
-        )))
-    }
-}
-
-pub async fn add_sun(house_id: u32) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/composer/entities", *BASE_URL);
+    params.set_name(name.clone());
 
-    let inner = SunEntityParams {
-        name: format!("Sun-House-{house_id}"),
-    };
-    let entity = Entity::new(EntityParams::Sun(inner));
-
-    let response = client.put(url).json(&entity).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to add sun: {}",
-            error_message
-        )))
+    // every house reads its own load profile unless the spec picks one
+    if let EntityParams::Curt(params) = &mut params {
+        params.column.get_or_insert(house_id as u64);
     }
-}
-
-pub async fn add_timeshifter(house_id: u32, mut inner: TimeShifterEntityParams) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/composer/entities", *BASE_URL);
-
-    let name = inner.name.clone();
-    inner.name = format!("{name}-House-{house_id}");
-
-    let entity = Entity::new(EntityParams::TimeShifter(inner));
-
-    let response = client.put(url).json(&entity).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to set timeshifter: {}",
-            error_message
-        )))
+    // the zone is composed into the same house, so it gets the same suffix
+    if let EntityParams::Thermostat(params) = &mut params {
+        params.zone = params.zone.take().map(|zone| format!("{zone}-House-{house_id}"));
     }
-}
 
-pub async fn add_battery(house_id: u32) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
+    let entity = Entity::new(params);
 
-    let url = format!("{}/composer/entities", *BASE_URL);
+    backend.add_entity(&entity).await?;
 
-    let inner = BatteryEntityParams {
-        name: format!("Battery-House-{house_id}"),
-    };
-    let entity = Entity::new(EntityParams::Battery(inner));
-
-    let response = client.put(url).json(&entity).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to set battery: {}",
-            error_message
-        )))
+    if let Some(timeshifter) = timeshifter {
+        super::timeshifters::register(house_id, &timeshifter);
     }
-}
 
-pub async fn add_solar(house_id: u32) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/composer/entities", *BASE_URL);
-
-    let inner = SolarEntityParams {
-        name: format!("PV-House-{house_id}"),
-    };
-    let entity = Entity::new(EntityParams::Solar(inner));
-
-    let response = client.put(url).json(&entity).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to set solar: {}",
-            error_message
-        )))
-    }
+    Ok(name)
 }
 
-pub async fn add_curt(house_id: u32, mut inner: CurtEntityParams) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/composer/entities", *BASE_URL);
-
-    let name = inner.name.clone();
-    inner.name = format!("{name}-House-{house_id}");
-
-    let entity = Entity::new(EntityParams::Curt(inner));
-
-    let response = client.put(url).json(&entity).send().await?;
+pub async fn add_timeshifter(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    inner: TimeShifterEntityParams,
+) -> Result<(), ApiError> {
+    add_entity(backend, house_id, EntityParams::TimeShifter(inner)).await?;
 
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to set curt: {}",
-            error_message
-        )))
-    }
+    Ok(())
 }
 
-pub async fn add_zone(house_id: u32, mut inner: ZoneEntityParams) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/composer/entities", *BASE_URL);
+/// Name the battery routes look the house battery up by, without the `-House-{id}` suffix
+pub const BATTERY_NAME: &str = "Battery";
 
-    let name = inner.name.clone();
-    inner.name = format!("{name}-House-{house_id}");
+/// Name the solar routes look the house PV system up by, without the `-House-{id}` suffix
+pub const SOLAR_NAME: &str = "PV";
 
-    let entity = Entity::new(EntityParams::Zone(inner));
+pub async fn add_battery(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    mut inner: BatteryEntityParams,
+) -> Result<(), ApiError> {
+    inner.name = BATTERY_NAME.to_string();
+    add_entity(backend, house_id, EntityParams::Battery(inner)).await?;
 
-    let response = client.put(url).json(&entity).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to set zone: {}",
-            error_message
-        )))
-    }
+    Ok(())
 }
 
-pub async fn add_meter(house_id: u32, mut inner: MeterEntityParams) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/composer/entities", *BASE_URL);
-
-    let name = inner.name.clone();
-    inner.name = format!("{name}-House-{house_id}");
-
-    let entity = Entity::new(EntityParams::Meter(inner));
-
-    let response = client.put(url).json(&entity).send().await?;
+pub async fn add_ev(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    inner: EvEntityParams,
+) -> Result<(), ApiError> {
+    add_entity(backend, house_id, EntityParams::Ev(inner)).await?;
 
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to set meter: {}",
-            error_message
-        )))
-    }
+    Ok(())
 }
 
-pub async fn add_thermostat(house_id: u32, mut inner: ThermostatEntityParams) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/composer/entities", *BASE_URL);
-
-    let name = inner.name.clone();
-    inner.name = format!("{name}-House-{house_id}");
-
-    let entity = Entity::new(EntityParams::Thermostat(inner));
+pub async fn add_solar(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    mut inner: SolarEntityParams,
+) -> Result<(), ApiError> {
+    inner.name = SOLAR_NAME.to_string();
+    add_entity(backend, house_id, EntityParams::Solar(inner)).await?;
 
-    let response = client.put(url).json(&entity).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to set thermostat: {}",
-            error_message
-        )))
-    }
+    Ok(())
 }
 
-pub async fn add_dhw(house_id: u32, mut inner: DhwEntityParams) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/composer/entities", *BASE_URL);
-
-    let name = inner.name.clone();
-    inner.name = format!("{name}-House-{house_id}");
-
-    let entity = Entity::new(EntityParams::Dhw(inner));
-
-    let response = client.put(url).json(&entity).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to set dhw: {}",
-            error_message
-        )))
-    }
+pub async fn set_config(backend: &dyn DemkitBackend, config: SimConfig) -> Result<(), ApiError> {
+    backend.set_config(&config).await
 }
 
-pub async fn add_heat_source(house_id: u32, mut inner: HeatSourceEntityParams) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/composer/entities", *BASE_URL);
-
-    let name = inner.name.clone();
-    inner.name = format!("{name}-House-{house_id}");
-
-    let entity = Entity::new(EntityParams::HeatSource(inner));
-
-    let response = client.put(url).json(&entity).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to set heat source: {}",
-            error_message
-        )))
-    }
+pub async fn load(backend: &dyn DemkitBackend) -> Result<(), ApiError> {
+    backend.load().await
 }
 
-pub async fn add_heat_pump(house_id: u32, mut inner: HeatPumpEntityParams) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/composer/entities", *BASE_URL);
-
-    let name = inner.name.clone();
-    inner.name = format!("{name}-House-{house_id}");
-
-    let entity = Entity::new(EntityParams::HeatPump(inner));
-
-    let response = client.put(url).json(&entity).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to set heat pump: {}",
-            error_message
-        )))
-    }
+pub async fn start(backend: &dyn DemkitBackend) -> Result<(), ApiError> {
+    backend.start().await
 }
 
-pub async fn set_config(config: SimConfig) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/composer/config", *BASE_URL);
-
-    let response = client.post(url).json(&config).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to set config: {}",
-            error_message
-        )))
-    }
+pub async fn reset(backend: &dyn DemkitBackend) -> Result<(), ApiError> {
+    backend.reset().await
 }
 
-pub async fn load() -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/composer/load", *BASE_URL);
-
-    let response = client.post(url).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to load: {}",
-            error_message
-        )))
-    }
+pub async fn remove_entity(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    name: &str,
+) -> Result<(), ApiError> {
+    remove_entity_by_name(backend, &format!("{name}-House-{house_id}")).await
 }
 
-pub async fn start() -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/composer/start", *BASE_URL);
-
-    let response = client.post(url).send().await?;
+pub async fn remove_entity_by_name(
+    backend: &dyn DemkitBackend,
+    entity_name: &str,
+) -> Result<(), ApiError> {
+    backend.remove_entity(entity_name).await?;
 
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to start: {}",
-            error_message
-        )))
+    if let Some((name, house_id)) = entity_name.rsplit_once("-House-") {
+        if let Ok(house_id) = house_id.parse() {
+            super::timeshifters::unregister(house_id, name);
+        }
     }
-}
-
-pub async fn reset() -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/composer/reset", *BASE_URL);
 
-    let response = client.post(url).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to reset: {}",
-            error_message
-        )))
-    }
+    Ok(())
 }
-
-pub async fn remove_entity(house_id: u32, name: &str) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/composer/entities/{name}-House-{house_id}", *BASE_URL);
-
-    let response = client.delete(url).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to remove timeshifter: {}",
-            error_message
-        )))
-    }
-}
\ No newline at end of file
@@ -0,0 +1,143 @@
+use num_complex::Complex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::ToSchema;
+
+use super::{check_response, ApiError, Commodities, DemkitBackend};
+
+/// Charging session of an electric vehicle, DEMKit calls these jobs
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    /// Unix time the vehicle plugs in
+    pub start_time: u64,
+    /// Unix time the vehicle leaves
+    pub end_time: u64,
+    /// Energy to charge during the session in Wh
+    pub charge: f64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EvProperties {
+    pub name: String,
+    #[serde(rename = "consumption")]
+    _consumption: Commodities,
+    #[serde(skip)]
+    pub electricity_consumption: Complex<f64>,
+    /// Whether the vehicle is plugged in
+    pub available: bool,
+    pub soc: f64,
+    pub capacity: f64,
+    #[serde(rename = "chargingPowers")]
+    pub charging_powers: Vec<f64>,
+    pub jobs: Vec<(u64, Session)>,
+    #[serde(rename = "currentJobIdx")]
+    pub current_job_idx: i64,
+}
+
+impl EvProperties {
+    /// Session the vehicle is plugged in for
+    pub fn current_session(&self) -> Option<&Session> {
+        match self.available {
+            true => usize::try_from(self.current_job_idx)
+                .ok()
+                .and_then(|idx| self.jobs.get(idx))
+                .map(|(_, session)| session),
+            false => None,
+        }
+    }
+
+    /// Sessions that did not start yet
+    pub fn upcoming_sessions(&self) -> Vec<Session> {
+        let next = usize::try_from(self.current_job_idx + 1).unwrap_or(0);
+
+        self.jobs
+            .iter()
+            .skip(next)
+            .map(|(_, session)| session.clone())
+            .collect()
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct StartSession {
+    /// Seconds from now until the vehicle leaves
+    pub departure_in: u64,
+    /// Energy to charge before departure in Wh
+    pub required_energy: f64,
+}
+
+fn entity_id(house_id: u32, name: &str) -> String {
+    format!("{name}-House-{house_id}")
+}
+
+pub async fn get_ev_properties(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    name: &str,
+) -> Result<EvProperties, ApiError> {
+    let entity = entity_id(house_id, name);
+    let response = backend.properties(&entity).await?;
+
+    let mut response_body = serde_json::from_value::<EvProperties>(response)?;
+
+    response_body.electricity_consumption = response_body._consumption.electricity(&entity)?;
+
+    Ok(response_body)
+}
+
+pub async fn start_session(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    name: &str,
+    session: StartSession,
+) -> Result<EvProperties, ApiError> {
+    let entity = entity_id(house_id, name);
+
+    let args = json!([session.departure_in, session.required_energy]);
+    let response = backend.callp(&entity, "startSession", args).await?;
+    check_response(&entity, response, "start session")?;
+
+    get_ev_properties(backend, house_id, name).await
+}
+
+pub async fn end_session(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    name: &str,
+) -> Result<EvProperties, ApiError> {
+    let entity = entity_id(house_id, name);
+
+    let response = backend.call(&entity, "endSession").await?;
+    check_response(&entity, response, "end session")?;
+
+    get_ev_properties(backend, house_id, name).await
+}
+
+pub async fn set_departure(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    name: &str,
+    departure_time: u64,
+) -> Result<EvProperties, ApiError> {
+    let entity = entity_id(house_id, name);
+
+    let response = backend.callp(&entity, "setDeparture", json!([departure_time])).await?;
+    check_response(&entity, response, "set departure")?;
+
+    get_ev_properties(backend, house_id, name).await
+}
+
+pub async fn set_max_charge_power(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    name: &str,
+    power: f64,
+) -> Result<EvProperties, ApiError> {
+    let entity = entity_id(house_id, name);
+
+    let response = backend.callp(&entity, "setMaxChargingPower", json!([power])).await?;
+    check_response(&entity, response, "set charge power limit")?;
+
+    get_ev_properties(backend, house_id, name).await
+}
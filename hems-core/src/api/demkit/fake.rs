@@ -0,0 +1,805 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use async_trait::async_trait;
+use serde_json::{json, Map, Value};
+
+use super::{
+    backend::{ComposerStatus, DemkitBackend, SimulationAction},
+    env::{Entity, SimConfig},
+    sim::Time,
+    ApiError,
+};
+
+const ZERO: &str = "__(0+0j)";
+
+#[derive(Default)]
+struct FakeState {
+    time: u64,
+    loaded: bool,
+    running: bool,
+    /// Entity names in the order they were added
+    entities: Vec<String>,
+    types: HashMap<String, String>,
+    /// Variables of each entity, as DEMKit would return them from `get`
+    vars: HashMap<String, Map<String, Value>>,
+}
+
+/// In-memory stand-in for a DEMKit instance.
+///
+/// Entities keep the parameters they were composed with plus plausible defaults for the
+/// properties hems-core reads, so every device route works without a running simulation.
+/// Nothing is simulated: values only change through `set` and the job functions.
+/// Selected with a `memory://` URL, e.g. `DEMKIT_URL=memory://demkit`.
+pub struct FakeBackend {
+    url: String,
+    state: Mutex<FakeState>,
+}
+
+impl FakeBackend {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            state: Mutex::new(FakeState::default()),
+        }
+    }
+}
+
+fn unknown_entity(entity: &str) -> ApiError {
+    ApiError::UnknownEntity(entity.to_string())
+}
+
+/// Parses a value the way DEMKit's `setVar` does
+fn parse_value(value: &str) -> Value {
+    match value {
+        "True" => Value::Bool(true),
+        "False" => Value::Bool(false),
+        "None" => Value::Null,
+        _ => match value.parse::<f64>() {
+            Ok(number) => json!(number),
+            Err(_) => Value::String(value.to_string()),
+        },
+    }
+}
+
+fn complex_str(value: &Value) -> String {
+    let re = value["re"].as_f64().unwrap_or(0.0);
+    let im = value["im"].as_f64().unwrap_or(0.0);
+
+    format!("__({re}{im:+}j)")
+}
+
+/// Variables of a freshly composed entity: its parameters plus defaults per entity type
+fn initial_vars(entity_type: &str, params: &Value) -> Map<String, Value> {
+    let mut vars = params.as_object().cloned().unwrap_or_default();
+
+    let defaults = match entity_type {
+        "battery" => {
+            let capacity = params["capacity"].as_f64().unwrap_or(10000.0);
+            let soc = params["initialSoC"].as_f64().unwrap_or(capacity / 2.0);
+            let charging_powers: Vec<f64> = serde_json::from_value(params["chargingPowers"].clone())
+                .unwrap_or_else(|_| vec![-3700.0, 3700.0]);
+            let efficiency: Option<Vec<f64>> = serde_json::from_value(params["chargingEfficiency"].clone()).ok();
+
+            // DEMKit sorts the powers on startup, keeping each efficiency with its power
+            let mut pairs: Vec<(f64, f64)> = charging_powers
+                .iter()
+                .copied()
+                .zip(efficiency.clone().unwrap_or_else(|| vec![1.0; charging_powers.len()]))
+                .collect();
+            pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+            vars.remove("chargingPowers");
+            vars.remove("chargingEfficiency");
+
+            json!({
+                "timeBase": 60,
+                "timeOffset": 0,
+                "devtype": "BufferStorage",
+                "commodities": ["ELECTRICITY"],
+                "strictComfort": false,
+                "consumption": {"ELECTRICITY": ZERO},
+                "soc": soc,
+                "initialSoC": soc,
+                "targetSoC": null,
+                "cop": 1.0,
+                "capacity": capacity,
+                "chargingPowers": pairs.iter().map(|(power, _)| *power).collect::<Vec<_>>(),
+                "selfConsumption": 0.0,
+                "internalPowers": pairs.iter().map(|(power, eff)| power * eff).collect::<Vec<_>>(),
+                "chargingEfficiency": pairs.iter().map(|(_, eff)| *eff).collect::<Vec<_>>(),
+                "discrete": false,
+                "useInefficiency": efficiency.is_some(),
+                "powerSetpoint": null,
+                "schedule": [],
+                "scheduleEnd": null,
+            })
+        }
+        "ev" => {
+            let capacity = params["capacity"].as_f64().unwrap_or(40000.0);
+            let max_charge_power = params["maxChargePower"].as_f64().unwrap_or(7400.0);
+            let jobs = match (
+                params["arrivalTime"].as_u64(),
+                params["departureTime"].as_u64(),
+                params["requiredEnergy"].as_f64(),
+            ) {
+                (Some(start_time), Some(end_time), Some(charge)) => json!([[0, {
+                    "startTime": start_time,
+                    "endTime": end_time,
+                    "charge": charge.min(capacity),
+                }]]),
+                _ => json!([]),
+            };
+
+            // DEMKit turns the session parameters into a job
+            for key in ["maxChargePower", "arrivalTime", "departureTime", "requiredEnergy"] {
+                vars.remove(key);
+            }
+
+            json!({
+                "timeBase": 60,
+                "timeOffset": 0,
+                "devtype": "BufferTimeshiftable",
+                "commodities": ["ELECTRICITY"],
+                "strictComfort": false,
+                "consumption": {"ELECTRICITY": ZERO},
+                "available": false,
+                "soc": capacity,
+                "capacity": capacity,
+                "chargingPowers": [0.0, max_charge_power],
+                "discrete": false,
+                "jobs": jobs,
+                "currentJobIdx": -1,
+                "currentJob": {},
+            })
+        }
+        "solar_panel" => json!({
+            "timeBase": 60,
+            "timeOffset": 0,
+            "devtype": "Solar",
+            "commodities": ["ELECTRICITY"],
+            "strictComfort": false,
+            "consumption": {"ELECTRICITY": ZERO},
+            "originalConsumption": {"ELECTRICITY": ZERO},
+            "size": 10.0,
+            "efficiency": 20.0,
+            "inclination": 35.0,
+            "azimuth": 180.0,
+            "onOffDevice": true,
+            "curtailLevel": null,
+            "curtailLimit": null,
+            "energyToday": 0.0,
+            "energyTotal": 0.0,
+        }),
+        "timeshiftable" => {
+            let profile: Vec<String> = params["profile"]
+                .as_array()
+                .map(|profile| profile.iter().map(complex_str).collect())
+                .unwrap_or_default();
+
+            // DEMKit keeps the profile as complex numbers, which it serializes as strings
+            vars.remove("profile");
+
+            json!({
+                "timeBase": 60,
+                "timeOffset": 0,
+                "devtype": "TimeShiftable",
+                "commodities": ["ELECTRICITY"],
+                "strictComfort": false,
+                "consumption": {"ELECTRICITY": ZERO},
+                "profile": profile,
+                "available": true,
+                "jobs": [],
+                "currentJobIdx": -1,
+                "currentJob": {},
+                "jobProgress": 0.0,
+                "plannedStartTime": null,
+            })
+        }
+        // DEMKit composes a heat pump as heat source when the heating settings ask for one,
+        // the default, and another one heating the hot water
+        "heat_source" | "heat_pump" => {
+            let (temperatures, powers) = match entity_type {
+                "heat_source" => (json!([0.0, 35.0]), json!([0.0, 4500.0])),
+                _ => (json!([0.0, 60.0]), json!([-4500.0, 4500.0])),
+            };
+            let temperature = params["producingTemperatures"]
+                .as_array()
+                .and_then(|temperatures| temperatures.last())
+                .cloned()
+                .unwrap_or_else(|| temperatures[1].clone());
+
+            json!({
+                "timeBase": 60,
+                "timeOffset": 0,
+                "devtype": "BufferConverter",
+                "commodities": ["ELECTRICITY", "HEAT"],
+                "strictComfort": true,
+                "consumption": {"ELECTRICITY": ZERO, "HEAT": 0.0},
+                "temperature": temperature,
+                "cop": {"ELECTRICITY": 4.0},
+                "producingPowers": powers,
+                "producingTemperatures": temperatures,
+                "heatProduction": 0.0,
+                "soc": 0.0,
+                "capacity": 0.0,
+                "enabled": true,
+                "powerLevel": 1.0,
+                "ratedPowers": null,
+            })
+        }
+        "dhw" => json!({
+            "timeBase": 60,
+            "timeOffset": 0,
+            "devtype": "Load",
+            "commodities": ["HEAT"],
+            "strictComfort": false,
+            "consumption": {"HEAT": 0.0},
+            "temperature": 60.0,
+            "heatSupply": {"HEAT": 0.0},
+            "heatTemperature": {"HEAT": 0.0},
+        }),
+        "zone" => json!({
+            "temperature": params["initialTemperature"].as_f64().unwrap_or(20.0),
+            "valveHeat": 0.0,
+            "maxHeat": 7500.0,
+            "minHeat": -7500.0,
+            "commodities": ["HEAT"],
+            "consumption": {"HEAT": ZERO},
+        }),
+        "meter" => {
+            let commodities = params["commodities"].as_array().cloned().unwrap_or_default();
+            let consumption: Map<String, Value> = commodities
+                .iter()
+                .filter_map(|commodity| commodity.as_str())
+                .map(|commodity| (commodity.to_string(), json!(ZERO)))
+                .collect();
+
+            json!({
+                "timeBase": 60,
+                "devtype": "Meter",
+                "consumption": consumption,
+                "imported": 0.0,
+                "exported": 0.0,
+            })
+        }
+        _ => json!({}),
+    };
+
+    if let Value::Object(defaults) = defaults {
+        for (key, value) in defaults {
+            vars.entry(key).or_insert(value);
+        }
+    }
+
+    vars
+}
+
+impl FakeState {
+    fn vars(&self, entity: &str) -> Result<&Map<String, Value>, ApiError> {
+        self.vars.get(entity).ok_or_else(|| unknown_entity(entity))
+    }
+
+    fn vars_mut(&mut self, entity: &str) -> Result<&mut Map<String, Value>, ApiError> {
+        self.vars.get_mut(entity).ok_or_else(|| unknown_entity(entity))
+    }
+
+    fn properties(&self, entity: &str) -> Result<Value, ApiError> {
+        let vars = self.vars(entity)?;
+
+        match self.types.get(entity).map(String::as_str) {
+            // DEMKit's thermostat only exposes its setpoints and the zone it controls
+            Some("thermostat") => Ok(json!({
+                "devtype": "Thermostat",
+                "zone": vars.get("zone").or(self.first_zone(entity).as_ref()),
+                "heat_demand": 0.0,
+                "min_target_temp": vars.get("temperatureSetpointHeating"),
+                "max_target_temp": vars.get("temperatureSetpointCooling"),
+                "temperature_min": vars.get("temperatureMin"),
+                "temperature_max": vars.get("temperatureMax"),
+                "temperature_deadband": vars.get("temperatureDeadband"),
+            })),
+            _ => Ok(Value::Object(vars.clone())),
+        }
+    }
+
+    /// Zone of the house of `entity` that was composed first, which DEMKit gives thermostats
+    /// without a zone
+    fn first_zone(&self, entity: &str) -> Option<Value> {
+        let (_, house_id) = entity.rsplit_once("-House-")?;
+        let suffix = format!("-House-{house_id}");
+
+        self.entities
+            .iter()
+            .find(|name| name.ends_with(&suffix) && self.types.get(*name).map(String::as_str) == Some("zone"))
+            .map(|name| json!(name))
+    }
+
+    fn schedule_job(&mut self, entity: &str, args: &Value) -> Result<Value, ApiError> {
+        let delay = args[0].as_u64().unwrap_or(0);
+        let duration = args[1].as_u64().unwrap_or(0);
+        let Some((start_time, end_time)) = self
+            .time
+            .checked_add(delay)
+            .and_then(|start_time| Some((start_time, start_time.checked_add(duration)?)))
+        else {
+            return Ok(json!([false, "Job does not fit in the simulation time!"]));
+        };
+
+        let vars = self.vars_mut(entity)?;
+        let jobs = vars
+            .entry("jobs")
+            .or_insert_with(|| json!([]))
+            .as_array_mut()
+            .ok_or_else(|| ApiError::DemkitError(format!("{entity} has no jobs")))?;
+
+        let id = jobs.len();
+        jobs.push(json!([id, {"startTime": start_time, "endTime": end_time}]));
+
+        Ok(json!([true, id]))
+    }
+
+    /// Plugs the EV in right away, DEMKit would only do so on its next tick
+    fn start_session(&mut self, entity: &str, args: &Value) -> Result<Value, ApiError> {
+        let departure_in = args[0].as_u64().unwrap_or(0);
+        let charge = args[1].as_f64().unwrap_or(0.0);
+        let time = self.time;
+
+        let vars = self.vars_mut(entity)?;
+        if departure_in == 0 {
+            return Ok(json!([false, "Departure must be in the future!"]));
+        }
+        if charge <= 0.0 {
+            return Ok(json!([false, "Required energy must be positive!"]));
+        }
+        if vars["available"].as_bool().unwrap_or(false) {
+            return Ok(json!([false, "A session is already active!"]));
+        }
+
+        let capacity = vars["capacity"].as_f64().unwrap_or(0.0);
+        let session = json!({
+            "startTime": time,
+            "endTime": time + departure_in,
+            "charge": charge.min(capacity),
+        });
+
+        let jobs = vars
+            .entry("jobs")
+            .or_insert_with(|| json!([]))
+            .as_array_mut()
+            .ok_or_else(|| ApiError::DemkitError(format!("{entity} has no jobs")))?;
+        if jobs.last().and_then(|job| job[1]["endTime"].as_u64()).is_some_and(|end| end >= time) {
+            return Ok(json!([false, "Inconsistent job specification!"]));
+        }
+        let idx = jobs.len();
+        jobs.push(json!([idx, session.clone()]));
+
+        vars.insert("available".to_string(), Value::Bool(true));
+        vars.insert("currentJobIdx".to_string(), json!(idx));
+        vars.insert("currentJob".to_string(), session);
+        vars.insert("soc".to_string(), json!((capacity - charge).max(0.0)));
+
+        Ok(json!([true, format!("Starting session until {}", time + departure_in)]))
+    }
+
+    fn end_session(&mut self, entity: &str) -> Result<Value, ApiError> {
+        let vars = self.vars_mut(entity)?;
+
+        if !vars["available"].as_bool().unwrap_or(false) {
+            return Ok(json!([false, "No active session!"]));
+        }
+
+        vars.insert("available".to_string(), Value::Bool(false));
+        Ok(json!([true, ""]))
+    }
+
+    fn set_departure(&mut self, entity: &str, args: &Value) -> Result<Value, ApiError> {
+        let departure_time = args[0].as_u64().unwrap_or(0);
+        let time = self.time;
+
+        let vars = self.vars_mut(entity)?;
+        let available = vars["available"].as_bool().unwrap_or(false);
+        let next = (vars["currentJobIdx"].as_i64().unwrap_or(-1) + if available { 0 } else { 1 }) as usize;
+
+        let session = match vars.get_mut("jobs").and_then(|jobs| jobs.get_mut(next)) {
+            Some(job) => &mut job[1],
+            None => return Ok(json!([false, "No session to update!"])),
+        };
+
+        if departure_time <= time.max(session["startTime"].as_u64().unwrap_or(0)) {
+            return Ok(json!([false, "Departure must be after the start of the session and in the future!"]));
+        }
+
+        session["endTime"] = json!(departure_time);
+        if available {
+            vars.insert("currentJob".to_string(), vars["jobs"][next][1].clone());
+        }
+
+        Ok(json!([true, ""]))
+    }
+
+    fn set_max_charging_power(&mut self, entity: &str, args: &Value) -> Result<Value, ApiError> {
+        let power = args[0].as_f64().unwrap_or(0.0);
+
+        let vars = self.vars_mut(entity)?;
+        if power <= 0.0 {
+            return Ok(json!([false, "Charging power must be positive!"]));
+        }
+
+        vars.insert("chargingPowers".to_string(), json!([0.0, power]));
+        Ok(json!([true, ""]))
+    }
+
+    /// Scales the rated producing powers of a heat source like DEMKit's `applyPowerLimits`
+    fn apply_power_limits(vars: &mut Map<String, Value>) {
+        if vars["ratedPowers"].is_null() {
+            let rated = vars["producingPowers"].clone();
+            vars.insert("ratedPowers".to_string(), rated);
+        }
+
+        let scale = match vars["enabled"].as_bool().unwrap_or(true) {
+            true => vars["powerLevel"].as_f64().unwrap_or(1.0),
+            false => 0.0,
+        };
+        let powers: Vec<f64> = vars["ratedPowers"]
+            .as_array()
+            .map(|powers| powers.iter().filter_map(Value::as_f64).map(|power| power * scale).collect())
+            .unwrap_or_default();
+
+        vars.insert("producingPowers".to_string(), json!(powers));
+    }
+
+    fn set_setpoints(&mut self, entity: &str, args: &Value) -> Result<Value, ApiError> {
+        let vars = self.vars_mut(entity)?;
+
+        for (i, key) in [
+            "temperatureSetpointHeating",
+            "temperatureSetpointCooling",
+            "temperatureMin",
+            "temperatureMax",
+            "temperatureDeadband",
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            vars.insert(key.to_string(), args[i].clone());
+        }
+
+        Ok(json!([true, ""]))
+    }
+
+    fn set_curtailment(&mut self, entity: &str, args: &Value) -> Result<Value, ApiError> {
+        let vars = self.vars_mut(entity)?;
+
+        vars.insert("curtailLevel".to_string(), args[0].clone());
+        vars.insert("curtailLimit".to_string(), args[1].clone());
+
+        Ok(json!([true, ""]))
+    }
+
+    fn set_enabled(&mut self, entity: &str, args: &Value) -> Result<Value, ApiError> {
+        let enabled = args[0].as_bool().unwrap_or(false);
+
+        let vars = self.vars_mut(entity)?;
+        vars.insert("enabled".to_string(), Value::Bool(enabled));
+        Self::apply_power_limits(vars);
+
+        Ok(json!([true, ""]))
+    }
+
+    fn set_power_level(&mut self, entity: &str, args: &Value) -> Result<Value, ApiError> {
+        let level = args[0].as_f64().unwrap_or(-1.0);
+
+        let vars = self.vars_mut(entity)?;
+        if !(0.0..=1.0).contains(&level) {
+            return Ok(json!([false, "Power level must be between 0 and 1!"]));
+        }
+
+        vars.insert("powerLevel".to_string(), json!(level));
+        Self::apply_power_limits(vars);
+
+        Ok(json!([true, ""]))
+    }
+
+    fn set_supply_temperature(&mut self, entity: &str, args: &Value) -> Result<Value, ApiError> {
+        let temperature = args[0].as_f64().unwrap_or(0.0);
+
+        let vars = self.vars_mut(entity)?;
+        let minimum = vars["producingTemperatures"][0].as_f64().unwrap_or(0.0);
+        if temperature <= minimum {
+            return Ok(json!([false, format!("Supply temperature must be above {minimum}!")]));
+        }
+
+        vars.insert("producingTemperatures".to_string(), json!([minimum, temperature]));
+        vars.insert("temperature".to_string(), json!(temperature));
+
+        Ok(json!([true, ""]))
+    }
+
+    /// Manual battery control, only recorded since nothing is simulated
+    fn set_power_setpoint(&mut self, entity: &str, args: &Value) -> Result<Value, ApiError> {
+        let vars = self.vars_mut(entity)?;
+
+        vars.insert("powerSetpoint".to_string(), args[0].clone());
+        vars.insert("schedule".to_string(), json!([]));
+        vars.insert("scheduleEnd".to_string(), Value::Null);
+
+        Ok(json!([true, ""]))
+    }
+
+    fn set_schedule(&mut self, entity: &str, args: &Value) -> Result<Value, ApiError> {
+        let vars = self.vars_mut(entity)?;
+
+        vars.insert("schedule".to_string(), args[0].clone());
+        vars.insert("scheduleEnd".to_string(), args[1].clone());
+        vars.insert("powerSetpoint".to_string(), Value::Null);
+
+        Ok(json!([true, ""]))
+    }
+
+    /// Adds the job like DEMKit would; nothing runs it, so it keeps waiting for its start
+    fn add_flexible_job(&mut self, entity: &str, args: &Value) -> Result<Value, ApiError> {
+        let earliest_start = args[0].as_u64().unwrap_or(0);
+        let latest_end = args[1].as_u64().unwrap_or(0);
+
+        let vars = self.vars_mut(entity)?;
+        if earliest_start >= latest_end {
+            return Ok(json!([false, "Earliest start must be before the latest end!"]));
+        }
+
+        let mut job = json!({"startTime": earliest_start, "endTime": latest_end});
+        if args[2].is_array() {
+            job["profile"] = args[2].clone();
+        }
+        if let Some(start_at) = args[3].as_u64() {
+            job["startAt"] = json!(start_at);
+        }
+
+        let jobs = vars
+            .entry("jobs")
+            .or_insert_with(|| json!([]))
+            .as_array_mut()
+            .ok_or_else(|| ApiError::DemkitError(format!("{entity} has no jobs")))?;
+
+        let id = jobs.len();
+        jobs.push(json!([id, job]));
+
+        Ok(json!([true, id]))
+    }
+
+    fn set_job_start(&mut self, entity: &str, args: &Value) -> Result<Value, ApiError> {
+        let id = args[0].as_u64();
+
+        let vars = self.vars_mut(entity)?;
+        let job = vars
+            .get_mut("jobs")
+            .and_then(Value::as_array_mut)
+            .and_then(|jobs| jobs.iter_mut().find(|job| job[0].as_u64() == id));
+
+        match job.and_then(|job| job[1].as_object_mut()) {
+            Some(job) => {
+                match args[1].as_u64() {
+                    Some(start_at) => job.insert("startAt".to_string(), json!(start_at)),
+                    None => job.remove("startAt"),
+                };
+                Ok(json!([true, ""]))
+            }
+            None => Ok(json!([false, "Job not found!"])),
+        }
+    }
+
+    fn cancel_job(&mut self, entity: &str, args: &Value) -> Result<Value, ApiError> {
+        let idx = args[0].as_u64().unwrap_or(0) as usize;
+
+        let vars = self.vars_mut(entity)?;
+        let jobs = match vars.get_mut("jobs").and_then(Value::as_array_mut) {
+            Some(jobs) => jobs,
+            None => return Ok(json!([false, "Job not found!"])),
+        };
+
+        // DEMKit keeps cancelled jobs so the IDs of the others stay the same
+        match jobs.get_mut(idx).and_then(|job| job[1].as_object_mut()) {
+            Some(job) if job.contains_key("cancelled") => Ok(json!([false, "Job already finished!"])),
+            Some(job) => {
+                job.insert("cancelled".to_string(), json!(true));
+                Ok(json!([true, ""]))
+            }
+            None => Ok(json!([false, "Job not found!"])),
+        }
+    }
+}
+
+#[async_trait]
+impl DemkitBackend for FakeBackend {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    async fn composer_status(&self) -> Result<ComposerStatus, ApiError> {
+        let state = self.state.lock().unwrap();
+
+        Ok(match (state.loaded, state.running) {
+            (_, true) => ComposerStatus::Active,
+            (true, false) => ComposerStatus::Loaded,
+            (false, false) => ComposerStatus::Inactive,
+        })
+    }
+
+    async fn get_time(&self) -> Result<u64, ApiError> {
+        Ok(self.state.lock().unwrap().time)
+    }
+
+    async fn set_time(&self, time: &Time) -> Result<(), ApiError> {
+        let mut state = self.state.lock().unwrap();
+
+        if time.time < state.time {
+            return Err(ApiError::Validation(
+                "Failed to set time: Time cannot be set to the past".to_string(),
+            ));
+        }
+
+        state.time = time.time;
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<String>, ApiError> {
+        let state = self.state.lock().unwrap();
+
+        if state.loaded {
+            Ok(state.entities.clone())
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    async fn get(&self, entity: &str, property: &str) -> Result<Value, ApiError> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state.vars(entity)?.get(property).cloned().unwrap_or(Value::Null))
+    }
+
+    async fn set(&self, entity: &str, property: &str, value: &str) -> Result<(), ApiError> {
+        let mut state = self.state.lock().unwrap();
+
+        state
+            .vars_mut(entity)?
+            .insert(property.to_string(), parse_value(value));
+
+        Ok(())
+    }
+
+    async fn call(&self, entity: &str, function: &str) -> Result<Value, ApiError> {
+        let mut state = self.state.lock().unwrap();
+
+        match function {
+            "getProperties" => state.properties(entity),
+            "forceShutdown" => {
+                let vars = state.vars_mut(entity)?;
+                vars.insert("available".to_string(), Value::Bool(false));
+                vars.insert("jobProgress".to_string(), json!(0.0));
+                Ok(Value::Bool(true))
+            }
+            "endSession" => state.end_session(entity),
+            "clearJobs" => {
+                let vars = state.vars_mut(entity)?;
+                vars.insert("jobs".to_string(), json!([]));
+                Ok(json!([true, ""]))
+            }
+            _ => Err(ApiError::DemkitError(format!(
+                "Failed to call {function} on {entity}: not supported by the in-memory backend"
+            ))),
+        }
+    }
+
+    async fn callp(&self, entity: &str, function: &str, args: Value) -> Result<Value, ApiError> {
+        let mut state = self.state.lock().unwrap();
+
+        match function {
+            "scheduleJob" => state.schedule_job(entity, &args),
+            "cancelJob" => state.cancel_job(entity, &args),
+            "addFlexibleJob" => state.add_flexible_job(entity, &args),
+            "setJobStart" => state.set_job_start(entity, &args),
+            "startSession" => state.start_session(entity, &args),
+            "setDeparture" => state.set_departure(entity, &args),
+            "setMaxChargingPower" => state.set_max_charging_power(entity, &args),
+            "setPowerSetpoint" => state.set_power_setpoint(entity, &args),
+            "setSchedule" => state.set_schedule(entity, &args),
+            "setCurtailment" => state.set_curtailment(entity, &args),
+            "setSetpoints" => state.set_setpoints(entity, &args),
+            "setEnabled" => state.set_enabled(entity, &args),
+            "setPowerLevel" => state.set_power_level(entity, &args),
+            "setSupplyTemperature" => state.set_supply_temperature(entity, &args),
+            _ => Err(ApiError::DemkitError(format!(
+                "Failed to call {function} on {entity}: not supported by the in-memory backend"
+            ))),
+        }
+    }
+
+    async fn add_entity(&self, entity: &Entity) -> Result<(), ApiError> {
+        let mut state = self.state.lock().unwrap();
+
+        let name = entity.entity["name"]
+            .as_str()
+            .ok_or_else(|| ApiError::Validation(format!("Failed to add {}: missing name", entity.r#type)))?
+            .to_string();
+
+        if state.vars.contains_key(&name) {
+            return Err(ApiError::Validation(format!(
+                "Failed to add {}: {name} already exists",
+                entity.r#type
+            )));
+        }
+
+        state.entities.push(name.clone());
+        state.types.insert(name.clone(), entity.r#type.clone());
+        state
+            .vars
+            .insert(name, initial_vars(&entity.r#type, &entity.entity));
+
+        Ok(())
+    }
+
+    async fn remove_entity(&self, entity: &str) -> Result<(), ApiError> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.vars.remove(entity).is_none() {
+            return Err(unknown_entity(entity));
+        }
+
+        state.types.remove(entity);
+        state.entities.retain(|name| name != entity);
+
+        Ok(())
+    }
+
+    async fn set_config(&self, config: &SimConfig) -> Result<(), ApiError> {
+        self.state.lock().unwrap().time = config.start_time;
+
+        Ok(())
+    }
+
+    async fn load(&self) -> Result<(), ApiError> {
+        self.state.lock().unwrap().loaded = true;
+
+        Ok(())
+    }
+
+    async fn start(&self) -> Result<(), ApiError> {
+        let mut state = self.state.lock().unwrap();
+
+        if !state.loaded {
+            return Err(ApiError::DemkitError(
+                "Failed to start: nothing loaded".to_string(),
+            ));
+        }
+
+        state.running = true;
+
+        Ok(())
+    }
+
+    async fn reset(&self) -> Result<(), ApiError> {
+        *self.state.lock().unwrap() = FakeState::default();
+
+        Ok(())
+    }
+
+    async fn simulation(&self, action: SimulationAction) -> Result<(), ApiError> {
+        let mut state = self.state.lock().unwrap();
+
+        if !state.running {
+            return Err(ApiError::DemkitError(format!(
+                "Failed to {} simulation: simulation is not running",
+                action.as_str()
+            )));
+        }
+
+        if let SimulationAction::Stop = action {
+            state.running = false;
+        }
+
+        Ok(())
+    }
+
+    async fn add_ha_entity(&self, _entity_id: &str) -> Result<(), ApiError> {
+        Ok(())
+    }
+}
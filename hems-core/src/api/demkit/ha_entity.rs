@@ -1,7 +1,12 @@
-use serde_json::json;
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::RwLock,
+};
+
+use once_cell::sync::Lazy;
 use utoipa::ToSchema;
 
-use super::{ApiError, BASE_URL, CLIENT};
+use super::{ApiError, DemkitBackend};
 use crate::api::ha::{LOAD_MAP, init_load_map};
 
 #[derive(serde::Deserialize, ToSchema)]
@@ -10,23 +15,41 @@ pub struct EntityRequest {
     consumption: String,
 }
 
-pub async fn add_entity(entity: EntityRequest) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(super::init);
-    let mut load_map = LOAD_MAP.get_or_init(init_load_map).write().unwrap();
-
-    let url = format!("{}/entity", *BASE_URL);
+/// Home Assistant entities added to each house, by entity ID.
+///
+/// Like the timeshifters, it is emptied when the house is reset and does not know entities
+/// added before a restart.
+static HA_ENTITIES: Lazy<RwLock<HashMap<u32, BTreeSet<String>>>> = Lazy::new(Default::default);
+
+/// Home Assistant entities added to the house through hems-core
+pub fn list_entities(house_id: u32) -> Vec<String> {
+    HA_ENTITIES
+        .read()
+        .unwrap()
+        .get(&house_id)
+        .map(|entities| entities.iter().cloned().collect())
+        .unwrap_or_default()
+}
 
-    let request_json = json!({"entity_id": entity.entity_id});
+/// Forgets every Home Assistant entity of the house, e.g. after its DEMKit instance was reset
+pub fn unregister_house(house_id: u32) {
+    HA_ENTITIES.write().unwrap().remove(&house_id);
+}
 
-    let response = client.post(url).json(&request_json).send().await?;
+pub async fn add_entity(backend: &dyn DemkitBackend, house_id: u32, entity: EntityRequest) -> Result<(), ApiError> {
+    backend.add_ha_entity(&entity.entity_id).await?;
 
-    if !response.status().is_success() {
-        return Err(ApiError::DemkitError("Failed to add device".to_string()));
-    }
+    HA_ENTITIES
+        .write()
+        .unwrap()
+        .entry(house_id)
+        .or_default()
+        .insert(entity.entity_id.clone());
 
     if entity.consumption != "-1" {
+        let mut load_map = LOAD_MAP.get_or_init(init_load_map).write().unwrap();
         load_map.insert(entity.entity_id.to_string(), entity.consumption);
     }
 
-    return Ok(());
+    Ok(())
 }
@@ -0,0 +1,284 @@
+use std::{collections::HashMap, sync::Arc, sync::RwLock, time::Duration};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{
+    check_response,
+    registry::Registry,
+    thermal::{self, ThermostatProperties},
+    ApiError, DemkitBackend,
+};
+
+/// How often the schedules are checked against the simulation time of each house
+const PUSH_INTERVAL: Duration = Duration::from_secs(5);
+const DAY: u64 = 24 * 3600;
+const WEEK: u64 = 7 * DAY;
+/// 1970-01-01, the start of Unix time, was a Thursday
+const EPOCH_WEEKDAY: u64 = 3;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, ToSchema)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, ToSchema)]
+pub enum ScheduleMode {
+    /// Keep the zone within a degree of the setpoint
+    Comfort,
+    /// Only heat below the setpoint, letting the zone drift while nobody is home
+    Away,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct ScheduleEntryParams {
+    pub day: Weekday,
+    /// Time of day the entry takes effect, as `HH:MM` in UTC
+    #[schema(example = "07:30")]
+    pub time: String,
+    /// Setpoint in Celsius, checked against the other thermostat settings like a thermostat update
+    pub setpoint: f64,
+    pub mode: ScheduleMode,
+}
+
+#[derive(Serialize, Clone, Debug, ToSchema)]
+pub struct ScheduleEntry {
+    pub id: u32,
+    pub day: Weekday,
+    /// Time of day the entry takes effect, as `HH:MM` in UTC
+    pub time: String,
+    /// Setpoint in Celsius
+    pub setpoint: f64,
+    pub mode: ScheduleMode,
+    /// Seconds since Monday 00:00
+    #[serde(skip)]
+    week_offset: u64,
+}
+
+#[derive(Default)]
+struct ZoneSchedule {
+    entries: Vec<ScheduleEntry>,
+    next_id: u32,
+    /// Entry last pushed to the thermostat, as `(id, setpoint, mode)`
+    pushed: Option<(u32, f64, ScheduleMode)>,
+}
+
+/// Schedules by house ID and zone
+static SCHEDULES: Lazy<RwLock<HashMap<(u32, String), ZoneSchedule>>> = Lazy::new(Default::default);
+
+impl ScheduleEntry {
+    /// Entry whose setpoints the thermostat accepts with its current settings
+    fn new(id: u32, params: ScheduleEntryParams, thermostat: &ThermostatProperties) -> Result<Self, ApiError> {
+        // the setpoints `apply` sets for the entry
+        let (heating, cooling) = match params.mode {
+            ScheduleMode::Comfort => thermal::target_setpoints(params.setpoint),
+            ScheduleMode::Away => thermal::heating_setpoints(thermostat, params.setpoint),
+        };
+        thermal::check_setpoints(
+            heating,
+            cooling,
+            thermostat.temperature_min,
+            thermostat.temperature_max,
+            &thermostat.temperature_deadband,
+        )?;
+
+        let minutes = parse_time(&params.time).ok_or_else(|| {
+            ApiError::Validation(format!("Invalid time: {}. Time must be HH:MM", params.time))
+        })?;
+
+        Ok(ScheduleEntry {
+            id,
+            day: params.day,
+            time: format!("{:02}:{:02}", minutes / 60, minutes % 60),
+            setpoint: params.setpoint,
+            mode: params.mode,
+            week_offset: params.day as u64 * DAY + minutes * 60,
+        })
+    }
+}
+
+/// Minutes since midnight of an `HH:MM` time
+fn parse_time(time: &str) -> Option<u64> {
+    let (hours, minutes) = time.split_once(':')?;
+    if hours.is_empty() || hours.len() > 2 || minutes.len() != 2 {
+        return None;
+    }
+
+    let (hours, minutes) = (hours.parse::<u64>().ok()?, minutes.parse::<u64>().ok()?);
+
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+/// Entries of the zone, in the order they take effect during the week
+pub fn list(house_id: u32, zone: &str) -> Vec<ScheduleEntry> {
+    SCHEDULES
+        .read()
+        .unwrap()
+        .get(&(house_id, zone.to_string()))
+        .map(|schedule| schedule.entries.clone())
+        .unwrap_or_default()
+}
+
+pub fn get(house_id: u32, zone: &str, id: u32) -> Result<ScheduleEntry, ApiError> {
+    list(house_id, zone)
+        .into_iter()
+        .find(|entry| entry.id == id)
+        .ok_or_else(|| not_found(house_id, zone, id))
+}
+
+/// Adds an entry for the thermostat of the zone
+pub fn add(
+    house_id: u32,
+    zone: &str,
+    params: ScheduleEntryParams,
+    thermostat: &ThermostatProperties,
+) -> Result<ScheduleEntry, ApiError> {
+    let mut schedules = SCHEDULES.write().unwrap();
+    let schedule = schedules.entry((house_id, zone.to_string())).or_default();
+
+    let entry = ScheduleEntry::new(schedule.next_id, params, thermostat)?;
+    schedule.next_id += 1;
+    schedule.entries.push(entry.clone());
+    sort(schedule);
+
+    Ok(entry)
+}
+
+pub fn update(
+    house_id: u32,
+    zone: &str,
+    id: u32,
+    params: ScheduleEntryParams,
+    thermostat: &ThermostatProperties,
+) -> Result<ScheduleEntry, ApiError> {
+    let entry = ScheduleEntry::new(id, params, thermostat)?;
+
+    let mut schedules = SCHEDULES.write().unwrap();
+    let schedule = schedules
+        .get_mut(&(house_id, zone.to_string()))
+        .ok_or_else(|| not_found(house_id, zone, id))?;
+    let current = schedule
+        .entries
+        .iter_mut()
+        .find(|entry| entry.id == id)
+        .ok_or_else(|| not_found(house_id, zone, id))?;
+
+    *current = entry.clone();
+    sort(schedule);
+
+    Ok(entry)
+}
+
+pub fn remove(house_id: u32, zone: &str, id: u32) -> Result<(), ApiError> {
+    let mut schedules = SCHEDULES.write().unwrap();
+    let schedule = schedules
+        .get_mut(&(house_id, zone.to_string()))
+        .ok_or_else(|| not_found(house_id, zone, id))?;
+
+    let len = schedule.entries.len();
+    schedule.entries.retain(|entry| entry.id != id);
+
+    match schedule.entries.len() < len {
+        true => Ok(()),
+        false => Err(not_found(house_id, zone, id)),
+    }
+}
+
+/// Removes every entry, the thermostat keeps its last setpoint
+pub fn clear(house_id: u32, zone: &str) {
+    SCHEDULES.write().unwrap().remove(&(house_id, zone.to_string()));
+}
+
+/// Pushes the active entries of all zones again on the next check, e.g. after the house was
+/// composed again
+pub fn resend(house_id: u32) {
+    for ((schedule_house, _), schedule) in SCHEDULES.write().unwrap().iter_mut() {
+        if *schedule_house == house_id {
+            schedule.pushed = None;
+        }
+    }
+}
+
+fn not_found(house_id: u32, zone: &str, id: u32) -> ApiError {
+    ApiError::NotFound(format!("Zone {zone} of house {house_id} has no schedule entry {id}"))
+}
+
+fn sort(schedule: &mut ZoneSchedule) {
+    schedule.entries.sort_by_key(|entry| entry.week_offset);
+    // an edit may change the active entry, so check it again
+    schedule.pushed = None;
+}
+
+/// Entry in effect at `time`, the last entry of the week stays active until the first one
+fn active_entry(entries: &[ScheduleEntry], time: u64) -> Option<&ScheduleEntry> {
+    let week_offset = (time + EPOCH_WEEKDAY * DAY) % WEEK;
+
+    entries
+        .iter()
+        .rev()
+        .find(|entry| entry.week_offset <= week_offset)
+        .or(entries.last())
+}
+
+/// Sets the thermostat of the zone to the entry active at the simulation time, if it changed
+/// since the last push
+pub async fn apply(backend: &dyn DemkitBackend, house_id: u32, zone: &str) -> Result<(), ApiError> {
+    let time = backend.get_time().await?;
+    let key = (house_id, zone.to_string());
+
+    let entry = {
+        let schedules = SCHEDULES.read().unwrap();
+        let Some(schedule) = schedules.get(&key) else {
+            return Ok(());
+        };
+
+        match active_entry(&schedule.entries, time) {
+            Some(entry) if schedule.pushed != Some((entry.id, entry.setpoint, entry.mode)) => entry.clone(),
+            _ => return Ok(()),
+        }
+    };
+
+    let thermostat = thermal::get_thermostat_properties(backend, house_id, zone).await?.name;
+
+    // the heating intervals DEMKit reads from its thermostat files would override the schedule
+    let response = backend.call(&thermostat, "clearJobs").await?;
+    check_response(&thermostat, response, "clear the thermostat jobs")?;
+
+    match entry.mode {
+        ScheduleMode::Comfort => thermal::set_target_temp(backend, house_id, zone, entry.setpoint).await?,
+        ScheduleMode::Away => thermal::set_heating_setpoint(backend, house_id, zone, entry.setpoint).await?,
+    }
+
+    if let Some(schedule) = SCHEDULES.write().unwrap().get_mut(&key) {
+        schedule.pushed = Some((entry.id, entry.setpoint, entry.mode));
+    }
+
+    Ok(())
+}
+
+/// Keeps the thermostats of all zones with a schedule in line with their simulation time
+pub async fn run(registry: Arc<Registry>) {
+    let mut interval = actix_web::rt::time::interval(PUSH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let zones: Vec<(u32, String)> = SCHEDULES.read().unwrap().keys().cloned().collect();
+
+        for (house_id, zone) in zones {
+            let backend = registry.backend(house_id);
+
+            // houses that are not composed or loaded yet are tried again on the next check
+            if let Err(e) = apply(backend.as_ref(), house_id, &zone).await {
+                log::debug!("Failed to apply the heating schedule of zone {zone} of house {house_id}: {e}");
+            }
+        }
+    }
+}
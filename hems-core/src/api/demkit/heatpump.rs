@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use num_complex::Complex;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::devices::get_device_property;
+use super::{check_response, parse_complex_str, ApiError, Commodities, Commodity, DemkitBackend};
+
+/// Heat source heating the zones, a heat pump in the default house spec
+pub fn heat_source_entity(house_id: u32) -> String {
+    format!("HeatPump-House-{house_id}")
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+pub struct HeatSourceProperties {
+    pub name: String,
+    pub devtype: String,
+    pub commodities: Vec<String>,
+    #[serde(rename = "consumption")]
+    _consumption: Commodities,
+    /// Zero for heat sources without an electricity input, e.g. gas boilers
+    #[serde(skip)]
+    pub electricity_consumption: Complex<f64>,
+    /// Supply temperature in Celsius
+    pub temperature: f64,
+    /// Heat produced per unit of each input commodity
+    pub cop: HashMap<String, f64>,
+    #[serde(rename = "producingPowers")]
+    pub producing_powers: Vec<f64>,
+
+    #[serde(skip)]
+    pub heat_production: f64,
+    #[serde(skip)]
+    pub enabled: bool,
+    #[serde(skip)]
+    pub power_level: f64,
+}
+
+pub async fn get_heat_source_properties(
+    backend: &dyn DemkitBackend,
+    entity: &str,
+) -> Result<HeatSourceProperties, ApiError> {
+    let response = backend.properties(entity).await?;
+
+    let mut response_body = serde_json::from_value::<HeatSourceProperties>(response)?;
+
+    response_body.electricity_consumption = match &response_body._consumption.electricity {
+        Some(commodity) => parse_complex_str(commodity)?,
+        None => Complex::new(0.0, 0.0),
+    };
+
+    // not part of DEMKit's properties, the heat production may be a complex number
+    let heat_production = get_device_property::<Commodity>(backend, entity, "heatProduction").await?;
+    response_body.heat_production = parse_complex_str(&heat_production)?.re;
+    response_body.enabled = get_device_property::<bool>(backend, entity, "enabled").await?;
+    response_body.power_level = get_device_property::<f64>(backend, entity, "powerLevel").await?;
+
+    Ok(response_body)
+}
+
+pub async fn get_heatpump_properties(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+) -> Result<HeatSourceProperties, ApiError> {
+    get_heat_source_properties(backend, &heat_source_entity(house_id)).await
+}
+
+pub async fn set_enabled(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    enabled: bool,
+) -> Result<HeatSourceProperties, ApiError> {
+    let entity = heat_source_entity(house_id);
+
+    let response = backend.callp(&entity, "setEnabled", json!([enabled])).await?;
+    check_response(&entity, response, "switch the heat pump")?;
+
+    get_heatpump_properties(backend, house_id).await
+}
+
+/// Sets the share of the rated heat output the heat pump may produce, as a percentage
+pub async fn set_power_level(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    level: f64,
+) -> Result<HeatSourceProperties, ApiError> {
+    if !(0.0..=100.0).contains(&level) {
+        return Err(ApiError::Validation(format!(
+            "Invalid power level: {level}. Power level must be between 0 and 100 percent"
+        )));
+    }
+
+    let entity = heat_source_entity(house_id);
+
+    let response = backend.callp(&entity, "setPowerLevel", json!([level / 100.0])).await?;
+    check_response(&entity, response, "set the power level")?;
+
+    get_heatpump_properties(backend, house_id).await
+}
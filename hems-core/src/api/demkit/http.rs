@@ -0,0 +1,230 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use super::{
+    backend::{ComposerStatus, DemkitBackend, SimulationAction},
+    env::{Entity, SimConfig},
+    sim::Time,
+    ApiError,
+};
+use crate::api::upstream::{self, Call, CircuitBreaker, Policy};
+
+/// DEMKit instance reached over its REST API
+pub struct HttpBackend {
+    client: reqwest::Client,
+    base_url: String,
+    policy: Policy,
+    breaker: CircuitBreaker,
+}
+
+impl HttpBackend {
+    pub fn new(client: reqwest::Client, policy: &Policy, base_url: &str) -> Self {
+        Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            policy: policy.clone(),
+            breaker: CircuitBreaker::new(policy),
+        }
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder, call: Call) -> Result<reqwest::Response, ApiError> {
+        Ok(upstream::send("DEMKit", &self.policy, &self.breaker, request, call).await?)
+    }
+
+    /// Maps a failed DEMKit response, which answers 400 for rejected input and 404 for
+    /// unknown entities or variables
+    async fn error(response: reqwest::Response, action: &str) -> ApiError {
+        let status = response.status();
+        let error_message = response.text().await.unwrap_or_default();
+        let message = format!("Failed to {action}: {}", error_message);
+
+        match status {
+            reqwest::StatusCode::BAD_REQUEST => ApiError::Validation(message),
+            reqwest::StatusCode::NOT_FOUND => ApiError::UnknownEntity(message),
+            _ => ApiError::DemkitError(message),
+        }
+    }
+
+    async fn expect_success(response: reqwest::Response, action: &str) -> Result<(), ApiError> {
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(Self::error(response, action).await)
+        }
+    }
+
+    /// DEMKit answers calls returning `None` with an empty body
+    async fn json_or_null(response: reqwest::Response, action: &str) -> Result<Value, ApiError> {
+        if !response.status().is_success() {
+            return Err(Self::error(response, action).await);
+        }
+
+        let body = response.text().await?;
+
+        if body.trim().is_empty() {
+            Ok(Value::Null)
+        } else {
+            Ok(serde_json::from_str(&body)?)
+        }
+    }
+}
+
+#[async_trait]
+impl DemkitBackend for HttpBackend {
+    fn url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn breaker(&self) -> Option<&CircuitBreaker> {
+        Some(&self.breaker)
+    }
+
+    async fn composer_status(&self) -> Result<ComposerStatus, ApiError> {
+        let url = format!("{}/composer", self.base_url);
+
+        let response = self.send(self.client.get(url), Call::Idempotent).await?;
+
+        if !response.status().is_success() {
+            return Err(Self::error(response, "get composer status").await);
+        }
+
+        ComposerStatus::try_from(response.text().await?.as_str())
+    }
+
+    async fn get_time(&self) -> Result<u64, ApiError> {
+        let url = format!("{}/time", self.base_url);
+
+        let response = self.send(self.client.get(url), Call::Idempotent).await?;
+
+        Ok(response.json::<u64>().await?)
+    }
+
+    async fn set_time(&self, time: &Time) -> Result<(), ApiError> {
+        let url = format!("{}/time", self.base_url);
+
+        let response = self.send(self.client.post(url).json(time), Call::Once).await?;
+
+        Self::expect_success(response, "set time").await
+    }
+
+    async fn list(&self) -> Result<Vec<String>, ApiError> {
+        let url = format!("{}/list", self.base_url);
+
+        let response = self.send(self.client.get(url), Call::Idempotent).await?;
+
+        Ok(response.json::<Vec<String>>().await?)
+    }
+
+    async fn get(&self, entity: &str, property: &str) -> Result<Value, ApiError> {
+        let url = format!("{}/get/{entity}/{property}", self.base_url);
+
+        let response = self.send(self.client.get(url), Call::Idempotent).await?;
+
+        Self::json_or_null(response, &format!("get {property} of {entity}")).await
+    }
+
+    async fn set(&self, entity: &str, property: &str, value: &str) -> Result<(), ApiError> {
+        let url = format!("{}/set/{entity}/{property}/{value}", self.base_url);
+
+        let response = self.send(self.client.get(url), Call::Idempotent).await?;
+
+        Self::expect_success(response, &format!("set {property} of {entity}")).await
+    }
+
+    async fn call(&self, entity: &str, function: &str) -> Result<Value, ApiError> {
+        let url = format!("{}/call/{entity}/{function}", self.base_url);
+
+        let response = self.send(self.client.get(url), Call::Once).await?;
+
+        Self::json_or_null(response, &format!("call {function} on {entity}")).await
+    }
+
+    async fn callp(&self, entity: &str, function: &str, args: Value) -> Result<Value, ApiError> {
+        let url = format!("{}/callp/{entity}/{function}", self.base_url);
+
+        let response = self.send(self.client.put(url).json(&args), Call::Once).await?;
+
+        Self::json_or_null(response, &format!("call {function} on {entity}")).await
+    }
+
+    /// Unlike other calls, reading properties has no side effects and is retried
+    async fn properties(&self, entity: &str) -> Result<Value, ApiError> {
+        let url = format!("{}/call/{entity}/getProperties", self.base_url);
+
+        let response = self.send(self.client.get(url), Call::Idempotent).await?;
+
+        match Self::json_or_null(response, &format!("get properties of {entity}")).await? {
+            Value::Null => Err(ApiError::UnknownEntity(entity.to_string())),
+            properties => Ok(properties),
+        }
+    }
+
+    async fn add_entity(&self, entity: &Entity) -> Result<(), ApiError> {
+        let url = format!("{}/composer/entities", self.base_url);
+
+        let response = self.send(self.client.put(url).json(entity), Call::Once).await?;
+
+        Self::expect_success(response, &format!("add {}", entity.r#type)).await
+    }
+
+    async fn remove_entity(&self, entity: &str) -> Result<(), ApiError> {
+        let url = format!("{}/composer/entities/{entity}", self.base_url);
+
+        let response = self.send(self.client.delete(url), Call::Once).await?;
+
+        Self::expect_success(response, &format!("remove {entity}")).await
+    }
+
+    async fn set_config(&self, config: &SimConfig) -> Result<(), ApiError> {
+        let url = format!("{}/composer/config", self.base_url);
+
+        let response = self.send(self.client.post(url).json(config), Call::Once).await?;
+
+        Self::expect_success(response, "set config").await
+    }
+
+    async fn load(&self) -> Result<(), ApiError> {
+        let url = format!("{}/composer/load", self.base_url);
+
+        let response = self.send(self.client.post(url), Call::Slow).await?;
+
+        Self::expect_success(response, "load").await
+    }
+
+    async fn start(&self) -> Result<(), ApiError> {
+        let url = format!("{}/composer/start", self.base_url);
+
+        let response = self.send(self.client.post(url), Call::Slow).await?;
+
+        Self::expect_success(response, "start").await
+    }
+
+    async fn reset(&self) -> Result<(), ApiError> {
+        let url = format!("{}/composer/reset", self.base_url);
+
+        let response = self.send(self.client.post(url), Call::Slow).await?;
+
+        Self::expect_success(response, "reset").await
+    }
+
+    async fn simulation(&self, action: SimulationAction) -> Result<(), ApiError> {
+        let url = format!("{}/simulation/{}", self.base_url, action.as_str());
+
+        let response = self.send(self.client.post(url), Call::Once).await?;
+
+        Self::expect_success(response, &format!("{} simulation", action.as_str())).await
+    }
+
+    async fn add_ha_entity(&self, entity_id: &str) -> Result<(), ApiError> {
+        let url = format!("{}/entity", self.base_url);
+
+        let request = self.client.post(url).json(&json!({"entity_id": entity_id}));
+        let response = self.send(request, Call::Once).await?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::DemkitError("Failed to add device".to_string()));
+        }
+
+        Ok(())
+    }
+}
@@ -1,15 +1,119 @@
-use super::{init, parse_complex_str, ApiError, Commodities, Measurement, BASE_URL, CLIENT};
+use std::collections::BTreeMap;
 
-pub async fn get_energy_import(house_id: u32) -> Result<Measurement, ApiError> {
-    let client = CLIENT.get_or_init(init);
+use futures::future::join_all;
+use serde::Deserialize;
 
-    let url = format!("{}/get/SmartMeter-House-{house_id}/consumption", *BASE_URL);
+use super::devices::get_device_property;
+use super::{parse_complex_str, ApiError, Commodities, Commodity, DemkitBackend, Measurement};
 
-    let response = client.get(url).send().await?;
+/// Energy content of Groningen natural gas, which DEMKit meters as chemical power, in J/m³
+const NATGAS_ENERGY_DENSITY: f64 = 35.17e6;
 
-    let response_body = response.json::<Commodities>().await?;
+#[derive(Deserialize, Debug)]
+pub struct MeterProperties {
+    pub name: String,
+    #[serde(rename = "timeBase")]
+    pub time_base: u64,
+    pub commodities: Vec<String>,
+    #[serde(rename = "consumption")]
+    _consumption: BTreeMap<String, Commodity>,
+    /// Flow per commodity in W, negative when exporting
+    #[serde(skip)]
+    pub flows: BTreeMap<String, f64>,
+    /// Sum of the imported power over all ticks, in W per tick
+    #[serde(skip)]
+    pub imported: f64,
+    /// Sum of the exported power over all ticks, in W per tick
+    #[serde(skip)]
+    pub exported: f64,
+}
+
+impl MeterProperties {
+    /// Converts a counter summed every tick to Wh
+    pub fn energy(&self, counter: f64) -> f64 {
+        counter * self.time_base as f64 / 3600.0
+    }
+}
+
+pub async fn get_meter_properties(
+    backend: &dyn DemkitBackend,
+    entity: &str,
+) -> Result<MeterProperties, ApiError> {
+    let response = backend.properties(entity).await?;
+
+    meter_from_properties(backend, entity, response).await
+}
+
+/// Builds the meter from properties already fetched, only reading the energy counters
+async fn meter_from_properties(
+    backend: &dyn DemkitBackend,
+    entity: &str,
+    properties: serde_json::Value,
+) -> Result<MeterProperties, ApiError> {
+    let mut response_body = serde_json::from_value::<MeterProperties>(properties)?;
+
+    response_body.flows = response_body
+        ._consumption
+        .iter()
+        .map(|(commodity, flow)| Ok((commodity.clone(), parse_complex_str(flow)?.re)))
+        .collect::<Result<_, ApiError>>()?;
+    response_body.imported = get_device_property(backend, entity, "imported").await?;
+    response_body.exported = get_device_property(backend, entity, "exported").await?;
+
+    Ok(response_body)
+}
+
+/// Meters of the house, optionally only those metering `commodity`
+pub async fn list_meters(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    commodity: Option<&str>,
+) -> Result<Vec<MeterProperties>, ApiError> {
+    let entities = super::list_entities(backend, house_id).await?;
+
+    let properties = join_all(entities.iter().map(|entity| backend.properties(entity))).await;
+
+    let mut meters = Vec::new();
+    for (entity, properties) in entities.iter().zip(properties) {
+        let properties = match properties {
+            Ok(properties) => properties,
+            // controllers and other entities without properties
+            Err(ApiError::UnknownEntity(_)) => continue,
+            Err(e) => {
+                log::warn!("Skipping {entity} while listing the meters of house {house_id}: {e}");
+                continue;
+            }
+        };
+
+        if properties["devtype"] != "Meter" {
+            continue;
+        }
 
-    let power = parse_complex_str(&response_body.electricity.expect("Electricity commodity not found"))?;
+        let meter = meter_from_properties(backend, entity, properties).await?;
+        if commodity.is_none_or(|commodity| meter.commodities.iter().any(|c| c == commodity)) {
+            meters.push(meter);
+        }
+    }
+
+    Ok(meters)
+}
+
+pub struct GasMeterReading {
+    /// Current gas flow
+    pub flow: Measurement,
+    /// Gas used since the simulation started
+    pub total: Measurement,
+    /// Current gas consumption as chemical power
+    pub power: Measurement,
+}
+
+pub async fn get_energy_import(backend: &dyn DemkitBackend, house_id: u32) -> Result<Measurement, ApiError> {
+    let entity = format!("SmartMeter-House-{house_id}");
+    let response = backend.get(&entity, "consumption").await?;
+
+    let response_body = serde_json::from_value::<Commodities>(response)?;
+
+    let power = response_body.electricity(&entity)?;
 
     let cons = power.norm() * power.re.signum();
 
@@ -19,16 +123,13 @@ pub async fn get_energy_import(house_id: u32) -> Result<Measurement, ApiError> {
     })
 }
 
-pub async fn get_energy_export(house_id: u32) -> Result<Measurement, ApiError> {
-    let client = CLIENT.get_or_init(init);
+pub async fn get_energy_export(backend: &dyn DemkitBackend, house_id: u32) -> Result<Measurement, ApiError> {
+    let entity = format!("SmartMeter-House-{house_id}");
+    let response = backend.get(&entity, "consumption").await?;
 
-    let url = format!("{}/get/SmartMeter-House-{house_id}/consumption", *BASE_URL);
+    let response_body = serde_json::from_value::<Commodities>(response)?;
 
-    let response = client.get(url).send().await?;
-
-    let response_body = response.json::<Commodities>().await?;
-
-    let power = parse_complex_str(&response_body.electricity.expect("Electricity commodity not found"))?;
+    let power = response_body.electricity(&entity)?;
 
     let cons = -power.norm() * power.re.signum();
 
@@ -38,3 +139,26 @@ pub async fn get_energy_export(house_id: u32) -> Result<Measurement, ApiError> {
     })
 }
 
+/// Gas meter of the house, reading the NATGAS commodity
+pub async fn get_gas_meter(backend: &dyn DemkitBackend, house_id: u32) -> Result<GasMeterReading, ApiError> {
+    let entity = format!("SmartGasMeter-House-{house_id}");
+    let meter = get_meter_properties(backend, &entity).await?;
+
+    let response = backend.get(&entity, "consumption").await?;
+    let power = serde_json::from_value::<Commodities>(response)?.natgas(&entity)?;
+
+    Ok(GasMeterReading {
+        flow: Measurement {
+            value: (power.re * 3600.0 / NATGAS_ENERGY_DENSITY).max(0.0),
+            unit: String::from("m³/h"),
+        },
+        total: Measurement {
+            value: meter.energy(meter.imported) * 3600.0 / NATGAS_ENERGY_DENSITY,
+            unit: String::from("m³"),
+        },
+        power: Measurement {
+            value: power.re.max(0.0),
+            unit: String::from("W"),
+        },
+    })
+}
@@ -0,0 +1,221 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::{backend::DemkitBackend, fake::FakeBackend, http::HttpBackend, ApiError};
+use crate::{api::upstream::Policy, config::DemkitConfig};
+
+/// Maps houses to the DEMKit instance simulating them.
+///
+/// Houses without a registered backend use the instance at `DEMKIT_URL`. DEMKit composes a
+/// single host per instance, so every instance is owned by at most one composed house and
+/// instance-wide operations (pause, reset, ...) are refused for any other house.
+pub struct Registry {
+    client: reqwest::Client,
+    policy: Policy,
+    default: Arc<dyn DemkitBackend>,
+    inner: RwLock<Houses>,
+}
+
+#[derive(Default)]
+struct Houses {
+    /// Backends registered per house, from `DEMKIT_HOUSE_URLS` or the API
+    backends: HashMap<u32, Arc<dyn DemkitBackend>>,
+    /// The house composed on each DEMKit instance, by base URL
+    owners: HashMap<String, u32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct HouseBackend {
+    /// House ID
+    pub house_id: u32,
+    /// Base URL of the DEMKit instance simulating the house
+    pub url: String,
+    /// Whether the house is currently composed on that instance
+    pub composed: bool,
+}
+
+impl Registry {
+    pub fn new(policy: Policy, default_url: &str) -> Self {
+        let client = policy.client();
+        let default = connect(&client, &policy, default_url);
+
+        Self {
+            client,
+            policy,
+            default,
+            inner: RwLock::new(Houses::default()),
+        }
+    }
+
+    pub fn from_config(config: &DemkitConfig) -> Self {
+        let default_url = config.url.as_deref().unwrap_or_default();
+        let registry = Self::new(config.upstream.policy(), default_url);
+
+        for (house_id, url) in config.house_urls() {
+            let backend = registry.connect(url);
+            registry.inner.write().unwrap().backends.insert(house_id, backend);
+        }
+
+        registry
+    }
+
+    /// Reuses the backend of a known instance, so houses sharing an in-memory fake share its state
+    fn connect(&self, url: &str) -> Arc<dyn DemkitBackend> {
+        let houses = self.inner.read().unwrap();
+
+        Self::find(&houses, &self.default, url).unwrap_or_else(|| connect(&self.client, &self.policy, url))
+    }
+
+    fn find(
+        houses: &Houses,
+        default: &Arc<dyn DemkitBackend>,
+        url: &str,
+    ) -> Option<Arc<dyn DemkitBackend>> {
+        let url = url.trim_end_matches('/');
+
+        std::iter::once(default)
+            .chain(houses.backends.values())
+            .find(|backend| backend.url() == url)
+            .cloned()
+    }
+
+    /// DEMKit instance simulating the house
+    pub fn backend(&self, house_id: u32) -> Arc<dyn DemkitBackend> {
+        let houses = self.inner.read().unwrap();
+
+        houses
+            .backends
+            .get(&house_id)
+            .cloned()
+            .unwrap_or_else(|| self.default.clone())
+    }
+
+    pub fn register(&self, house_id: u32, url: &str) -> Result<(), ApiError> {
+        let current = self.backend(house_id);
+        let mut houses = self.inner.write().unwrap();
+
+        if houses.owners.get(current.url()) == Some(&house_id) {
+            return Err(ApiError::HouseConflict(format!(
+                "House {house_id} is composed on {}, reset it before moving it to another backend",
+                current.url()
+            )));
+        }
+
+        let backend = Self::find(&houses, &self.default, url)
+            .unwrap_or_else(|| connect(&self.client, &self.policy, url));
+        houses.backends.insert(house_id, backend);
+
+        Ok(())
+    }
+
+    /// Fails if the DEMKit instance of the house is owned by another composed house
+    pub fn check_owner(&self, house_id: u32) -> Result<(), ApiError> {
+        let backend = self.backend(house_id);
+        let houses = self.inner.read().unwrap();
+
+        match houses.owners.get(backend.url()) {
+            Some(owner) if *owner != house_id => Err(ApiError::HouseConflict(format!(
+                "DEMKit instance {} is in use by house {owner}",
+                backend.url()
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// DEMKit instance of the house for changing its devices, fails like [`Registry::check_owner`]
+    pub fn owned_backend(&self, house_id: u32) -> Result<Arc<dyn DemkitBackend>, ApiError> {
+        self.check_owner(house_id)?;
+        Ok(self.backend(house_id))
+    }
+
+    /// Marks the house as composed on its DEMKit instance, `true` if it did not hold it yet
+    pub fn claim(&self, house_id: u32) -> Result<bool, ApiError> {
+        let backend = self.backend(house_id);
+        let mut houses = self.inner.write().unwrap();
+
+        match houses.owners.get(backend.url()) {
+            Some(owner) if *owner != house_id => Err(ApiError::HouseConflict(format!(
+                "DEMKit instance {} is in use by house {owner}",
+                backend.url()
+            ))),
+            Some(_) => Ok(false),
+            None => {
+                houses.owners.insert(backend.url().to_string(), house_id);
+                Ok(true)
+            }
+        }
+    }
+
+    /// Releases the DEMKit instance of the house, e.g. after a reset
+    pub fn release(&self, house_id: u32) {
+        let backend = self.backend(house_id);
+        let mut houses = self.inner.write().unwrap();
+
+        if houses.owners.get(backend.url()) == Some(&house_id) {
+            houses.owners.remove(backend.url());
+        }
+    }
+
+    /// Every known DEMKit instance, once
+    pub fn backends(&self) -> Vec<Arc<dyn DemkitBackend>> {
+        let houses = self.inner.read().unwrap();
+        let mut backends = vec![self.default.clone()];
+
+        for backend in houses.backends.values() {
+            if !backends.iter().any(|known| known.url() == backend.url()) {
+                backends.push(backend.clone());
+            }
+        }
+
+        backends
+    }
+
+    pub fn list(&self) -> Vec<HouseBackend> {
+        let houses = self.inner.read().unwrap();
+
+        let mut list: Vec<HouseBackend> = houses
+            .backends
+            .iter()
+            .map(|(house_id, backend)| HouseBackend {
+                house_id: *house_id,
+                url: backend.url().to_string(),
+                composed: houses.owners.get(backend.url()) == Some(house_id),
+            })
+            .collect();
+
+        // houses composed on the default instance without an explicit registration
+        for (url, house_id) in &houses.owners {
+            if !houses.backends.contains_key(house_id) {
+                list.push(HouseBackend {
+                    house_id: *house_id,
+                    url: url.clone(),
+                    composed: true,
+                });
+            }
+        }
+
+        list.sort_by_key(|house| house.house_id);
+        list
+    }
+}
+
+/// Backend for a DEMKit URL, `memory://` URLs get an in-memory fake
+pub fn connect(client: &reqwest::Client, policy: &Policy, url: &str) -> Arc<dyn DemkitBackend> {
+    if url.starts_with("memory://") {
+        Arc::new(FakeBackend::new(url))
+    } else {
+        Arc::new(HttpBackend::new(client.clone(), policy, url))
+    }
+}
+
+/// Whether a DEMKit entity belongs to the house, based on the `-House-{id}` naming scheme
+pub fn is_house_entity(house_id: u32, entity_name: &str) -> bool {
+    let host = format!("House-{house_id}");
+
+    entity_name == host || entity_name.ends_with(&format!("-{host}"))
+}
@@ -1,77 +1,25 @@
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use super::{init, ApiError, BASE_URL, CLIENT};
+use super::{backend::SimulationAction, ApiError, DemkitBackend};
 
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct Time {
     pub time: u64,
 }
 
-pub async fn pause_simulation() -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/simulation/pause", *BASE_URL);
-    let response = client.post(&url).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to pause simulation: {}",
-            error_message
-        )))
-    }
+pub async fn pause_simulation(backend: &dyn DemkitBackend) -> Result<(), ApiError> {
+    backend.simulation(SimulationAction::Pause).await
 }
 
-pub async fn resume_simulation() -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/simulation/resume", *BASE_URL);
-    let response = client.post(&url).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to resume simulation: {}",
-            error_message
-        )))
-    }
+pub async fn resume_simulation(backend: &dyn DemkitBackend) -> Result<(), ApiError> {
+    backend.simulation(SimulationAction::Resume).await
 }
 
-pub async fn stop_simulation() -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/simulation/stop", *BASE_URL);
-    let response = client.post(&url).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to stop simulation: {}",
-            error_message
-        )))
-    }
+pub async fn stop_simulation(backend: &dyn DemkitBackend) -> Result<(), ApiError> {
+    backend.simulation(SimulationAction::Stop).await
 }
 
-pub async fn set_time(time: Time) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!("{}/time", *BASE_URL);
-    let response = client.post(&url).json(&time).send().await?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        let error_message = response.text().await?;
-        Err(ApiError::DemkitError(format!(
-            "Failed to set time: {}",
-            error_message
-        )))
-    }
-}
\ No newline at end of file
+pub async fn set_time(backend: &dyn DemkitBackend, time: Time) -> Result<(), ApiError> {
+    backend.set_time(&time).await
+}
@@ -1,7 +1,9 @@
 use num_complex::Complex;
 use serde::Deserialize;
+use serde_json::json;
+use utoipa::ToSchema;
 
-use super::{init, parse_complex_str, ApiError, Commodities, BASE_URL, CLIENT};
+use super::{check_response, env, ApiError, Commodities, DemkitBackend};
 
 #[allow(dead_code)]
 #[derive(Deserialize, Debug)]
@@ -15,42 +17,107 @@ pub struct SolarProperties {
     pub commodities: Vec<String>,
     #[serde(rename = "strictComfort")]
     pub strict_comfort: bool,
-    pub consumption: Commodities,
+    #[serde(rename = "consumption")]
+    _consumption: Commodities,
     pub size: f64,
     pub efficiency: f64,
     pub inclination: f64,
     pub azimuth: f64,
     #[serde(rename = "onOffDevice")]
     pub on_off_device: bool,
+    /// Consumption before curtailment
     #[serde(rename = "originalConsumption")]
-    _consumption: Commodities,
+    _original_consumption: Commodities,
+    /// Share of the potential production the panels may produce, 0 to 1
+    #[serde(rename = "curtailLevel", default)]
+    pub curtail_level: Option<f64>,
+    /// Maximum production in W
+    #[serde(rename = "curtailLimit", default)]
+    pub curtail_limit: Option<f64>,
+    /// Energy produced today, in local time, in Wh
+    #[serde(rename = "energyToday", default)]
+    pub energy_today: f64,
+    /// Energy produced since the simulation started in Wh
+    #[serde(rename = "energyTotal", default)]
+    pub energy_total: f64,
 
-    pub electricity_consumption: Option<Complex<f64>>,
+    /// Negative while producing
+    #[serde(skip)]
+    pub electricity_consumption: Complex<f64>,
+    /// What the panels would consume without curtailment, negative while producing
+    #[serde(skip)]
+    pub potential_consumption: Complex<f64>,
 }
 
-pub async fn get_solar_properties(house_id: u32) -> Result<SolarProperties, ApiError> {
-    let client = CLIENT.get_or_init(init);
+pub async fn get_solar_properties(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+) -> Result<SolarProperties, ApiError> {
+    let entity = format!("{}-House-{house_id}", env::SOLAR_NAME);
+    let response = backend.properties(&entity).await?;
 
-    let url = format!("{}/call/PV-House-{house_id}/getProperties", *BASE_URL);
+    let mut response_body = serde_json::from_value::<SolarProperties>(response)?;
 
-    let response = client.get(url).send().await?;
+    response_body.electricity_consumption = response_body._consumption.electricity(&entity)?;
+    response_body.potential_consumption = response_body._original_consumption.electricity(&entity)?;
 
-    let mut response_body = response.json::<SolarProperties>().await?;
+    Ok(response_body)
+}
 
-    response_body.electricity_consumption =
-        Some(parse_complex_str(&response_body._consumption.electricity.clone().expect("Electricity commodity not found"))?);
+pub async fn set_solar_state(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    state: bool,
+) -> Result<(), ApiError> {
+    let state = if state { "True" } else { "False" };
 
-    Ok(response_body)
+    backend
+        .set(&format!("{}-House-{house_id}", env::SOLAR_NAME), "onOffDevice", state)
+        .await
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct Curtailment {
+    /// Share of the potential production the panels may produce, in percentage
+    #[schema(minimum = 0.0, maximum = 100.0)]
+    pub percentage: Option<f64>,
+    /// Maximum production in W
+    #[schema(minimum = 0.0)]
+    pub max_power: Option<f64>,
 }
 
-pub async fn set_solar_state(house_id: u32, state: bool) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
+/// Limits the production of the panels, clearing the limits when `curtailment` is `None`
+pub async fn set_curtailment(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    curtailment: Option<Curtailment>,
+) -> Result<SolarProperties, ApiError> {
+    let (percentage, max_power) = match curtailment {
+        Some(Curtailment { percentage: None, max_power: None }) => {
+            return Err(ApiError::Validation(
+                "Invalid curtailment: give a percentage, a maximum power or both".to_string(),
+            ));
+        }
+        Some(curtailment) => (curtailment.percentage, curtailment.max_power),
+        None => (None, None),
+    };
 
-    let state = if state { "True" } else { "False" };
+    if let Some(percentage) = percentage.filter(|p| !(0.0..=100.0).contains(p)) {
+        return Err(ApiError::Validation(format!(
+            "Invalid percentage: {percentage}. Percentage must be between 0 and 100"
+        )));
+    }
+    if let Some(max_power) = max_power.filter(|p| *p < 0.0) {
+        return Err(ApiError::Validation(format!(
+            "Invalid maximum power: {max_power}. Maximum power must not be negative"
+        )));
+    }
 
-    let url = format!("{}/set/PV-House-{house_id}/onOffDevice/{state}", *BASE_URL);
+    let entity = format!("{}-House-{house_id}", env::SOLAR_NAME);
+    let level = percentage.map(|p| p / 100.0);
 
-    client.get(url).send().await?;
+    let response = backend.callp(&entity, "setCurtailment", json!([level, max_power])).await?;
+    check_response(&entity, response, "curtail the solar panels")?;
 
-    Ok(())
+    get_solar_properties(backend, house_id).await
 }
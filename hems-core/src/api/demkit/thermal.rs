@@ -1,7 +1,10 @@
+use futures::future::join_all;
 use num_complex::Complex;
 use serde::Deserialize;
+use serde_json::json;
+use utoipa::ToSchema;
 
-use super::{init, parse_complex_str, ApiError, Commodities, BASE_URL, CLIENT};
+use super::{check_response, ApiError, Commodities, DemkitBackend};
 
 #[allow(dead_code)]
 #[derive(Deserialize, Debug)]
@@ -9,87 +12,300 @@ pub struct ZoneProperties {
     pub temperature: f64,
     #[serde(rename = "valveHeat")]
     pub valve_heat: f64,
+    /// Maximum heating power of the heat emitter in W
+    #[serde(rename = "maxHeat")]
+    pub max_heat: f64,
+    /// Maximum cooling power of the heat emitter in W, negative
+    #[serde(rename = "minHeat")]
+    pub min_heat: f64,
     #[serde(rename = "consumption")]
     _consumption: Commodities,
-    pub heat_consumption: Option<Complex<f64>>,
+    #[serde(skip)]
+    pub heat_consumption: Complex<f64>,
 }
 
 #[allow(dead_code)]
 #[derive(Deserialize, Debug)]
 pub struct ThermostatProperties {
+    /// Entity name of the thermostat
+    #[serde(skip)]
+    pub name: String,
+    /// Entity name of the zone the thermostat controls
+    pub zone: String,
+    /// Heating (positive) or cooling (negative) power the thermostat requests in W
+    pub heat_demand: f64,
+    /// Current heating setpoint
     pub min_target_temp: f64,
+    /// Current cooling setpoint
     pub max_target_temp: f64,
+    /// Heating setpoint outside the heating intervals
+    pub temperature_min: f64,
+    /// Cooling setpoint outside the heating intervals
+    pub temperature_max: f64,
+    /// Offsets from the setpoints at which heating and cooling start and stop
+    pub temperature_deadband: Vec<f64>,
 }
 
+/// Changes to the thermostat, settings that are not given keep their current value
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct ThermostatUpdate {
+    /// Temperature below which the zone is heated, in Celsius
+    pub heating_setpoint: Option<f64>,
+    /// Temperature above which the zone is cooled, in Celsius
+    pub cooling_setpoint: Option<f64>,
+    /// Heating setpoint outside the heating intervals, in Celsius
+    pub min_temperature: Option<f64>,
+    /// Cooling setpoint outside the heating intervals, in Celsius
+    pub max_temperature: Option<f64>,
+    /// Offsets in Celsius: heating starts below the heating setpoint plus the first and stops
+    /// above it plus the second, cooling stops below the cooling setpoint plus the third and
+    /// starts above it plus the fourth
+    #[schema(example = json!([-0.1, 0.0, 0.5, 0.6]))]
+    pub deadband: Option<Vec<f64>>,
+}
 
-pub async fn get_current_zone_temp(house_id: u32) -> Result<ZoneProperties, ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let url = format!(
-        "{}/call/Zone-House-{house_id}/getProperties",
-        *BASE_URL
-    );
+fn zone_entity(house_id: u32, zone: &str) -> String {
+    format!("{zone}-House-{house_id}")
+}
 
-    let response = client.get(url).send().await?;
+pub async fn get_current_zone_temp(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    zone: &str,
+) -> Result<ZoneProperties, ApiError> {
+    let entity = zone_entity(house_id, zone);
+    let response = backend.properties(&entity).await?;
 
-    let mut response_body = response.json::<ZoneProperties>().await?;
-    response_body.heat_consumption =
-        Some(parse_complex_str(&response_body._consumption.heat.clone().expect("Heat commodity not found"))?);
+    let mut response_body = serde_json::from_value::<ZoneProperties>(response)?;
+    response_body.heat_consumption = response_body._consumption.heat(&entity)?;
 
     Ok(response_body)
 }
 
-pub async fn get_thermostat_properties(house_id: u32) -> Result<ThermostatProperties, ApiError> {
-    let client = CLIENT.get_or_init(init);
+/// Thermostats of the house, one per zone
+pub async fn list_thermostats(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+) -> Result<Vec<ThermostatProperties>, ApiError> {
+    let entities = super::list_entities(backend, house_id).await?;
 
-    let url = format!(
-        "{}/call/Thermostat-House-{house_id}/getProperties",
-        *BASE_URL
-    );
+    let properties = join_all(entities.iter().map(|entity| backend.properties(entity))).await;
 
-    let response = client.get(url).send().await?;
+    let mut thermostats = Vec::new();
+    for (entity, properties) in entities.into_iter().zip(properties) {
+        let properties = match properties {
+            Ok(properties) => properties,
+            // controllers and other entities without properties
+            Err(ApiError::UnknownEntity(_)) => continue,
+            Err(e) => return Err(e),
+        };
 
-    let response_body = response.json::<ThermostatProperties>().await?;
+        if properties["devtype"] != "Thermostat" {
+            continue;
+        }
 
-    Ok(response_body)
+        let mut thermostat = serde_json::from_value::<ThermostatProperties>(properties)?;
+        thermostat.name = entity;
+        thermostats.push(thermostat);
+    }
+
+    Ok(thermostats)
 }
 
-const DELTA_TEMP: f64 = 1.0;
+/// Thermostat controlling the zone
+pub async fn get_thermostat_properties(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    zone: &str,
+) -> Result<ThermostatProperties, ApiError> {
+    let entity = zone_entity(house_id, zone);
+
+    list_thermostats(backend, house_id)
+        .await?
+        .into_iter()
+        .find(|thermostat| thermostat.zone == entity)
+        .ok_or_else(|| ApiError::NotFound(format!("House {house_id} has no thermostat for zone {zone}")))
+}
 
-pub async fn set_target_temp(house_id: u32, temp: f64) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
+/// Each zone of the house with its thermostat, in the order the thermostats were composed
+pub async fn list_zones(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+) -> Result<Vec<(String, ZoneProperties, ThermostatProperties)>, ApiError> {
+    let thermostats = list_thermostats(backend, house_id).await?;
 
-    let target_temp_min = temp - DELTA_TEMP;
-    let target_temp_max = temp + DELTA_TEMP;
+    let mut zones = Vec::new();
+    for thermostat in thermostats {
+        let zone = thermostat
+            .zone
+            .strip_suffix(&format!("-House-{house_id}"))
+            .unwrap_or(&thermostat.zone)
+            .to_string();
+        let properties = get_current_zone_temp(backend, house_id, &zone).await?;
 
-    if target_temp_min < 0.0 || target_temp_max > 35.0 {
-        return Err(ApiError::DemkitError(format!("Invalid Temperature: {}. Temp must be between 0 and 35 degrees Celsius", target_temp_max)));
+        zones.push((zone, properties, thermostat));
     }
 
-    let url_min = format!(
-        "{}/set/Thermostat-House-{house_id}/temperatureSetpointHeating/{target_temp_min}",
-        *BASE_URL
-    );
+    Ok(zones)
+}
 
-    let url_min_away = format!(
-        "{}/set/Thermostat-House-{house_id}/temperatureMin/{target_temp_min}",
-        *BASE_URL
-    );
+const DELTA_TEMP: f64 = 1.0;
 
-    let url_max = format!(
-        "{}/set/Thermostat-House-{house_id}/temperatureSetpointCooling/{target_temp_max}",
-        *BASE_URL
-    );
+fn check_temperature(name: &str, temperature: f64) -> Result<(), ApiError> {
+    if !temperature.is_finite() {
+        return Err(ApiError::Validation(format!("Invalid {name}: {temperature}")));
+    }
+
+    Ok(())
+}
+
+/// Checks the settings against each other, as the thermostat would run them
+pub(crate) fn check_setpoints(
+    heating: f64,
+    cooling: f64,
+    temperature_min: f64,
+    temperature_max: f64,
+    deadband: &[f64],
+) -> Result<(), ApiError> {
+    check_temperature("heating setpoint", heating)?;
+    check_temperature("cooling setpoint", cooling)?;
+    check_temperature("minimum temperature", temperature_min)?;
+    check_temperature("maximum temperature", temperature_max)?;
 
-    let url_max_away = format!(
-        "{}/set/Thermostat-House-{house_id}/temperatureMax/{target_temp_max}",
-        *BASE_URL
-    );
+    if heating > cooling {
+        return Err(ApiError::Validation(format!(
+            "Invalid setpoints: the heating setpoint {heating} is above the cooling setpoint {cooling}"
+        )));
+    }
+    if temperature_min > temperature_max {
+        return Err(ApiError::Validation(format!(
+            "Invalid setpoints: the minimum temperature {temperature_min} is above the maximum temperature {temperature_max}"
+        )));
+    }
 
-    client.get(url_min).send().await?;
-    client.get(url_min_away).send().await?;
-    client.get(url_max).send().await?;
-    client.get(url_max_away).send().await?;
+    match deadband[..] {
+        [heat_on, heat_off, cool_off, cool_on] if heat_on <= heat_off && cool_off <= cool_on => {
+            // heating and cooling would take turns without a band in which both are off
+            if heating + heat_off > cooling + cool_off {
+                return Err(ApiError::Validation(format!(
+                    "Invalid deadband: heating stops at {} but cooling already stops at {}",
+                    heating + heat_off,
+                    cooling + cool_off
+                )));
+            }
+        }
+        _ => {
+            return Err(ApiError::Validation(format!(
+                "Invalid deadband: {deadband:?}. Deadband must be four ascending offsets, two per setpoint"
+            )));
+        }
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Sets all settings in one call, so the thermostat never runs a tick with only part of them
+async fn set_setpoints(
+    backend: &dyn DemkitBackend,
+    thermostat: &str,
+    heating: f64,
+    cooling: f64,
+    temperature_min: f64,
+    temperature_max: f64,
+    deadband: &[f64],
+) -> Result<(), ApiError> {
+    check_setpoints(heating, cooling, temperature_min, temperature_max, deadband)?;
+
+    let response = backend
+        .callp(thermostat, "setSetpoints", json!([heating, cooling, temperature_min, temperature_max, deadband]))
+        .await?;
+    check_response(thermostat, response, "update the thermostat")
+}
+
+/// Heating and cooling setpoints keeping the zone around `temp`
+pub(crate) fn target_setpoints(temp: f64) -> (f64, f64) {
+    (temp - DELTA_TEMP, temp + DELTA_TEMP)
+}
+
+/// Heating and cooling setpoints heating below `setpoint`, cooling only above the cooling
+/// setpoint used outside the heating intervals
+pub(crate) fn heating_setpoints(thermostat: &ThermostatProperties, setpoint: f64) -> (f64, f64) {
+    (setpoint, thermostat.temperature_max.max(setpoint))
+}
+
+pub async fn set_target_temp(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    zone: &str,
+    temp: f64,
+) -> Result<(), ApiError> {
+    let (target_temp_min, target_temp_max) = target_setpoints(temp);
+
+    set_temperature_band(backend, house_id, zone, target_temp_min, target_temp_max).await
+}
+
+/// Heats below `min` and cools above `max`, the setpoints outside the heating intervals are kept
+pub async fn set_temperature_band(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    zone: &str,
+    target_temp_min: f64,
+    target_temp_max: f64,
+) -> Result<(), ApiError> {
+    let thermostat = get_thermostat_properties(backend, house_id, zone).await?;
+
+    set_setpoints(
+        backend,
+        &thermostat.name,
+        target_temp_min,
+        target_temp_max,
+        thermostat.temperature_min,
+        thermostat.temperature_max,
+        &thermostat.temperature_deadband,
+    )
+    .await
+}
+
+/// Heats below `setpoint` and only cools above the cooling setpoint used outside the heating
+/// intervals, the setpoints outside the heating intervals are kept
+pub async fn set_heating_setpoint(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    zone: &str,
+    setpoint: f64,
+) -> Result<(), ApiError> {
+    let thermostat = get_thermostat_properties(backend, house_id, zone).await?;
+    let (heating, cooling) = heating_setpoints(&thermostat, setpoint);
+
+    set_setpoints(
+        backend,
+        &thermostat.name,
+        heating,
+        cooling,
+        thermostat.temperature_min,
+        thermostat.temperature_max,
+        &thermostat.temperature_deadband,
+    )
+    .await
+}
+
+pub async fn update_thermostat(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    zone: &str,
+    update: ThermostatUpdate,
+) -> Result<ThermostatProperties, ApiError> {
+    let current = get_thermostat_properties(backend, house_id, zone).await?;
+
+    // settings left out are checked as well, so a new heating setpoint cannot pass the current
+    // cooling setpoint
+    let heating = update.heating_setpoint.unwrap_or(current.min_target_temp);
+    let cooling = update.cooling_setpoint.unwrap_or(current.max_target_temp);
+    let temperature_min = update.min_temperature.unwrap_or(current.temperature_min);
+    let temperature_max = update.max_temperature.unwrap_or(current.temperature_max);
+    let deadband = update.deadband.unwrap_or(current.temperature_deadband);
+
+    set_setpoints(backend, &current.name, heating, cooling, temperature_min, temperature_max, &deadband).await?;
+
+    get_thermostat_properties(backend, house_id, zone).await
+}
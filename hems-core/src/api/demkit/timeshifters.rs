@@ -1,128 +1,160 @@
-use std::fmt;
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::RwLock,
+};
 
 use num_complex::Complex;
-use serde::{
-    de::{self, MapAccess, SeqAccess, Visitor},
-    Deserialize, Deserializer, Serialize,
-};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use super::{init, parse_complex_str, ApiError, Commodities, Commodity, BASE_URL, CLIENT};
-
-#[derive(Serialize, Deserialize, Debug, ToSchema)]
-#[serde(rename_all = "camelCase")]
-pub struct Job {
-    pub start_time: u64,
-    pub end_time: u64,
-}
+use serde_json::{json, Value};
 
-fn deserialize_jobs<'de, D>(deserializer: D) -> Result<Vec<Job>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    struct JobVisitor;
+use super::{check_response, env::InternalComplex, parse_complex_str, ApiError, Commodities, Commodity, DemkitBackend};
 
-    impl<'de> Visitor<'de> for JobVisitor {
-        type Value = Vec<Job>;
+/// Longest window of a flexible job, DEMKit cuts windows off a day after the earliest start
+const MAX_JOB_WINDOW: u64 = 24 * 3600;
 
-        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            formatter.write_str(
-                "an array with an integer ID and an object containing startTime and endTime",
-            )
-        }
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    /// Waiting for its start
+    Pending,
+    Running,
+    /// Ran its whole profile or reached its deadline
+    Finished,
+    /// Cancelled or shut down before it finished
+    Cancelled,
+}
 
-        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
-        where
-            A: SeqAccess<'de>,
-        {
-            let mut jobs: Vec<Job> = Vec::new();
+/// Job of a timeshifter, it runs its profile somewhere within its window
+#[derive(Serialize, Clone, Debug, ToSchema)]
+pub struct Job {
+    /// Job ID assigned by DEMKit, stays the same when other jobs are cancelled
+    pub id: u32,
+    /// Unix time from which the job may start
+    pub earliest_start: u64,
+    /// Unix time by which the job must have finished
+    pub latest_end: u64,
+    /// Start picked through the API
+    pub start_time: Option<u64>,
+    /// Start planned by DEMKit, until the job starts
+    pub planned_start_time: Option<u64>,
+    /// Time the job started running
+    pub started_at: Option<u64>,
+    pub state: JobState,
+    /// Energy used by the job so far in Wh
+    pub energy: f64,
+}
 
-            while let Some(job) = seq.next_element::<(u64, Job)>()? {
-                jobs.push(Job {
-                    start_time: job.1.start_time,
-                    end_time: job.1.end_time,
-                });
-            }
+/// Job as DEMKit keeps it, with the window between `startTime` and `endTime`
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DemkitJob {
+    start_time: u64,
+    end_time: u64,
+    start_at: Option<u64>,
+    started_at: Option<u64>,
+    finished_at: Option<u64>,
+    /// Profile of this job, if it does not run the timeshifter's own
+    profile: Option<Vec<Value>>,
+    #[serde(default)]
+    cancelled: bool,
+    #[serde(default)]
+    energy: f64,
+}
 
-            Ok(jobs)
+impl DemkitJob {
+    fn state(&self) -> JobState {
+        match (self.cancelled, self.finished_at, self.started_at) {
+            (true, _, _) => JobState::Cancelled,
+            (_, Some(_), _) => JobState::Finished,
+            (_, _, Some(_)) => JobState::Running,
+            _ => JobState::Pending,
         }
     }
 
-    deserializer.deserialize_seq(JobVisitor)
+    fn into_job(self, id: u32, planned_start_time: Option<u64>) -> Job {
+        Job {
+            id,
+            earliest_start: self.start_time,
+            latest_end: self.end_time,
+            start_time: self.start_at,
+            planned_start_time,
+            started_at: self.started_at,
+            state: self.state(),
+            energy: self.energy,
+        }
+    }
 }
 
-// Custom deserializer function
-fn deserialize_empty_as_none<'de, D>(deserializer: D) -> Result<Option<Job>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    struct EmptyAsNone;
-
-    impl<'de> Visitor<'de> for EmptyAsNone {
-        type Value = Option<Job>;
+/// Jobs as DEMKit lists them, only the current one has a planned start
+fn into_jobs(jobs: Vec<(u32, DemkitJob)>, current_job_idx: i32, planned_start_time: Option<u64>) -> Vec<Job> {
+    jobs.into_iter()
+        .enumerate()
+        .map(|(idx, (id, job))| match idx as i32 == current_job_idx {
+            true => job.into_job(id, planned_start_time),
+            false => job.into_job(id, None),
+        })
+        .collect()
+}
 
-        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            formatter.write_str("a struct or an empty object `{}`")
-        }
+/// Timeshifters composed into each house, by name without the `-House-{id}` suffix.
+///
+/// Filled when timeshiftable entities are added through hems-core and emptied when they are
+/// removed or the house is reset, so it does not know entities composed before a restart.
+static TIMESHIFTERS: Lazy<RwLock<HashMap<u32, BTreeSet<String>>>> = Lazy::new(Default::default);
+
+pub fn register(house_id: u32, name: &str) {
+    TIMESHIFTERS
+        .write()
+        .unwrap()
+        .entry(house_id)
+        .or_default()
+        .insert(name.to_string());
+}
 
-        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
-        where
-            A: MapAccess<'de>,
-        {
-            // parse Job { startTime: 0, endTime: 0 }
-            let mut start_time: Option<u64> = None;
-            let mut end_time: Option<u64> = None;
-
-            while let Some(key) = map.next_key::<String>()? {
-                match key.as_str() {
-                    "startTime" => {
-                        start_time = Some(map.next_value()?);
-                    }
-                    "endTime" => {
-                        end_time = Some(map.next_value()?);
-                    }
-                    _ => {
-                        let _: serde_json::Value = map.next_value()?;
-                    }
-                }
-            }
-
-            match (start_time, end_time) {
-                (None, None) => Ok(None),
-                (None, _) => Err(de::Error::custom("startTime field not found")),
-                (_, None) => Err(de::Error::custom("endTime field not found")),
-                (Some(start_time), Some(end_time)) => Ok(Some(Job { start_time, end_time })),
-            }
-        }
+pub fn unregister(house_id: u32, name: &str) {
+    if let Some(names) = TIMESHIFTERS.write().unwrap().get_mut(&house_id) {
+        names.remove(name);
     }
+}
 
-    deserializer.deserialize_any(EmptyAsNone)
+/// Forgets every timeshifter of the house, e.g. after its DEMKit instance was reset
+pub fn unregister_house(house_id: u32) {
+    TIMESHIFTERS.write().unwrap().remove(&house_id);
 }
 
-pub enum TimeShifters {
-    DishWasher,
-    WashingMachine,
+/// Names of the timeshifters of the house, sorted
+pub fn list(house_id: u32) -> Vec<String> {
+    TIMESHIFTERS
+        .read()
+        .unwrap()
+        .get(&house_id)
+        .map(|names| names.iter().cloned().collect())
+        .unwrap_or_default()
 }
 
-impl TimeShifters {
-    pub fn get_device_name(&self) -> &str {
-        match self {
-            TimeShifters::DishWasher => "DishWasher",
-            TimeShifters::WashingMachine => "WashingMachine",
-        }
+/// DEMKit entity of a registered timeshifter
+fn entity_id(house_id: u32, name: &str) -> Result<String, ApiError> {
+    let known = TIMESHIFTERS
+        .read()
+        .unwrap()
+        .get(&house_id)
+        .is_some_and(|names| names.contains(name));
+
+    if known {
+        Ok(format!("{name}-House-{house_id}"))
+    } else {
+        Err(ApiError::UnknownEntity(format!("No timeshifter named {name} in house {house_id}")))
     }
 }
 
-impl TryFrom<&str> for TimeShifters {
-    type Error = &'static str;
+/// Removes a registered timeshifter from DEMKit, which also unregisters it
+pub async fn remove(backend: &dyn DemkitBackend, house_id: u32, name: &str) -> Result<(), ApiError> {
+    let entity = entity_id(house_id, name)?;
 
-    fn try_from(s: &str) -> Result<Self, Self::Error> {
-        match s {
-            "DishWasher" => Ok(TimeShifters::DishWasher),
-            "WashingMachine" => Ok(TimeShifters::WashingMachine),
-            _ => Err("Invalid TimeShifter name"),
-        }
-    }
+    super::env::remove_entity_by_name(backend, &entity).await
 }
 
 #[derive(Deserialize, Debug)]
@@ -130,17 +162,23 @@ pub struct TimeShifterInfo {
     pub name: String,
     #[serde(rename = "consumption")]
     _consumption: Commodities,
-    pub electricity_consumption: Option<Complex<f64>>,
+    #[serde(skip)]
+    pub electricity_consumption: Complex<f64>,
     #[serde(rename = "profile")]
     _profile: Vec<String>,
-    pub device_profile: Option<Vec<Complex<f64>>>,
+    #[serde(skip)]
+    pub device_profile: Vec<Complex<f64>>,
     pub available: bool,
-    #[serde(rename = "currentJob")]
-    #[serde(deserialize_with = "deserialize_empty_as_none")]
+    /// Job at `current_job_idx`, DEMKit's own copy of it carries no ID
+    #[serde(skip)]
     pub current_job: Option<Job>,
     #[serde(rename = "currentJobIdx")]
     pub current_job_idx: i32,
-    #[serde(deserialize_with = "deserialize_jobs")]
+    #[serde(rename = "jobs")]
+    _jobs: Vec<(u32, DemkitJob)>,
+    #[serde(rename = "plannedStartTime")]
+    _planned_start_time: Option<u64>,
+    #[serde(skip)]
     pub jobs: Vec<Job>,
     #[serde(rename = "jobProgress")]
     pub job_progress: f64,
@@ -152,119 +190,255 @@ pub struct ScheduleJob {
     duration: u64,
 }
 
-pub async fn get_properties(
-    house_id: u32,
-    entity: TimeShifters,
-) -> Result<TimeShifterInfo, ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let entity_name = entity.get_device_name();
-    let entity_id = format!("{entity_name}-House-{house_id}");
-
-    let url = format!("{}/call/{entity_id}/getProperties", *BASE_URL);
+/// Job that may run anywhere within a window, DEMKit or the caller picks the start
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct FlexibleJobParams {
+    /// Unix time from which the job may start
+    pub earliest_start: u64,
+    /// Unix time by which the job must have finished, at most a day after the earliest start
+    pub latest_end: u64,
+    /// Power profile of this job in the time base of the timeshifter, the timeshifter's own
+    /// profile if not given
+    pub profile: Option<Vec<InternalComplex>>,
+    /// Unix time to start at, DEMKit plans the start if not given
+    pub start_time: Option<u64>,
+}
 
-    let response = client.get(url).send().await?;
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct JobStart {
+    /// Unix time to start at, `null` lets DEMKit plan the start again
+    pub start_time: Option<u64>,
+}
 
-    let mut response_body = response.json::<TimeShifterInfo>().await?;
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct JobProperties {
+    time_base: u64,
+    profile: Vec<Value>,
+    jobs: Vec<(u32, DemkitJob)>,
+    current_job_idx: i32,
+    planned_start_time: Option<u64>,
+}
 
-    response_body.device_profile = Some(
-        response_body
-            ._profile
-            .iter()
-            .map(|s| parse_complex_str(&Commodity::Complex(s.to_string())).unwrap())
-            .collect(),
-    );
+impl JobProperties {
+    /// Seconds a job running `profile`, or the timeshifter's own profile, takes
+    fn job_length(&self, profile: Option<usize>) -> u64 {
+        profile.unwrap_or(self.profile.len()) as u64 * self.time_base
+    }
+}
 
-    response_body.electricity_consumption = Some(parse_complex_str(
-        &response_body
-            ._consumption
-            .electricity
-            .clone()
-            .expect("Electricity commodity not found"),
-    )?);
+async fn job_properties(backend: &dyn DemkitBackend, entity_id: &str) -> Result<JobProperties, ApiError> {
+    let response = backend.properties(entity_id).await?;
 
-    Ok(response_body)
+    Ok(serde_json::from_value::<JobProperties>(response)?)
 }
 
-pub async fn get_jobs(house_id: u32, entity: TimeShifters) -> Result<Vec<Job>, ApiError> {
-    let client = CLIENT.get_or_init(init);
+pub async fn get_properties(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    name: &str,
+) -> Result<TimeShifterInfo, ApiError> {
+    let entity_id = entity_id(house_id, name)?;
 
-    let entity_name = entity.get_device_name();
-    let entity_id = format!("{entity_name}-House-{house_id}");
+    let response = backend.properties(&entity_id).await?;
 
-    let url = format!("{}/get/{entity_id}/jobs", *BASE_URL);
+    let mut response_body = serde_json::from_value::<TimeShifterInfo>(response)?;
 
-    let response = client.get(url).send().await?;
+    response_body.device_profile = response_body
+        ._profile
+        .iter()
+        .map(|s| parse_complex_str(&Commodity::Complex(s.to_string())))
+        .collect::<Result<_, _>>()?;
 
-    let response_body = response.json::<Vec<Job>>().await?;
+    response_body.electricity_consumption = response_body._consumption.electricity(&entity_id)?;
+    response_body.jobs = into_jobs(
+        std::mem::take(&mut response_body._jobs),
+        response_body.current_job_idx,
+        response_body._planned_start_time,
+    );
+    response_body.current_job = usize::try_from(response_body.current_job_idx)
+        .ok()
+        .and_then(|idx| response_body.jobs.get(idx))
+        .cloned();
 
     Ok(response_body)
 }
 
 pub async fn schedule_job(
+    backend: &dyn DemkitBackend,
     house_id: u32,
-    entity: TimeShifters,
+    name: &str,
     job: ScheduleJob,
 ) -> Result<Job, ApiError> {
-    let client = CLIENT.get_or_init(init);
+    let entity_id = entity_id(house_id, name)?;
+
+    let body = json!([job.delay, job.duration]);
+    let response = backend.callp(&entity_id, "scheduleJob", body).await?;
+    let job_id = new_job_id(&entity_id, response)?;
+
+    get_properties(backend, house_id, name)
+        .await?
+        .jobs
+        .into_iter()
+        .find(|job| job.id == job_id)
+        .ok_or_else(|| ApiError::DemkitError(format!("{entity_id} lost job {job_id}")))
+}
 
-    let entity_name = entity.get_device_name();
-    let entity_id = format!("{entity_name}-House-{house_id}");
+/// ID of a job DEMKit accepted, or why it refused the job
+fn new_job_id(entity_id: &str, response: Value) -> Result<u32, ApiError> {
+    match serde_json::from_value::<(bool, Value)>(response)? {
+        (true, Value::Number(id)) => id
+            .as_u64()
+            .and_then(|id| u32::try_from(id).ok())
+            .ok_or_else(|| ApiError::DemkitError(format!("{entity_id} returned an invalid job ID: {id}"))),
+        (_, message) => Err(ApiError::Validation(format!(
+            "Failed to schedule job: {}",
+            message.as_str().unwrap_or_default()
+        ))),
+    }
+}
 
-    let url = format!("{}/callp/{entity_id}/scheduleJob", *BASE_URL);
+/// Jobs of the timeshifter with their windows and start times
+pub async fn list_jobs(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    name: &str,
+) -> Result<Vec<Job>, ApiError> {
+    let entity_id = entity_id(house_id, name)?;
 
-    let body = [job.delay, job.duration];
-    let response = client.put(url).json(&body).send().await?;
+    let properties = job_properties(backend, &entity_id).await?;
 
-    let response_body = response.json::<(bool, String)>().await?;
+    Ok(into_jobs(properties.jobs, properties.current_job_idx, properties.planned_start_time))
+}
 
-    let current_time = super::get_time().await;
+pub async fn get_job(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    name: &str,
+    job_id: u32,
+) -> Result<Job, ApiError> {
+    list_jobs(backend, house_id, name)
+        .await?
+        .into_iter()
+        .find(|job| job.id == job_id)
+        .ok_or_else(|| job_not_found(house_id, name, job_id))
+}
 
-    match response_body.0 {
-        true => Ok(Job {
-            start_time: current_time + job.delay,
-            end_time: current_time + job.delay + job.duration,
-        }),
-        false => Err(ApiError::DemkitError(format!(
-            "Failed to schedule job: {}",
-            response_body.1
-        ))),
+fn job_not_found(house_id: u32, name: &str, job_id: u32) -> ApiError {
+    ApiError::NotFound(format!("Timeshifter {name} of house {house_id} has no job {job_id}"))
+}
+
+/// Checks that a job of `job_length` seconds started at `start_time` finishes within its window
+fn check_start_time(start_time: u64, job_length: u64, earliest_start: u64, latest_end: u64) -> Result<(), ApiError> {
+    let fits = start_time
+        .checked_add(job_length)
+        .is_some_and(|end| start_time >= earliest_start && end <= latest_end);
+
+    if !fits {
+        return Err(ApiError::Validation(format!(
+            "Invalid start time: {start_time}. The job takes {job_length} seconds and must run between \
+             {earliest_start} and {latest_end}"
+        )));
+    }
+
+    Ok(())
+}
+
+pub async fn add_flexible_job(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    name: &str,
+    job: FlexibleJobParams,
+) -> Result<Job, ApiError> {
+    let entity_id = entity_id(house_id, name)?;
+
+    if job.earliest_start >= job.latest_end {
+        return Err(ApiError::Validation(format!(
+            "Invalid window: the earliest start {} is not before the latest end {}",
+            job.earliest_start, job.latest_end
+        )));
     }
+    if job.latest_end - job.earliest_start > MAX_JOB_WINDOW {
+        return Err(ApiError::Validation(format!(
+            "Invalid window: the latest end {} is more than {MAX_JOB_WINDOW} seconds after the earliest start {}",
+            job.latest_end, job.earliest_start
+        )));
+    }
+    if let Some(start_time) = job.start_time {
+        let job_length = job_properties(backend, &entity_id)
+            .await?
+            .job_length(job.profile.as_ref().map(Vec::len));
+        check_start_time(start_time, job_length, job.earliest_start, job.latest_end)?;
+    }
+
+    let body = json!([job.earliest_start, job.latest_end, job.profile, job.start_time]);
+    let response = backend.callp(&entity_id, "addFlexibleJob", body).await?;
+
+    let job_id = new_job_id(&entity_id, response)?;
+
+    get_job(backend, house_id, name, job_id).await
 }
 
-pub async fn cancel_job(house_id: u32, entity: TimeShifters, job_id: u32) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
+pub async fn set_job_start(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    name: &str,
+    job_id: u32,
+    start: JobStart,
+) -> Result<Job, ApiError> {
+    let entity_id = entity_id(house_id, name)?;
+
+    let properties = job_properties(backend, &entity_id).await?;
+    let job = properties
+        .jobs
+        .iter()
+        .find(|(id, _)| *id == job_id)
+        .map(|(_, job)| job)
+        .ok_or_else(|| job_not_found(house_id, name, job_id))?;
+    if let Some(start_time) = start.start_time {
+        let job_length = properties.job_length(job.profile.as_ref().map(Vec::len));
+        check_start_time(start_time, job_length, job.start_time, job.end_time)?;
+    }
 
-    let entity_name = entity.get_device_name();
-    let entity_id = format!("{entity_name}-House-{house_id}");
+    let body = json!([job_id, start.start_time]);
+    let response = backend.callp(&entity_id, "setJobStart", body).await?;
+    check_response(&entity_id, response, "set the job start")?;
 
-    let url = format!("{}/callp/{entity_id}/cancelJob", *BASE_URL);
+    get_job(backend, house_id, name, job_id).await
+}
 
-    let body = [job_id];
-    let response = client.put(url).json(&body).send().await?;
+pub async fn cancel_job(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    name: &str,
+    job_id: u32,
+) -> Result<(), ApiError> {
+    let entity_id = entity_id(house_id, name)?;
 
-    let (success, error_message) = response.json::<(bool, String)>().await?;
+    let body = json!([job_id]);
+    let response = backend.callp(&entity_id, "cancelJob", body).await?;
+
+    let (success, error_message) = serde_json::from_value::<(bool, String)>(response)?;
 
     match success {
         true => Ok(()),
-        false => Err(ApiError::DemkitError(format!("Failed to cancel job: {}", error_message))),
+        false => Err(ApiError::Validation(format!("Failed to cancel job: {}", error_message))),
     }
 }
 
-pub async fn force_shutdown(house_id: u32, entity: TimeShifters) -> Result<(), ApiError> {
-    let client = CLIENT.get_or_init(init);
-
-    let entity_name = entity.get_device_name();
-    let entity_id = format!("{entity_name}-House-{house_id}");
-
-    let url = format!("{}/call/{entity_id}/forceShutdown", *BASE_URL);
+pub async fn force_shutdown(
+    backend: &dyn DemkitBackend,
+    house_id: u32,
+    name: &str,
+) -> Result<(), ApiError> {
+    let entity_id = entity_id(house_id, name)?;
 
-    let response = client.get(url).send().await?;
+    let response = backend.call(&entity_id, "forceShutdown").await?;
 
-    let response_body = response.json::<bool>().await?;
+    let response_body = serde_json::from_value::<bool>(response)?;
 
     match response_body {
         true => Ok(()),
-        false => Err(ApiError::DemkitError(format!("Failed to force shutdown"))),
+        false => Err(ApiError::DemkitError("Failed to force shutdown".to_string())),
     }
 }
@@ -0,0 +1,53 @@
+use actix_web::{http::StatusCode, HttpResponse};
+use serde::Serialize;
+use utoipa::{IntoResponses, ToSchema};
+
+/// Body of every error response
+#[derive(Serialize, ToSchema, Debug)]
+pub struct ErrorBody {
+    /// Stable, machine readable error code, e.g. `unknown_entity` or `upstream_timeout`
+    pub code: String,
+    /// Human readable description of the error
+    pub message: String,
+    /// Error reported by DEMKit or Home Assistant, if any
+    #[schema(nullable)]
+    pub detail: Option<String>,
+}
+
+impl ErrorBody {
+    pub fn new(code: &str, message: impl Into<String>, detail: Option<String>) -> Self {
+        Self {
+            code: code.to_string(),
+            message: message.into(),
+            detail,
+        }
+    }
+
+    pub fn response(self, status: StatusCode) -> HttpResponse {
+        HttpResponse::build(status).json(self)
+    }
+}
+
+/// Status of a failed upstream request: 504 when it timed out, 502 otherwise
+pub fn request_status(error: &reqwest::Error) -> StatusCode {
+    if error.is_timeout() {
+        StatusCode::GATEWAY_TIMEOUT
+    } else {
+        StatusCode::BAD_GATEWAY
+    }
+}
+
+/// Errors caused by DEMKit or Home Assistant, shared by all routes calling them
+#[allow(dead_code)]
+#[derive(IntoResponses)]
+pub enum UpstreamErrors {
+    /// The upstream service failed, rejected the request or returned an invalid response
+    #[response(status = 502)]
+    BadGateway(ErrorBody),
+    /// The upstream service kept failing, calls are suspended until its circuit breaker closes
+    #[response(status = 503)]
+    ServiceUnavailable(ErrorBody),
+    /// The upstream service did not respond in time
+    #[response(status = 504)]
+    GatewayTimeout(ErrorBody),
+}
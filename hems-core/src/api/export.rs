@@ -0,0 +1,366 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use actix_web::web::Bytes;
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, UInt64Array};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use futures::{Stream, StreamExt, TryStreamExt};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::Compression,
+    file::properties::WriterProperties,
+    format::KeyValue,
+};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+use super::history::{History, HistoryError, RunInfo, WideColumns, WideRow};
+
+/// Rows read from the history and encoded per chunk of an export, a row group in Parquet
+const PAGE_ROWS: usize = 1_000;
+
+#[derive(Deserialize, ToSchema, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ExportQuery {
+    /// `csv` (default) or `parquet`
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// Start of the range in simulation time, inclusive
+    pub from: Option<u64>,
+    /// End of the range in simulation time, exclusive
+    pub to: Option<u64>,
+}
+
+fn json(value: &impl serde::Serialize) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| "null".to_string())
+}
+
+fn units(columns: &WideColumns) -> BTreeMap<&str, &str> {
+    columns
+        .columns
+        .iter()
+        .map(String::as_str)
+        .zip(columns.units.iter().map(String::as_str))
+        .collect()
+}
+
+/// Rows of the house in `[from, to)`, each page read on the blocking thread pool once the
+/// previous one was sent, so the export never holds the database or the whole history
+pub fn pages(
+    history: Arc<History>,
+    house_id: u32,
+    columns: Arc<WideColumns>,
+    from: Option<u64>,
+    to: Option<u64>,
+) -> impl Stream<Item = Result<Vec<WideRow>, HistoryError>> + 'static {
+    futures::stream::try_unfold(Some(from.unwrap_or(0)), move |cursor| {
+        let (history, columns) = (history.clone(), columns.clone());
+
+        async move {
+            let Some(cursor) = cursor else {
+                return Ok(None);
+            };
+            let rows = history
+                .blocking(move |history| history.wide_rows(house_id, &columns, cursor, to, PAGE_ROWS))
+                .await?;
+
+            // a short page is the last one
+            let next = match rows.last() {
+                Some((time, _)) if rows.len() == PAGE_ROWS => time.checked_add(1),
+                _ => None,
+            };
+            Ok((!rows.is_empty()).then_some((rows, next)))
+        }
+    })
+}
+
+/// `#` comment lines with the run info and units, followed by the header row
+fn csv_header(house_id: u32, run: &RunInfo, columns: &WideColumns) -> Result<Bytes, HistoryError> {
+    let mut output = format!(
+        "# house_id: {house_id}\n# sim_config: {}\n# composition: {}\n# units: {}\n",
+        json(&run.sim_config),
+        json(&run.composition),
+        json(&units(columns)),
+    )
+    .into_bytes();
+
+    let mut writer = csv::Writer::from_writer(&mut output);
+    writer
+        .write_record(std::iter::once("time").chain(columns.columns.iter().map(String::as_str)))
+        .and_then(|_| writer.flush().map_err(Into::into))
+        .map_err(|e| HistoryError::Export(e.to_string()))?;
+    drop(writer);
+
+    Ok(output.into())
+}
+
+fn csv_rows(rows: &[WideRow]) -> Result<Bytes, HistoryError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+
+    for (time, values) in rows {
+        let record = std::iter::once(time.to_string())
+            .chain(values.iter().map(|value| value.map(|value| value.to_string()).unwrap_or_default()));
+        writer.write_record(record).map_err(|e| HistoryError::Export(e.to_string()))?;
+    }
+
+    writer
+        .into_inner()
+        .map(Bytes::from)
+        .map_err(|e| HistoryError::Export(e.to_string()))
+}
+
+/// CSV export, encoded page by page while it is sent.
+///
+/// Empty cells are series without a sample at that time. pandas reads it with
+/// `read_csv(path, comment="#")`, R with `read.csv(path, comment.char = "#")`.
+pub fn csv_stream(
+    house_id: u32,
+    run: &RunInfo,
+    columns: &WideColumns,
+    pages: impl Stream<Item = Result<Vec<WideRow>, HistoryError>> + 'static,
+) -> impl Stream<Item = Result<Bytes, HistoryError>> + 'static {
+    let header = csv_header(house_id, run, columns);
+
+    futures::stream::once(async move { header }).chain(pages.map(|page| csv_rows(&page?)))
+}
+
+fn record_batch(schema: &SchemaRef, rows: &[WideRow]) -> Result<RecordBatch, HistoryError> {
+    let times: ArrayRef = Arc::new(UInt64Array::from_iter_values(rows.iter().map(|(time, _)| *time)));
+    let columns = (0..schema.fields().len() - 1)
+        .map(|index| Arc::new(Float64Array::from_iter(rows.iter().map(|(_, values)| values[index]))) as ArrayRef);
+
+    RecordBatch::try_new(schema.clone(), std::iter::once(times).chain(columns).collect())
+        .map_err(|e| HistoryError::Export(e.to_string()))
+}
+
+/// Parquet export with a `time` column and a nullable column per series, a row group per page
+/// sent as soon as it is encoded.
+///
+/// Units are stored in the field metadata, the run info in the file's key-value metadata
+/// (`hems.house_id`, `hems.sim_config` and `hems.composition`).
+pub fn parquet_stream(
+    house_id: u32,
+    run: &RunInfo,
+    columns: &WideColumns,
+    pages: impl Stream<Item = Result<Vec<WideRow>, HistoryError>> + 'static,
+) -> Result<impl Stream<Item = Result<Bytes, HistoryError>> + 'static, HistoryError> {
+    let fields = std::iter::once(Field::new("time", DataType::UInt64, false))
+        .chain(columns.columns.iter().zip(&columns.units).map(|(column, unit)| {
+            Field::new(column, DataType::Float64, true)
+                .with_metadata([("unit".to_string(), unit.clone())].into())
+        }))
+        .collect::<Vec<_>>();
+    let schema = Arc::new(Schema::new(fields));
+
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_key_value_metadata(Some(vec![
+            KeyValue::new("hems.house_id".to_string(), house_id.to_string()),
+            KeyValue::new("hems.sim_config".to_string(), json(&run.sim_config)),
+            KeyValue::new("hems.composition".to_string(), json(&run.composition)),
+        ]))
+        .build();
+
+    let writer = ArrowWriter::try_new(Vec::new(), schema.clone(), Some(properties))
+        .map_err(|e| HistoryError::Export(e.to_string()))?;
+
+    let chunks = futures::stream::try_unfold(
+        (Box::pin(pages), Some(writer)),
+        move |(mut pages, writer)| {
+            let schema = schema.clone();
+
+            async move {
+                let Some(mut writer) = writer else {
+                    return Ok(None);
+                };
+
+                match pages.try_next().await? {
+                    Some(rows) => {
+                        writer.write(&record_batch(&schema, &rows)?).map_err(|e| HistoryError::Export(e.to_string()))?;
+                        writer.flush().map_err(|e| HistoryError::Export(e.to_string()))?;
+
+                        // whatever the writer passed on so far, it keeps track of the offsets itself
+                        let chunk = Bytes::from(std::mem::take(writer.inner_mut()));
+                        Ok(Some((chunk, (pages, Some(writer)))))
+                    }
+                    // the rest of the last row group and the footer
+                    None => {
+                        let chunk = writer.into_inner().map_err(|e| HistoryError::Export(e.to_string()))?;
+                        Ok(Some((Bytes::from(chunk), (pages, None))))
+                    }
+                }
+            }
+        },
+    );
+
+    Ok(chunks)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use serde_json::json;
+
+    use super::*;
+    use crate::api::history::Sample;
+    use crate::config::HistoryConfig;
+
+    /// Battery consumption every minute for `rows` minutes, the zone temperature every other one
+    fn history(rows: usize) -> Arc<History> {
+        let history = History::open(&HistoryConfig {
+            path: ":memory:".into(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        for row in 0..rows {
+            let mut samples = vec![Sample::new("Battery", "electricity", "W", row as f64)];
+            if row % 2 == 0 {
+                samples.push(Sample::new("Zone", "temperature", "°C", 20.0));
+            }
+            history.record(1, row as u64 * 60, &samples).unwrap();
+        }
+        history.record_config(1, &json!({"timezone": "Europe/Amsterdam"})).unwrap();
+        history.record_composition(1, &json!({"entities": []})).unwrap();
+
+        Arc::new(history)
+    }
+
+    fn export(
+        history: &Arc<History>,
+        from: Option<u64>,
+    ) -> (RunInfo, Arc<WideColumns>, impl Stream<Item = Result<Vec<WideRow>, HistoryError>>) {
+        let run = history.run_info(1).unwrap();
+        let columns = Arc::new(history.wide_columns(1, from, None).unwrap());
+        let pages = pages(history.clone(), 1, columns.clone(), from, None);
+
+        (run, columns, pages)
+    }
+
+    async fn collect(chunks: impl Stream<Item = Result<Bytes, HistoryError>>) -> Vec<Bytes> {
+        chunks.try_collect().await.unwrap()
+    }
+
+    #[actix_web::test]
+    async fn pages_split_at_the_page_size() {
+        let history = history(PAGE_ROWS + 5);
+
+        let (_, _, pages) = export(&history, None);
+        let pages: Vec<Vec<WideRow>> = pages.try_collect().await.unwrap();
+        assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), [PAGE_ROWS, 5]);
+        assert_eq!(pages[0].last().map(|(time, _)| *time), Some((PAGE_ROWS as u64 - 1) * 60));
+        assert_eq!(pages[1][0], (PAGE_ROWS as u64 * 60, vec![Some(PAGE_ROWS as f64), Some(20.0)]));
+        assert_eq!(pages[1][1], (PAGE_ROWS as u64 * 60 + 60, vec![Some(PAGE_ROWS as f64 + 1.0), None]));
+
+        let (_, _, pages) = export(&history, Some(600));
+        let pages: Vec<Vec<WideRow>> = pages.try_collect().await.unwrap();
+        assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), [PAGE_ROWS - 5]);
+        assert_eq!(pages[0][0].0, 600);
+    }
+
+    #[actix_web::test]
+    async fn full_last_page_ends_the_export() {
+        let history = history(PAGE_ROWS);
+
+        let (_, _, pages) = export(&history, None);
+        let pages: Vec<Vec<WideRow>> = pages.try_collect().await.unwrap();
+        assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), [PAGE_ROWS]);
+    }
+
+    #[actix_web::test]
+    async fn csv_reads_back() {
+        let history = history(PAGE_ROWS + 5);
+
+        let (run, columns, pages) = export(&history, None);
+        let chunks = collect(csv_stream(1, &run, &columns, pages)).await;
+        assert_eq!(chunks.len(), 3);
+        let csv = chunks.concat();
+
+        let comments: Vec<&str> = std::str::from_utf8(&csv)
+            .unwrap()
+            .lines()
+            .take_while(|line| line.starts_with('#'))
+            .collect();
+        assert_eq!(
+            comments,
+            [
+                "# house_id: 1",
+                r#"# sim_config: {"timezone":"Europe/Amsterdam"}"#,
+                r#"# composition: {"entities":[]}"#,
+                r#"# units: {"Battery.electricity":"W","Zone.temperature":"°C"}"#,
+            ]
+        );
+
+        let mut reader = csv::ReaderBuilder::new().comment(Some(b'#')).from_reader(csv.as_slice());
+        assert_eq!(reader.headers().unwrap(), vec!["time", "Battery.electricity", "Zone.temperature"]);
+        let records: Vec<csv::StringRecord> = reader.records().collect::<Result<_, _>>().unwrap();
+        assert_eq!(records.len(), PAGE_ROWS + 5);
+        assert_eq!(records[0], vec!["0", "0", "20"]);
+        assert_eq!(records[PAGE_ROWS + 1], vec!["60060", "1001", ""]);
+    }
+
+    #[actix_web::test]
+    async fn parquet_chunks_form_a_file() {
+        let history = history(PAGE_ROWS + 5);
+
+        let (run, columns, pages) = export(&history, None);
+        let chunks = collect(parquet_stream(1, &run, &columns, pages).unwrap()).await;
+        // a row group per page and the footer
+        assert_eq!(chunks.len(), 3);
+        let builder = ParquetRecordBatchReaderBuilder::try_new(Bytes::from(chunks.concat())).unwrap();
+
+        let metadata = builder.metadata();
+        assert_eq!(metadata.num_row_groups(), 2);
+        let key_values: BTreeMap<&str, Option<&str>> = metadata
+            .file_metadata()
+            .key_value_metadata()
+            .unwrap()
+            .iter()
+            .filter(|kv| kv.key.starts_with("hems."))
+            .map(|kv| (kv.key.as_str(), kv.value.as_deref()))
+            .collect();
+        assert_eq!(
+            key_values,
+            BTreeMap::from([
+                ("hems.composition", Some(r#"{"entities":[]}"#)),
+                ("hems.house_id", Some("1")),
+                ("hems.sim_config", Some(r#"{"timezone":"Europe/Amsterdam"}"#)),
+            ])
+        );
+
+        let schema = builder.schema().clone();
+        let names: Vec<&str> = schema.fields().iter().map(|field| field.name().as_str()).collect();
+        assert_eq!(names, ["time", "Battery.electricity", "Zone.temperature"]);
+        assert_eq!(schema.field(2).metadata().get("unit").map(String::as_str), Some("°C"));
+
+        let batches: Vec<RecordBatch> = builder.with_batch_size(PAGE_ROWS).build().unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(batches.iter().map(RecordBatch::num_rows).sum::<usize>(), PAGE_ROWS + 5);
+        let temperatures = batches.iter().map(|batch| batch.column(2).null_count()).sum::<usize>();
+        assert_eq!(temperatures, (PAGE_ROWS + 5) / 2);
+        let times = batches[1].column(0).as_any().downcast_ref::<UInt64Array>().unwrap();
+        assert_eq!(times.value(0), PAGE_ROWS as u64 * 60);
+    }
+}
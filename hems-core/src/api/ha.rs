@@ -1,26 +1,41 @@
-use std::{collections::HashMap, sync::{OnceLock, RwLock}};
+use std::{collections::HashMap, sync::{Arc, OnceLock, RwLock}};
 
-use once_cell::sync::Lazy;
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
 use reqwest;
 use serde::{Deserialize, Serialize};
 
-static BASE_URL: Lazy<String> = Lazy::new(|| {
-    std::env::var("HA_URL").expect("HA_URL is not set")
-});
-static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+use super::error::{request_status, ErrorBody};
+use super::upstream::UpstreamError;
+use crate::config::HomeAssistantConfig;
 
 pub static LOAD_MAP: OnceLock<RwLock<HashMap<String, String>>> = OnceLock::new();
 
+pub mod backend;
 pub mod entity;
+pub mod fake;
+pub mod http;
 
-pub fn init() -> reqwest::Client {
-    reqwest::Client::new()
-}
+use backend::HomeAssistantBackend;
 
 pub fn init_load_map() -> RwLock<HashMap<String, String>> {
     RwLock::new(HashMap::new())
 }
 
+/// Backend for the configured Home Assistant, `memory://` URLs get an in-memory fake
+pub fn from_config(config: &HomeAssistantConfig) -> Arc<dyn HomeAssistantBackend> {
+    let url = config.url.as_deref().unwrap_or_default();
+
+    if url.starts_with("memory://") {
+        return Arc::new(fake::FakeBackend::from_file(config.fake_states.as_deref()));
+    }
+
+    let policy = config.upstream.policy();
+    let token = config.token.as_deref().unwrap_or_default();
+
+    Arc::new(http::HttpBackend::new(policy.client(), &policy, url, token))
+}
+
+#[allow(clippy::enum_variant_names)]
 #[derive(thiserror::Error, Debug)]
 pub enum ApiError {
     #[error("Request failed: {0}")]
@@ -29,8 +44,79 @@ pub enum ApiError {
     SerdeError(#[from] serde_json::Error),
     #[error("Home Assistant API error: {0}")]
     HomeAssistantError(String),
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
     #[error("LOAD_MAP error: {0}")]
     LoadMapError(String),
+    #[error("Unknown entity: {0}")]
+    UnknownEntity(String),
+    #[error("Circuit open: {0}")]
+    CircuitOpen(String),
+}
+
+impl From<UpstreamError> for ApiError {
+    fn from(error: UpstreamError) -> Self {
+        match error {
+            UpstreamError::Request(e) => ApiError::ReqwestError(e),
+            e @ UpstreamError::CircuitOpen(..) => ApiError::CircuitOpen(e.to_string()),
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::ReqwestError(e) => request_status(e),
+            ApiError::SerdeError(_) | ApiError::HomeAssistantError(_) | ApiError::Unauthorized(_) => {
+                StatusCode::BAD_GATEWAY
+            }
+            ApiError::LoadMapError(_) | ApiError::UnknownEntity(_) => StatusCode::NOT_FOUND,
+            ApiError::CircuitOpen(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let body = match self {
+            ApiError::ReqwestError(e) if e.is_timeout() => ErrorBody::new(
+                "upstream_timeout",
+                "Home Assistant did not respond in time",
+                Some(e.to_string()),
+            ),
+            ApiError::ReqwestError(e) => ErrorBody::new(
+                "upstream_unavailable",
+                "Home Assistant request failed",
+                Some(e.to_string()),
+            ),
+            ApiError::SerdeError(e) => ErrorBody::new(
+                "upstream_invalid_response",
+                "Home Assistant returned an unexpected response",
+                Some(e.to_string()),
+            ),
+            ApiError::HomeAssistantError(detail) => ErrorBody::new(
+                "upstream_error",
+                "Home Assistant reported an error",
+                Some(detail.clone()),
+            ),
+            ApiError::Unauthorized(detail) => ErrorBody::new(
+                "upstream_unauthorized",
+                "Home Assistant rejected the access token",
+                Some(detail.clone()),
+            ),
+            ApiError::LoadMapError(message) => ErrorBody::new("unknown_load", message, None),
+            ApiError::UnknownEntity(detail) => ErrorBody::new(
+                "unknown_entity",
+                "Unknown Home Assistant entity",
+                Some(detail.clone()),
+            ),
+            ApiError::CircuitOpen(detail) => ErrorBody::new(
+                "upstream_circuit_open",
+                "Home Assistant is unavailable, try again later",
+                Some(detail.clone()),
+            ),
+        };
+
+        body.response(self.status_code())
+    }
 }
 
 #[allow(dead_code)]
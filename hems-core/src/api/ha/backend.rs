@@ -0,0 +1,48 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::{json, Value};
+use utoipa::ToSchema;
+
+use super::ApiError;
+use crate::api::upstream::CircuitBreaker;
+
+/// A request an in-memory stand-in received, in the order it was made
+#[derive(Serialize, ToSchema, Debug, Clone)]
+#[serde(tag = "call", rename_all = "snake_case")]
+pub enum HomeAssistantCall {
+    GetState { entity_id: String },
+    SetState { entity_id: String, state: Value },
+    CallService { domain: String, service: String, data: Value },
+}
+
+/// A Home Assistant instance, as seen through its REST API
+#[async_trait]
+pub trait HomeAssistantBackend: Send + Sync {
+    /// Circuit breaker guarding the calls, for backends reached over the network
+    fn breaker(&self) -> Option<&CircuitBreaker> {
+        None
+    }
+
+    /// Latest calls received by an in-memory stand-in, `None` for a real Home Assistant
+    fn calls(&self) -> Option<Vec<HomeAssistantCall>> {
+        None
+    }
+
+    /// Checks that the instance is reachable and accepts the configured token
+    async fn validate(&self) -> Result<(), ApiError>;
+
+    async fn get_entity_state(&self, entity_id: &str) -> Result<Value, ApiError>;
+
+    async fn set_entity_state(&self, entity_id: &str, entity_state: Value) -> Result<(), ApiError>;
+
+    /// Calls `domain.service`, returning the states changed by the call
+    async fn call_service(&self, domain: &str, service: &str, data: Value) -> Result<Value, ApiError>;
+
+    async fn toggle_entity_state(&self, entity_id: &str, entity_state: bool) -> Result<Value, ApiError> {
+        let domain = entity_id.split('.').next().unwrap_or_default();
+        let service = if entity_state { "turn_on" } else { "turn_off" };
+
+        self.call_service(domain, service, json!({ "entity_id": entity_id }))
+            .await
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+    sync::Mutex,
+};
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use super::{
+    backend::{HomeAssistantBackend, HomeAssistantCall},
+    ApiError,
+};
+
+/// Calls kept for `GET /debug/home-assistant/calls`, older ones are dropped
+const MAX_CALLS: usize = 1_000;
+
+/// In-memory stand-in for Home Assistant.
+///
+/// Serves canned entity states and records the latest calls, so scenarios mixing simulated and
+/// "real" devices can run locally. Selected with a `memory://` URL, e.g.
+/// `HA_URL=memory://homeassistant`; canned states are read from the JSON file in
+/// `home_assistant.fake_states` (`HA_FAKE_STATES`), mapping entity ids to a state string or a
+/// full state object.
+pub struct FakeBackend {
+    states: Mutex<HashMap<String, Value>>,
+    calls: Mutex<VecDeque<HomeAssistantCall>>,
+}
+
+impl FakeBackend {
+    pub fn new(states: HashMap<String, Value>) -> Self {
+        Self {
+            states: Mutex::new(
+                states
+                    .into_iter()
+                    .map(|(entity_id, state)| {
+                        let state = state_object(&entity_id, state);
+                        (entity_id, state)
+                    })
+                    .collect(),
+            ),
+            calls: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Reads canned states from a JSON file, starting without states if it cannot be read
+    pub fn from_file(path: Option<&Path>) -> Self {
+        let states = match path {
+            Some(path) => match std::fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|input| serde_json::from_str(&input).map_err(|e| e.to_string()))
+            {
+                Ok(states) => states,
+                Err(e) => {
+                    log::warn!("Ignoring canned Home Assistant states in {}: {e}", path.display());
+                    HashMap::new()
+                }
+            },
+            None => HashMap::new(),
+        };
+
+        Self::new(states)
+    }
+
+    fn record(&self, call: HomeAssistantCall) {
+        log::debug!("Home Assistant call: {:?}", call);
+
+        let mut calls = self.calls.lock().unwrap();
+        if calls.len() == MAX_CALLS {
+            calls.pop_front();
+        }
+        calls.push_back(call);
+    }
+}
+
+/// Wraps a plain state string into the object Home Assistant returns for an entity
+fn state_object(entity_id: &str, state: Value) -> Value {
+    let mut state = match state {
+        Value::Object(_) => state,
+        Value::String(state) => json!({ "state": state }),
+        other => json!({ "state": other.to_string() }),
+    };
+
+    state["entity_id"] = json!(entity_id);
+    if state.get("attributes").is_none() {
+        state["attributes"] = json!({});
+    }
+
+    state
+}
+
+#[async_trait]
+impl HomeAssistantBackend for FakeBackend {
+    fn calls(&self) -> Option<Vec<HomeAssistantCall>> {
+        Some(self.calls.lock().unwrap().iter().cloned().collect())
+    }
+
+    async fn validate(&self) -> Result<(), ApiError> {
+        Ok(())
+    }
+
+    async fn get_entity_state(&self, entity_id: &str) -> Result<Value, ApiError> {
+        self.record(HomeAssistantCall::GetState {
+            entity_id: entity_id.to_string(),
+        });
+
+        self.states
+            .lock()
+            .unwrap()
+            .get(entity_id)
+            .cloned()
+            .ok_or_else(|| ApiError::UnknownEntity(entity_id.to_string()))
+    }
+
+    async fn set_entity_state(&self, entity_id: &str, entity_state: Value) -> Result<(), ApiError> {
+        self.record(HomeAssistantCall::SetState {
+            entity_id: entity_id.to_string(),
+            state: entity_state.clone(),
+        });
+
+        if entity_state.get("state").is_none() {
+            return Err(ApiError::HomeAssistantError(
+                "Failed to set entity state: No state specified.".to_string(),
+            ));
+        }
+
+        self.states
+            .lock()
+            .unwrap()
+            .insert(entity_id.to_string(), state_object(entity_id, entity_state));
+
+        Ok(())
+    }
+
+    async fn call_service(&self, domain: &str, service: &str, data: Value) -> Result<Value, ApiError> {
+        self.record(HomeAssistantCall::CallService {
+            domain: domain.to_string(),
+            service: service.to_string(),
+            data: data.clone(),
+        });
+
+        let new_state = match service {
+            "turn_on" => "on",
+            "turn_off" => "off",
+            _ => return Ok(json!([])),
+        };
+
+        let entity_id = data["entity_id"].as_str().unwrap_or_default();
+        let mut states = self.states.lock().unwrap();
+
+        match states.get_mut(entity_id) {
+            Some(state) => {
+                state["state"] = json!(new_state);
+                Ok(json!([state.clone()]))
+            }
+            None => Ok(json!([])),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn keeps_the_latest_calls() {
+        let fake = FakeBackend::new(HashMap::from([("switch.kettle".to_string(), json!("off"))]));
+
+        for _ in 0..MAX_CALLS {
+            fake.get_entity_state("switch.kettle").await.unwrap();
+        }
+        fake.toggle_entity_state("switch.kettle", true).await.unwrap();
+
+        let calls = fake.calls().unwrap();
+        assert_eq!(calls.len(), MAX_CALLS);
+        assert!(matches!(calls.last(), Some(HomeAssistantCall::CallService { service, .. }) if service == "turn_on"));
+    }
+}
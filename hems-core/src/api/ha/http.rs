@@ -0,0 +1,121 @@
+use async_trait::async_trait;
+use serde_json::Value;
+
+use super::{backend::HomeAssistantBackend, ApiError};
+use crate::api::upstream::{self, Call, CircuitBreaker, Policy};
+
+/// Home Assistant instance reached over its REST API with a long-lived access token
+pub struct HttpBackend {
+    client: reqwest::Client,
+    base_url: String,
+    token: String,
+    policy: Policy,
+    breaker: CircuitBreaker,
+}
+
+impl HttpBackend {
+    pub fn new(client: reqwest::Client, policy: &Policy, base_url: &str, token: &str) -> Self {
+        Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            policy: policy.clone(),
+            breaker: CircuitBreaker::new(policy),
+        }
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder, call: Call) -> Result<reqwest::Response, ApiError> {
+        let request = request.bearer_auth(&self.token);
+
+        Ok(upstream::send("Home Assistant", &self.policy, &self.breaker, request, call).await?)
+    }
+
+    async fn error_text(response: reqwest::Response) -> String {
+        response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unknown error".to_string())
+    }
+}
+
+#[async_trait]
+impl HomeAssistantBackend for HttpBackend {
+    fn breaker(&self) -> Option<&CircuitBreaker> {
+        Some(&self.breaker)
+    }
+
+    async fn validate(&self) -> Result<(), ApiError> {
+        let url = format!("{}/api/", self.base_url);
+
+        let response = self.send(self.client.get(url), Call::Idempotent).await?;
+
+        if matches!(
+            response.status(),
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN
+        ) {
+            return Err(ApiError::Unauthorized(
+                "HA_TOKEN was rejected by Home Assistant".to_string(),
+            ));
+        }
+
+        if !response.status().is_success() {
+            return Err(ApiError::HomeAssistantError(format!(
+                "Failed to reach Home Assistant: {}",
+                Self::error_text(response).await
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn get_entity_state(&self, entity_id: &str) -> Result<Value, ApiError> {
+        let url = format!("{}/api/states/{}", self.base_url, entity_id);
+
+        let response = self.send(self.client.get(url), Call::Idempotent).await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(ApiError::UnknownEntity(entity_id.to_string()));
+        }
+
+        if !response.status().is_success() {
+            return Err(ApiError::HomeAssistantError(format!(
+                "Failed to get entity state: {}",
+                Self::error_text(response).await
+            )));
+        }
+
+        Ok(response.json().await?)
+    }
+
+    async fn set_entity_state(&self, entity_id: &str, entity_state: Value) -> Result<(), ApiError> {
+        let url = format!("{}/api/states/{}", self.base_url, entity_id);
+
+        let response = self
+            .send(self.client.post(url).json(&entity_state), Call::Idempotent)
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::HomeAssistantError(format!(
+                "Failed to set entity state: {}",
+                Self::error_text(response).await
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn call_service(&self, domain: &str, service: &str, data: Value) -> Result<Value, ApiError> {
+        let url = format!("{}/api/services/{}/{}", self.base_url, domain, service);
+
+        let response = self.send(self.client.post(url).json(&data), Call::Once).await?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::HomeAssistantError(format!(
+                "Failed to call {domain}.{service}: {}",
+                Self::error_text(response).await
+            )));
+        }
+
+        Ok(response.json().await?)
+    }
+}
@@ -0,0 +1,209 @@
+use std::time::Instant;
+
+use actix_web::{get, web, HttpResponse, Responder};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::demkit::{
+    backend::{ComposerStatus, DemkitBackend},
+    registry::Registry,
+};
+use super::ha::backend::HomeAssistantBackend;
+use super::upstream::{BreakerStatus, CircuitState};
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    /// Every upstream service is reachable
+    Ok,
+    /// Calls to at least one upstream service are failing or suspended
+    Degraded,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UpstreamHealth {
+    /// `demkit` or `home_assistant`
+    pub service: String,
+    /// Base URL of the DEMKit instance
+    #[schema(nullable)]
+    pub url: Option<String>,
+    pub circuit: BreakerStatus,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Health {
+    pub status: HealthStatus,
+    /// Circuit breakers of the DEMKit instances and Home Assistant, in-memory stand-ins have none
+    pub upstreams: Vec<UpstreamHealth>,
+}
+
+#[utoipa::path(
+    get,
+    description = "Health check endpoint, reporting the circuit breakers of the upstream services. The service stays up while they fail, a `degraded` status still answers 200.",
+    path = "/healthz",
+    responses(
+        (status = OK, description = "Health check OK", body = Health),
+    )
+)]
+#[get("/healthz")]
+pub async fn health(
+    registry: web::Data<Registry>,
+    ha: web::Data<dyn HomeAssistantBackend>,
+) -> impl Responder {
+    let mut upstreams: Vec<UpstreamHealth> = registry
+        .backends()
+        .iter()
+        .filter_map(|backend| {
+            backend.breaker().map(|breaker| UpstreamHealth {
+                service: "demkit".to_string(),
+                url: Some(backend.url().to_string()),
+                circuit: breaker.status(),
+            })
+        })
+        .collect();
+
+    if let Some(breaker) = ha.breaker() {
+        upstreams.push(UpstreamHealth {
+            service: "home_assistant".to_string(),
+            url: None,
+            circuit: breaker.status(),
+        });
+    }
+
+    let status = if upstreams
+        .iter()
+        .all(|upstream| upstream.circuit.state == CircuitState::Closed && upstream.circuit.consecutive_failures == 0)
+    {
+        HealthStatus::Ok
+    } else {
+        HealthStatus::Degraded
+    };
+
+    HttpResponse::Ok().json(Health { status, upstreams })
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DependencyReadiness {
+    /// `demkit` or `home_assistant`
+    pub service: String,
+    /// Base URL of the DEMKit instance
+    #[schema(nullable)]
+    pub url: Option<String>,
+    /// Whether the service answered the probes
+    pub ready: bool,
+    /// Time taken by the probes, in milliseconds
+    pub latency_ms: u64,
+    /// State of the simulation on the DEMKit instance
+    #[schema(nullable)]
+    pub simulation: Option<ComposerStatus>,
+    /// Current simulation time, once a simulation is loaded
+    #[schema(nullable)]
+    pub time: Option<u64>,
+    /// Why a probe failed
+    #[schema(nullable)]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Readiness {
+    pub ready: bool,
+    pub dependencies: Vec<DependencyReadiness>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ReadinessQuery {
+    /// Only report ready once every DEMKit instance runs a simulation
+    #[serde(default)]
+    require_simulation: bool,
+}
+
+/// Probes the composer status and, once a simulation is loaded, the simulation time
+async fn probe_demkit(backend: &dyn DemkitBackend, require_simulation: bool) -> DependencyReadiness {
+    let started = Instant::now();
+
+    let probe = async {
+        let simulation = backend.composer_status().await?;
+        let time = match simulation {
+            ComposerStatus::Inactive => None,
+            _ => Some(backend.get_time().await?),
+        };
+
+        Ok::<_, super::demkit::ApiError>((simulation, time))
+    };
+
+    let (simulation, time, error) = match probe.await {
+        Ok((simulation, time)) => (Some(simulation), time, None),
+        Err(e) => (None, None, Some(e.to_string())),
+    };
+
+    let error = match simulation {
+        Some(status) if require_simulation && status != ComposerStatus::Active => {
+            Some("No simulation is running".to_string())
+        }
+        _ => error,
+    };
+
+    DependencyReadiness {
+        service: "demkit".to_string(),
+        url: Some(backend.url().to_string()),
+        ready: error.is_none(),
+        latency_ms: started.elapsed().as_millis() as u64,
+        simulation,
+        time,
+        error,
+    }
+}
+
+async fn probe_home_assistant(ha: &dyn HomeAssistantBackend) -> DependencyReadiness {
+    let started = Instant::now();
+    let error = ha.validate().await.err().map(|e| e.to_string());
+
+    DependencyReadiness {
+        service: "home_assistant".to_string(),
+        url: None,
+        ready: error.is_none(),
+        latency_ms: started.elapsed().as_millis() as u64,
+        simulation: None,
+        time: None,
+        error,
+    }
+}
+
+#[utoipa::path(
+    get,
+    description = "Readiness check probing every DEMKit instance (composer status and time) and Home Assistant.",
+    path = "/readyz",
+    responses(
+        (status = OK, description = "All dependencies are ready", body = Readiness),
+        (status = SERVICE_UNAVAILABLE, description = "At least one dependency is not ready", body = Readiness),
+    ),
+    params(ReadinessQuery),
+)]
+#[get("/readyz")]
+pub async fn ready(
+    registry: web::Data<Registry>,
+    ha: web::Data<dyn HomeAssistantBackend>,
+    query: web::Query<ReadinessQuery>,
+) -> impl Responder {
+    let backends = registry.backends();
+
+    let (mut dependencies, home_assistant) = futures::join!(
+        join_all(
+            backends
+                .iter()
+                .map(|backend| probe_demkit(backend.as_ref(), query.require_simulation))
+        ),
+        probe_home_assistant(ha.get_ref()),
+    );
+    dependencies.push(home_assistant);
+
+    let ready = dependencies.iter().all(|dependency| dependency.ready);
+    let readiness = Readiness { ready, dependencies };
+
+    if ready {
+        HttpResponse::Ok().json(readiness)
+    } else {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    }
+}
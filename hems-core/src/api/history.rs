@@ -0,0 +1,750 @@
+use std::{collections::HashMap, path::Path, sync::Arc, sync::Mutex, time::Duration};
+
+use actix_web::{http::StatusCode, web, HttpResponse, ResponseError};
+use futures::future::join_all;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::demkit::{self, backend::DemkitBackend, ha_entity, registry::Registry, Commodities};
+use super::error::ErrorBody;
+use super::ha::{self, backend::HomeAssistantBackend};
+use crate::config::HistoryConfig;
+
+#[derive(thiserror::Error, Debug)]
+pub enum HistoryError {
+    #[error("History database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("Invalid request: {0}")]
+    Validation(String),
+    #[error("Export failed: {0}")]
+    Export(String),
+    #[error("History task failed: {0}")]
+    Blocking(String),
+}
+
+impl ResponseError for HistoryError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            HistoryError::Database(_) | HistoryError::Export(_) | HistoryError::Blocking(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            HistoryError::Validation(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let body = match self {
+            HistoryError::Database(e) => {
+                ErrorBody::new("history_error", "Failed to read the device history", Some(e.to_string()))
+            }
+            HistoryError::Validation(message) => ErrorBody::new("validation_error", message, None),
+            HistoryError::Export(detail) => {
+                ErrorBody::new("export_error", "Failed to encode the export", Some(detail.clone()))
+            }
+            HistoryError::Blocking(detail) => {
+                ErrorBody::new("history_error", "Failed to access the device history", Some(detail.clone()))
+            }
+        };
+
+        body.response(self.status_code())
+    }
+}
+
+/// A sampled value of a device
+pub(super) struct Sample {
+    device: String,
+    quantity: &'static str,
+    unit: &'static str,
+    value: f64,
+}
+
+impl Sample {
+    pub(super) fn new(device: &str, quantity: &'static str, unit: &'static str, value: f64) -> Self {
+        Self {
+            device: device.to_string(),
+            quantity,
+            unit,
+            value,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Series {
+    /// Device name without the `-House-{id}` suffix, or the Home Assistant entity ID
+    pub device: String,
+    /// `electricity`, `heat` or `natgas` consumption, `soc` or `temperature`
+    pub quantity: String,
+    pub unit: String,
+    /// Simulation time of the first sample
+    pub first: u64,
+    /// Simulation time of the last sample
+    pub last: u64,
+    pub samples: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Bucket {
+    /// Simulation time at the start of the bucket
+    pub time: u64,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Samples aggregated into the bucket
+    pub samples: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SeriesHistory {
+    pub device: String,
+    pub quantity: String,
+    pub unit: String,
+    pub buckets: Vec<Bucket>,
+}
+
+/// Series of a house pivoted into one column each, for reading its samples row by row
+pub struct WideColumns {
+    /// `{device}.{quantity}` of each column
+    pub columns: Vec<String>,
+    /// Unit of each column
+    pub units: Vec<String>,
+    /// Column of each `(device, quantity)`
+    index: HashMap<(String, String), usize>,
+}
+
+/// Simulation time and the value of each column, `None` when the series has no sample then
+pub type WideRow = (u64, Vec<Option<f64>>);
+
+/// Simulation config and house spec a house was last configured and composed with
+#[derive(Serialize, Default)]
+pub struct RunInfo {
+    pub sim_config: Option<serde_json::Value>,
+    pub composition: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct HistoryQuery {
+    /// Start of the range in simulation time, inclusive, defaults to the first sample
+    pub from: Option<u64>,
+    /// End of the range in simulation time, exclusive, defaults to the last sample
+    pub to: Option<u64>,
+    /// Bucket width in seconds, defaults to the sampling interval
+    pub resolution: Option<u64>,
+    /// Only return the series of this device
+    pub device: Option<String>,
+    /// Only return series of this quantity
+    pub quantity: Option<String>,
+}
+
+/// Time series store of the sampled devices of every house.
+///
+/// Samples are keyed by house, device, quantity and simulation time. A simulation restarted at
+/// an earlier time overwrites the samples it runs over.
+pub struct History {
+    conn: Mutex<Connection>,
+    /// Simulation seconds between two samples of a house
+    pub interval: u64,
+}
+
+impl History {
+    pub fn open(config: &HistoryConfig) -> Result<Self, HistoryError> {
+        let conn = match config.path.as_path() {
+            path if path == Path::new(":memory:") => Connection::open_in_memory()?,
+            path => {
+                if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                    std::fs::create_dir_all(dir).map_err(|e| {
+                        HistoryError::Validation(format!("Cannot create {}: {e}", dir.display()))
+                    })?;
+                }
+                Connection::open(path)?
+            }
+        };
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS samples (
+                house_id INTEGER NOT NULL,
+                device TEXT NOT NULL,
+                quantity TEXT NOT NULL,
+                unit TEXT NOT NULL,
+                time INTEGER NOT NULL,
+                value REAL NOT NULL,
+                PRIMARY KEY (house_id, device, quantity, time)
+            ) WITHOUT ROWID;
+            CREATE INDEX IF NOT EXISTS samples_by_time ON samples (house_id, time);
+            CREATE TABLE IF NOT EXISTS runs (
+                house_id INTEGER PRIMARY KEY,
+                sim_config TEXT,
+                composition TEXT
+            );",
+        )?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            interval: config.interval,
+        })
+    }
+
+    /// Runs `f` on the blocking thread pool, so SQLite never stalls the async workers
+    pub async fn blocking<T, F>(self: Arc<Self>, f: F) -> Result<T, HistoryError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&History) -> Result<T, HistoryError> + Send + 'static,
+    {
+        web::block(move || f(&self))
+            .await
+            .map_err(|e| HistoryError::Blocking(e.to_string()))?
+    }
+
+    pub(super) fn record(&self, house_id: u32, time: u64, samples: &[Sample]) -> Result<(), HistoryError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT OR REPLACE INTO samples (house_id, device, quantity, unit, time, value)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for sample in samples.iter().filter(|sample| sample.value.is_finite()) {
+                insert.execute(params![
+                    house_id,
+                    sample.device,
+                    sample.quantity,
+                    sample.unit,
+                    time as i64,
+                    sample.value
+                ])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Remembers the simulation config of the house for exports
+    pub fn record_config(&self, house_id: u32, config: &impl Serialize) -> Result<(), HistoryError> {
+        let config = serde_json::to_string(config).map_err(|e| HistoryError::Export(e.to_string()))?;
+
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO runs (house_id, sim_config) VALUES (?1, ?2)
+             ON CONFLICT (house_id) DO UPDATE SET sim_config = excluded.sim_config",
+            params![house_id, config],
+        )?;
+        Ok(())
+    }
+
+    /// Remembers the spec the house was composed from for exports
+    pub fn record_composition(&self, house_id: u32, spec: &impl Serialize) -> Result<(), HistoryError> {
+        let spec = serde_json::to_string(spec).map_err(|e| HistoryError::Export(e.to_string()))?;
+
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO runs (house_id, composition) VALUES (?1, ?2)
+             ON CONFLICT (house_id) DO UPDATE SET composition = excluded.composition",
+            params![house_id, spec],
+        )?;
+        Ok(())
+    }
+
+    pub fn run_info(&self, house_id: u32) -> Result<RunInfo, HistoryError> {
+        let conn = self.conn.lock().unwrap();
+        let mut select = conn.prepare_cached("SELECT sim_config, composition FROM runs WHERE house_id = ?1")?;
+        let mut rows = select.query(params![house_id])?;
+
+        let Some(row) = rows.next()? else {
+            return Ok(RunInfo::default());
+        };
+        // stored by hems-core itself, so only absent values are expected
+        let parse = |json: Option<String>| json.and_then(|json| serde_json::from_str(&json).ok());
+
+        Ok(RunInfo {
+            sim_config: parse(row.get(0)?),
+            composition: parse(row.get(1)?),
+        })
+    }
+
+    /// Series with samples of the house in `[from, to)`, one column each
+    pub fn wide_columns(&self, house_id: u32, from: Option<u64>, to: Option<u64>) -> Result<WideColumns, HistoryError> {
+        let (from, to) = range(from, to)?;
+        let conn = self.conn.lock().unwrap();
+
+        let mut select = conn.prepare_cached(
+            "SELECT device, quantity, unit FROM samples
+             WHERE house_id = ?1 AND time >= ?2 AND time < ?3
+             GROUP BY device, quantity ORDER BY device, quantity",
+        )?;
+        let series = select
+            .query_map(params![house_id, from, to], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(WideColumns {
+            columns: series.iter().map(|(device, quantity, _)| format!("{device}.{quantity}")).collect(),
+            units: series.iter().map(|(_, _, unit)| unit.clone()).collect(),
+            index: series
+                .into_iter()
+                .enumerate()
+                .map(|(index, (device, quantity, _))| ((device, quantity), index))
+                .collect(),
+        })
+    }
+
+    /// Rows of the first `limit` simulation times with samples in `[from, to)`, so a long history
+    /// can be read a page at a time. Series that are not in `columns` are left out.
+    pub fn wide_rows(
+        &self,
+        house_id: u32,
+        columns: &WideColumns,
+        from: u64,
+        to: Option<u64>,
+        limit: usize,
+    ) -> Result<Vec<WideRow>, HistoryError> {
+        let (from, to) = clamp(Some(from), to);
+        let conn = self.conn.lock().unwrap();
+
+        let mut select = conn.prepare_cached(
+            "SELECT time, device, quantity, value FROM samples
+             WHERE house_id = ?1 AND time >= ?2 AND time <= (
+                 SELECT MAX(time) FROM (
+                     SELECT DISTINCT time FROM samples
+                     WHERE house_id = ?1 AND time >= ?2 AND time < ?3
+                     ORDER BY time LIMIT ?4
+                 )
+             )
+             ORDER BY time",
+        )?;
+        let samples = select.query_map(params![house_id, from, to, limit.min(i64::MAX as usize) as i64], |row| {
+            Ok((
+                row.get::<_, i64>(0)? as u64,
+                (row.get::<_, String>(1)?, row.get::<_, String>(2)?),
+                row.get::<_, f64>(3)?,
+            ))
+        })?;
+
+        let mut rows: Vec<WideRow> = Vec::new();
+        for sample in samples {
+            let (time, series, value) = sample?;
+
+            if rows.last().is_none_or(|(last, _)| *last != time) {
+                rows.push((time, vec![None; columns.columns.len()]));
+            }
+            if let (Some((_, row)), Some(&index)) = (rows.last_mut(), columns.index.get(&series)) {
+                row[index] = Some(value);
+            }
+        }
+
+        Ok(rows)
+    }
+
+    /// Series recorded for the house
+    pub fn list_series(&self, house_id: u32) -> Result<Vec<Series>, HistoryError> {
+        let conn = self.conn.lock().unwrap();
+        let mut select = conn.prepare_cached(
+            "SELECT device, quantity, unit, MIN(time), MAX(time), COUNT(*) FROM samples
+             WHERE house_id = ?1 GROUP BY device, quantity ORDER BY device, quantity",
+        )?;
+
+        let series = select
+            .query_map(params![house_id], |row| {
+                Ok(Series {
+                    device: row.get(0)?,
+                    quantity: row.get(1)?,
+                    unit: row.get(2)?,
+                    first: row.get::<_, i64>(3)? as u64,
+                    last: row.get::<_, i64>(4)? as u64,
+                    samples: row.get::<_, i64>(5)? as u64,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+
+        Ok(series)
+    }
+
+    /// Samples of the house in `[from, to)`, aggregated into buckets of `resolution` seconds
+    pub fn query(&self, house_id: u32, query: &HistoryQuery) -> Result<Vec<SeriesHistory>, HistoryError> {
+        let resolution = query.resolution.unwrap_or(self.interval);
+        if resolution == 0 {
+            return Err(HistoryError::Validation("resolution must be positive".to_string()));
+        }
+
+        let resolution = resolution.min(i64::MAX as u64) as i64;
+        let (from, to) = range(query.from, query.to)?;
+
+        let conn = self.conn.lock().unwrap();
+        let mut select = conn.prepare_cached(
+            "SELECT device, quantity, unit, (time / ?4) * ?4 AS bucket,
+                    AVG(value), MIN(value), MAX(value), COUNT(*)
+             FROM samples
+             WHERE house_id = ?1 AND time >= ?2 AND time < ?3
+               AND (?5 IS NULL OR device = ?5) AND (?6 IS NULL OR quantity = ?6)
+             GROUP BY device, quantity, bucket
+             ORDER BY device, quantity, bucket",
+        )?;
+
+        let rows = select.query_map(
+            params![
+                house_id,
+                from,
+                to,
+                resolution,
+                query.device,
+                query.quantity
+            ],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    Bucket {
+                        time: row.get::<_, i64>(3)? as u64,
+                        mean: row.get(4)?,
+                        min: row.get(5)?,
+                        max: row.get(6)?,
+                        samples: row.get::<_, i64>(7)? as u64,
+                    },
+                ))
+            },
+        )?;
+
+        let mut history: Vec<SeriesHistory> = Vec::new();
+        for row in rows {
+            let (device, quantity, unit, bucket) = row?;
+
+            match history.last_mut() {
+                Some(series) if series.device == device && series.quantity == quantity => {
+                    series.buckets.push(bucket)
+                }
+                _ => history.push(SeriesHistory {
+                    device,
+                    quantity,
+                    unit,
+                    buckets: vec![bucket],
+                }),
+            }
+        }
+
+        Ok(history)
+    }
+}
+
+/// Clamps a `[from, to)` range of simulation time to SQLite's signed integers
+fn clamp(from: Option<u64>, to: Option<u64>) -> (i64, i64) {
+    let from = from.unwrap_or(0).min(i64::MAX as u64) as i64;
+    let to = to.unwrap_or(u64::MAX).min(i64::MAX as u64) as i64;
+
+    (from, to)
+}
+
+/// Clamped `[from, to)` range of simulation time, which must not be empty
+fn range(from: Option<u64>, to: Option<u64>) -> Result<(i64, i64), HistoryError> {
+    let (from, to) = clamp(from, to);
+
+    if from >= to {
+        return Err(HistoryError::Validation(format!(
+            "from ({from}) must be before to ({to})"
+        )));
+    }
+
+    Ok((from, to))
+}
+
+/// Consumption, state of charge and temperature reported by the DEMKit entities of the house
+async fn sample_demkit(backend: &dyn DemkitBackend, house_id: u32) -> Result<Vec<Sample>, demkit::ApiError> {
+    let entities = demkit::list_entities(backend, house_id).await?;
+    let properties = join_all(entities.iter().map(|entity| backend.properties(entity))).await;
+
+    let host = format!("House-{house_id}");
+    let suffix = format!("-{host}");
+    let mut samples = Vec::new();
+
+    for (entity, properties) in entities.iter().zip(properties) {
+        // entities removed since they were listed are skipped
+        let properties = match properties {
+            Ok(properties) => properties,
+            Err(demkit::ApiError::UnknownEntity(_)) => continue,
+            Err(e) => return Err(e),
+        };
+        let device = match entity.strip_suffix(&suffix) {
+            Some(device) => device,
+            None if *entity == host => "House",
+            None => entity.as_str(),
+        };
+
+        if let Ok(consumption) = serde_json::from_value::<Commodities>(properties["consumption"].clone()) {
+            if let Ok(power) = consumption.electricity(entity) {
+                samples.push(Sample::new(device, "electricity", "W", power.re));
+            }
+            if let Ok(power) = consumption.heat(entity) {
+                samples.push(Sample::new(device, "heat", "W", power.re));
+            }
+            if let Ok(power) = consumption.natgas(entity) {
+                samples.push(Sample::new(device, "natgas", "W", power.re));
+            }
+        }
+        if let Some(soc) = properties["soc"].as_f64() {
+            samples.push(Sample::new(device, "soc", "Wh", soc));
+        }
+        if let Some(temperature) = properties["temperature"].as_f64() {
+            samples.push(Sample::new(device, "temperature", "°C", temperature));
+        }
+    }
+
+    Ok(samples)
+}
+
+/// Consumption of the Home Assistant entities added to the house
+async fn sample_ha(ha: &dyn HomeAssistantBackend, house_id: u32) -> Vec<Sample> {
+    let entities = ha_entity::list_entities(house_id);
+    let states = join_all(entities.iter().map(|entity| ha::entity::get_entity_consumption(ha, entity))).await;
+
+    entities
+        .iter()
+        .zip(states)
+        .filter_map(|(entity, state)| match state {
+            Ok(state) => Some(Sample::new(entity, "electricity", "W", state.consumption.parse().ok()?)),
+            Err(e) => {
+                log::debug!("Failed to sample Home Assistant entity {entity} of house {house_id}: {e}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Samples every composed house each time its simulation time advanced by the interval
+pub async fn run(
+    registry: Arc<Registry>,
+    ha: Arc<dyn HomeAssistantBackend>,
+    history: Arc<History>,
+    poll: Duration,
+) {
+    let mut interval = actix_web::rt::time::interval(poll);
+    // simulation time of the last sample of each house
+    let mut sampled: HashMap<u32, u64> = HashMap::new();
+
+    loop {
+        interval.tick().await;
+
+        for house in registry.list().into_iter().filter(|house| house.composed) {
+            let house_id = house.house_id;
+            let backend = registry.backend(house_id);
+
+            // houses that are not loaded yet are tried again on the next check
+            let time = match backend.get_time().await {
+                Ok(time) => time,
+                Err(e) => {
+                    log::debug!("Failed to read the time of house {house_id}: {e}");
+                    continue;
+                }
+            };
+
+            // a reset simulation may start over at an earlier time
+            let due = match sampled.get(&house_id) {
+                Some(&last) => time < last || time - last >= history.interval,
+                None => true,
+            };
+            if !due {
+                continue;
+            }
+
+            let mut samples = match sample_demkit(backend.as_ref(), house_id).await {
+                Ok(samples) => samples,
+                Err(e) => {
+                    log::debug!("Failed to sample house {house_id}: {e}");
+                    continue;
+                }
+            };
+            if samples.is_empty() {
+                continue;
+            }
+            samples.extend(sample_ha(ha.as_ref(), house_id).await);
+
+            match history.clone().blocking(move |history| history.record(house_id, time, &samples)).await {
+                Ok(_) => {
+                    sampled.insert(house_id, time);
+                }
+                Err(e) => log::warn!("Failed to record the history of house {house_id}: {e}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> History {
+        History::open(&HistoryConfig {
+            path: ":memory:".into(),
+            interval: 900,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    /// Battery consumption and state of charge every 900s and a zone temperature at the start
+    fn recorded() -> History {
+        let history = history();
+
+        history
+            .record(
+                1,
+                0,
+                &[
+                    Sample::new("Battery", "electricity", "W", 100.0),
+                    Sample::new("Battery", "soc", "Wh", 500.0),
+                    Sample::new("Zone", "temperature", "°C", 20.0),
+                ],
+            )
+            .unwrap();
+        history
+            .record(
+                1,
+                900,
+                &[
+                    Sample::new("Battery", "electricity", "W", 300.0),
+                    Sample::new("Battery", "soc", "Wh", 600.0),
+                ],
+            )
+            .unwrap();
+        history.record(1, 1800, &[Sample::new("Battery", "electricity", "W", -200.0)]).unwrap();
+        history.record(2, 0, &[Sample::new("Battery", "electricity", "W", 1.0)]).unwrap();
+
+        history
+    }
+
+    fn query(from: Option<u64>, to: Option<u64>, resolution: Option<u64>) -> HistoryQuery {
+        HistoryQuery {
+            from,
+            to,
+            resolution,
+            device: None,
+            quantity: None,
+        }
+    }
+
+    /// `(time, mean, min, max, samples)` of each bucket
+    fn buckets(series: &SeriesHistory) -> Vec<(u64, f64, f64, f64, u64)> {
+        series
+            .buckets
+            .iter()
+            .map(|bucket| (bucket.time, bucket.mean, bucket.min, bucket.max, bucket.samples))
+            .collect()
+    }
+
+    #[test]
+    fn record_skips_values_that_are_not_finite() {
+        let history = history();
+
+        history
+            .record(
+                1,
+                0,
+                &[
+                    Sample::new("Battery", "electricity", "W", f64::NAN),
+                    Sample::new("Battery", "soc", "Wh", f64::INFINITY),
+                    Sample::new("Zone", "temperature", "°C", 20.0),
+                ],
+            )
+            .unwrap();
+
+        let series = history.list_series(1).unwrap();
+        assert_eq!(series.len(), 1);
+        assert_eq!((series[0].device.as_str(), series[0].quantity.as_str()), ("Zone", "temperature"));
+    }
+
+    #[test]
+    fn list_series_summarizes_the_house() {
+        let series = recorded().list_series(1).unwrap();
+
+        let summary: Vec<_> = series
+            .iter()
+            .map(|s| (s.device.as_str(), s.quantity.as_str(), s.unit.as_str(), s.first, s.last, s.samples))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("Battery", "electricity", "W", 0, 1800, 3),
+                ("Battery", "soc", "Wh", 0, 900, 2),
+                ("Zone", "temperature", "°C", 0, 0, 1),
+            ]
+        );
+        assert!(recorded().list_series(3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn query_aggregates_buckets() {
+        let history = recorded();
+
+        let series = history.query(1, &query(None, None, None)).unwrap();
+        assert_eq!(series.len(), 3);
+        assert_eq!(
+            buckets(&series[0]),
+            [(0, 100.0, 100.0, 100.0, 1), (900, 300.0, 300.0, 300.0, 1), (1800, -200.0, -200.0, -200.0, 1)]
+        );
+
+        let series = history.query(1, &query(None, None, Some(1800))).unwrap();
+        assert_eq!((series[0].device.as_str(), series[0].unit.as_str()), ("Battery", "W"));
+        assert_eq!(buckets(&series[0]), [(0, 200.0, 100.0, 300.0, 2), (1800, -200.0, -200.0, -200.0, 1)]);
+        assert_eq!(buckets(&series[1]), [(0, 550.0, 500.0, 600.0, 2)]);
+    }
+
+    #[test]
+    fn query_keeps_to_the_range() {
+        let history = recorded();
+
+        let series = history.query(1, &query(Some(900), Some(1800), None)).unwrap();
+        assert_eq!(series.len(), 2);
+        assert_eq!(buckets(&series[0]), [(900, 300.0, 300.0, 300.0, 1)]);
+        assert_eq!(buckets(&series[1]), [(900, 600.0, 600.0, 600.0, 1)]);
+
+        assert!(history.query(1, &query(Some(2700), None, None)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn query_rejects_invalid_ranges() {
+        let history = recorded();
+
+        for query in [
+            query(None, None, Some(0)),
+            query(Some(900), Some(900), None),
+            query(Some(1800), Some(900), None),
+            query(None, Some(0), None),
+        ] {
+            assert!(matches!(history.query(1, &query), Err(HistoryError::Validation(_))));
+        }
+    }
+
+    #[test]
+    fn query_filters_devices_and_quantities() {
+        let history = recorded();
+
+        let mut by_device = query(None, None, None);
+        by_device.device = Some("Zone".to_string());
+        let series = history.query(1, &by_device).unwrap();
+        assert_eq!(series.len(), 1);
+        assert_eq!((series[0].device.as_str(), series[0].quantity.as_str()), ("Zone", "temperature"));
+
+        let mut by_quantity = query(None, None, None);
+        by_quantity.quantity = Some("soc".to_string());
+        let series = history.query(1, &by_quantity).unwrap();
+        assert_eq!(series.len(), 1);
+        assert_eq!(buckets(&series[0]), [(0, 500.0, 500.0, 500.0, 1), (900, 600.0, 600.0, 600.0, 1)]);
+
+        by_quantity.device = Some("Zone".to_string());
+        assert!(history.query(1, &by_quantity).unwrap().is_empty());
+    }
+
+    #[test]
+    fn restarted_simulation_overwrites_samples() {
+        let history = recorded();
+
+        history.record(1, 0, &[Sample::new("Battery", "electricity", "W", 50.0)]).unwrap();
+
+        let series = history.list_series(1).unwrap();
+        assert_eq!(series[0].samples, 3);
+        let mut electricity = query(None, Some(900), None);
+        electricity.quantity = Some("electricity".to_string());
+        let series = history.query(1, &electricity).unwrap();
+        assert_eq!(buckets(&series[0]), [(0, 50.0, 50.0, 50.0, 1)]);
+    }
+}
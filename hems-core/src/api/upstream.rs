@@ -0,0 +1,292 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Timeouts, retries and circuit breaker settings for the calls to an upstream service
+#[derive(Clone, Debug)]
+pub struct Policy {
+    /// Timeout of a single call
+    pub timeout: Duration,
+    /// Timeout of calls known to be slow, e.g. loading a DEMKit simulation
+    pub slow_timeout: Duration,
+    /// Retries of idempotent calls failing to reach the service
+    pub retries: u32,
+    /// Delay before the first retry, doubled for every further retry
+    pub backoff: Duration,
+    /// Consecutive failed calls opening the circuit breaker
+    pub breaker_threshold: u32,
+    /// Time the circuit breaker stays open before letting a call through again
+    pub breaker_cooldown: Duration,
+}
+
+impl Policy {
+    /// Client for the service, the per-call timeouts are set on every request
+    pub fn client(&self) -> reqwest::Client {
+        reqwest::Client::builder()
+            .connect_timeout(self.timeout)
+            .build()
+            .unwrap_or_default()
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum UpstreamError {
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    #[error("{0} is failing, calls are suspended for {1}s")]
+    CircuitOpen(String, u64),
+}
+
+#[derive(Serialize, ToSchema, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Calls go through
+    Closed,
+    /// Calls fail immediately
+    Open,
+    /// The cooldown passed, the next call decides whether the circuit closes again
+    HalfOpen,
+}
+
+/// Stops calling a service after repeated failures, so callers fail fast instead of waiting
+/// for timeouts
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct BreakerStatus {
+    pub state: CircuitState,
+    /// Failed calls since the last successful one
+    pub consecutive_failures: u32,
+    /// Seconds until calls are let through again, while the circuit is open
+    #[schema(nullable)]
+    pub retry_in: Option<u64>,
+}
+
+impl CircuitBreaker {
+    pub fn new(policy: &Policy) -> Self {
+        Self {
+            threshold: policy.breaker_threshold,
+            cooldown: policy.breaker_cooldown,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    pub fn status(&self) -> BreakerStatus {
+        let state = self.state.lock().unwrap();
+
+        match state.opened_at {
+            Some(opened_at) if opened_at.elapsed() < self.cooldown => BreakerStatus {
+                state: CircuitState::Open,
+                consecutive_failures: state.consecutive_failures,
+                retry_in: Some((self.cooldown - opened_at.elapsed()).as_millis().div_ceil(1000) as u64),
+            },
+            Some(_) => BreakerStatus {
+                state: CircuitState::HalfOpen,
+                consecutive_failures: state.consecutive_failures,
+                retry_in: None,
+            },
+            None => BreakerStatus {
+                state: CircuitState::Closed,
+                consecutive_failures: state.consecutive_failures,
+                retry_in: None,
+            },
+        }
+    }
+
+    fn check(&self, service: &str) -> Result<(), UpstreamError> {
+        match self.status() {
+            BreakerStatus { state: CircuitState::Open, retry_in, .. } => Err(UpstreamError::CircuitOpen(
+                service.to_string(),
+                retry_in.unwrap_or_default(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn record_success(&self) {
+        *self.state.lock().unwrap() = BreakerState::default();
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        state.consecutive_failures += 1;
+        if state.consecutive_failures >= self.threshold {
+            // a failing half-open call reopens the circuit for another cooldown
+            state.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+/// How a call is sent to an upstream service
+#[derive(Clone, Copy, Debug)]
+pub enum Call {
+    /// Repeating the call has no further effect, it is retried when the service is unreachable
+    Idempotent,
+    /// Sent once, it may have been applied even if no response arrived
+    Once,
+    /// Sent once with the slow timeout
+    Slow,
+}
+
+/// Whether the service could not be reached or did not respond in time
+fn is_transport_error(error: &reqwest::Error) -> bool {
+    error.is_connect() || error.is_timeout() || error.is_request()
+}
+
+/// Sends a request guarded by the circuit breaker.
+///
+/// Only failures to reach the service count against the breaker, error responses show the
+/// service is up. Idempotent calls are retried on such failures with exponential backoff.
+pub async fn send(
+    service: &str,
+    policy: &Policy,
+    breaker: &CircuitBreaker,
+    request: reqwest::RequestBuilder,
+    call: Call,
+) -> Result<reqwest::Response, UpstreamError> {
+    let (request, retries) = match call {
+        Call::Idempotent => (request.timeout(policy.timeout), policy.retries),
+        Call::Once => (request.timeout(policy.timeout), 0),
+        Call::Slow => (request.timeout(policy.slow_timeout), 0),
+    };
+    let mut attempt = 0;
+
+    loop {
+        breaker.check(service)?;
+
+        // requests with a streamed body cannot be cloned and are sent once
+        let current = match request.try_clone() {
+            Some(current) if attempt < retries => current,
+            _ => return send_once(breaker, request).await,
+        };
+
+        match send_once(breaker, current).await {
+            Err(UpstreamError::Request(e)) if is_transport_error(&e) => {
+                let delay = policy.backoff * 2u32.saturating_pow(attempt);
+                log::warn!("Request to {service} failed, retrying in {}ms: {e}", delay.as_millis());
+
+                actix_web::rt::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+async fn send_once(
+    breaker: &CircuitBreaker,
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::Response, UpstreamError> {
+    match request.send().await {
+        Ok(response) => {
+            breaker.record_success();
+            Ok(response)
+        }
+        Err(e) => {
+            if is_transport_error(&e) {
+                breaker.record_failure();
+            }
+            Err(UpstreamError::Request(e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(breaker_threshold: u32, breaker_cooldown: Duration) -> Policy {
+        Policy {
+            timeout: Duration::from_millis(500),
+            slow_timeout: Duration::from_millis(500),
+            retries: 2,
+            backoff: Duration::from_millis(1),
+            breaker_threshold,
+            breaker_cooldown,
+        }
+    }
+
+    /// Request to a local port nothing listens on
+    fn unreachable(policy: &Policy) -> reqwest::RequestBuilder {
+        policy.client().get("http://127.0.0.1:9/")
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(&policy(3, Duration::from_secs(60)));
+
+        breaker.record_failure();
+        breaker.record_failure();
+        assert_eq!(breaker.status().state, CircuitState::Closed);
+        assert!(breaker.check("DEMKit").is_ok());
+
+        breaker.record_failure();
+        let status = breaker.status();
+        assert_eq!(status.state, CircuitState::Open);
+        assert_eq!(status.consecutive_failures, 3);
+        assert_eq!(status.retry_in, Some(60));
+        assert!(matches!(breaker.check("DEMKit"), Err(UpstreamError::CircuitOpen(service, 60)) if service == "DEMKit"));
+    }
+
+    #[test]
+    fn success_closes_the_circuit() {
+        let breaker = CircuitBreaker::new(&policy(2, Duration::from_secs(60)));
+
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        assert_eq!(breaker.status().state, CircuitState::Closed);
+        assert_eq!(breaker.status().consecutive_failures, 1);
+    }
+
+    #[test]
+    fn half_opens_after_the_cooldown() {
+        let breaker = CircuitBreaker::new(&policy(1, Duration::ZERO));
+
+        breaker.record_failure();
+        assert_eq!(breaker.status().state, CircuitState::HalfOpen);
+        assert!(breaker.check("DEMKit").is_ok());
+
+        breaker.record_success();
+        assert_eq!(breaker.status().state, CircuitState::Closed);
+    }
+
+    #[actix_web::test]
+    async fn idempotent_calls_are_retried() {
+        let policy = policy(10, Duration::from_secs(60));
+        let breaker = CircuitBreaker::new(&policy);
+
+        let result = send("DEMKit", &policy, &breaker, unreachable(&policy), Call::Idempotent).await;
+        assert!(matches!(result, Err(UpstreamError::Request(_))));
+        assert_eq!(breaker.status().consecutive_failures, 3);
+
+        let result = send("DEMKit", &policy, &breaker, unreachable(&policy), Call::Once).await;
+        assert!(matches!(result, Err(UpstreamError::Request(_))));
+        assert_eq!(breaker.status().consecutive_failures, 4);
+    }
+
+    #[actix_web::test]
+    async fn open_circuit_stops_retries() {
+        let policy = policy(1, Duration::from_secs(60));
+        let breaker = CircuitBreaker::new(&policy);
+
+        let result = send("DEMKit", &policy, &breaker, unreachable(&policy), Call::Idempotent).await;
+        assert!(matches!(result, Err(UpstreamError::CircuitOpen(..))));
+        assert_eq!(breaker.status().consecutive_failures, 1);
+    }
+}
@@ -0,0 +1,506 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use crate::api::upstream::Policy;
+
+/// Config file read when `HEMS_CONFIG` is not set, it is optional
+const DEFAULT_PATH: &str = "./hems-core.toml";
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("Invalid config file {0}: {1}")]
+    Toml(PathBuf, toml::de::Error),
+    #[error("Invalid configuration: {0}")]
+    Invalid(String),
+}
+
+/// Configuration of hems-core.
+///
+/// Read from the TOML file in `HEMS_CONFIG` (default `./hems-core.toml`), then overridden by
+/// environment variables. See `hems-core.example.toml` for every setting.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub openapi: OpenApiConfig,
+    pub demkit: DemkitConfig,
+    pub home_assistant: HomeAssistantConfig,
+    pub history: HistoryConfig,
+    pub houses: HousesConfig,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// `host:port` to listen on
+    pub bind: String,
+    /// HTTP workers, one per CPU core when not set
+    pub workers: Option<usize>,
+    pub cors: CorsConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: "0.0.0.0:8080".to_string(),
+            workers: None,
+            cors: CorsConfig::default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins allowed to call the API from a browser, `*` allows any; CORS is off when empty
+    pub allowed_origins: Vec<String>,
+    /// Seconds browsers may cache preflight responses
+    pub max_age: Option<usize>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct OpenApiConfig {
+    /// Whether to write the OpenAPI document at startup
+    pub emit: bool,
+    pub path: PathBuf,
+}
+
+impl Default for OpenApiConfig {
+    fn default() -> Self {
+        Self {
+            emit: true,
+            path: PathBuf::from("./api-docs/api-doc.json"),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct DemkitConfig {
+    /// DEMKit instance of houses without a dedicated one, `memory://` for the in-memory fake
+    pub url: Option<String>,
+    /// Dedicated DEMKit instances by house ID
+    pub houses: HashMap<String, String>,
+    pub upstream: UpstreamConfig,
+}
+
+impl DemkitConfig {
+    /// Dedicated DEMKit instances, validated by [`Config::load`]
+    pub fn house_urls(&self) -> Vec<(u32, &str)> {
+        self.houses
+            .iter()
+            .filter_map(|(house_id, url)| Some((house_id.parse().ok()?, url.as_str())))
+            .collect()
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct HomeAssistantConfig {
+    /// Home Assistant instance, `memory://` for the in-memory fake
+    pub url: Option<String>,
+    /// Long-lived access token
+    pub token: Option<String>,
+    /// File holding the token, e.g. a docker secret
+    pub token_file: Option<PathBuf>,
+    /// JSON file with canned states for the in-memory fake
+    pub fake_states: Option<PathBuf>,
+    pub upstream: UpstreamConfig,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct HousesConfig {
+    /// Directory with the named house specs, `{name}.toml` or `{name}.json`
+    pub spec_dir: PathBuf,
+}
+
+impl Default for HousesConfig {
+    fn default() -> Self {
+        Self {
+            spec_dir: PathBuf::from("./houses"),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// SQLite database holding the samples, `:memory:` keeps them until hems-core stops
+    pub path: PathBuf,
+    /// Simulation seconds between two samples of a house
+    pub interval: u64,
+    /// Milliseconds between two checks of the simulation time of every house
+    pub poll_ms: u64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("./history.db"),
+            interval: 900,
+            poll_ms: 1_000,
+        }
+    }
+}
+
+/// Timeouts, retries and circuit breaker of an upstream service, see [`Policy`]
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpstreamConfig {
+    pub timeout_ms: u64,
+    pub slow_timeout_ms: u64,
+    pub retries: u32,
+    pub retry_backoff_ms: u64,
+    pub breaker_threshold: u32,
+    pub breaker_cooldown_ms: u64,
+}
+
+impl Default for UpstreamConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 5_000,
+            slow_timeout_ms: 60_000,
+            retries: 2,
+            retry_backoff_ms: 100,
+            breaker_threshold: 5,
+            breaker_cooldown_ms: 30_000,
+        }
+    }
+}
+
+impl UpstreamConfig {
+    pub fn policy(&self) -> Policy {
+        Policy {
+            timeout: Duration::from_millis(self.timeout_ms),
+            slow_timeout: Duration::from_millis(self.slow_timeout_ms),
+            retries: self.retries,
+            backoff: Duration::from_millis(self.retry_backoff_ms),
+            breaker_threshold: self.breaker_threshold,
+            breaker_cooldown: Duration::from_millis(self.breaker_cooldown_ms),
+        }
+    }
+
+    /// Overrides from `{prefix}_TIMEOUT_MS`, `{prefix}_RETRIES`, ...
+    fn apply_env(&mut self, prefix: &str) -> Result<(), ConfigError> {
+        env_parse(&format!("{prefix}_TIMEOUT_MS"), &mut self.timeout_ms)?;
+        env_parse(&format!("{prefix}_SLOW_TIMEOUT_MS"), &mut self.slow_timeout_ms)?;
+        env_parse(&format!("{prefix}_RETRIES"), &mut self.retries)?;
+        env_parse(&format!("{prefix}_RETRY_BACKOFF_MS"), &mut self.retry_backoff_ms)?;
+        env_parse(&format!("{prefix}_BREAKER_THRESHOLD"), &mut self.breaker_threshold)?;
+        env_parse(&format!("{prefix}_BREAKER_COOLDOWN_MS"), &mut self.breaker_cooldown_ms)?;
+
+        Ok(())
+    }
+
+    fn validate(&self, section: &str) -> Result<(), ConfigError> {
+        if self.timeout_ms == 0 || self.slow_timeout_ms == 0 {
+            return Err(ConfigError::Invalid(format!("{section}.upstream timeouts must be positive")));
+        }
+
+        if self.breaker_threshold == 0 {
+            return Err(ConfigError::Invalid(format!(
+                "{section}.upstream.breaker_threshold must be at least 1"
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+fn env_var(key: &str) -> Option<String> {
+    std::env::var(key).ok().filter(|value| !value.trim().is_empty())
+}
+
+fn env_parse<T: FromStr>(key: &str, target: &mut T) -> Result<(), ConfigError> {
+    if let Some(value) = env_var(key) {
+        *target = value
+            .trim()
+            .parse()
+            .map_err(|_| ConfigError::Invalid(format!("{key} must be a number, got '{value}'")))?;
+    }
+
+    Ok(())
+}
+
+fn validate_url(key: &str, url: &str) -> Result<(), ConfigError> {
+    if ["http://", "https://", "memory://"].iter().any(|scheme| url.starts_with(scheme)) {
+        Ok(())
+    } else {
+        Err(ConfigError::Invalid(format!(
+            "{key} must be an http://, https:// or memory:// URL, got '{url}'"
+        )))
+    }
+}
+
+impl Config {
+    /// Reads the config file and environment overrides, failing on invalid or missing settings
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut config = match env_var("HEMS_CONFIG") {
+            Some(path) => Self::read(Path::new(&path))?,
+            None if Path::new(DEFAULT_PATH).exists() => Self::read(Path::new(DEFAULT_PATH))?,
+            None => Self::default(),
+        };
+
+        config.apply_env()?;
+        config.resolve_token()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn read(path: &Path) -> Result<Self, ConfigError> {
+        let input = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+
+        toml::from_str(&input).map_err(|e| ConfigError::Toml(path.to_path_buf(), e))
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        if let Some(bind) = env_var("HEMS_BIND") {
+            self.server.bind = bind;
+        }
+        if env_var("HEMS_WORKERS").is_some() {
+            let mut workers = 0;
+            env_parse("HEMS_WORKERS", &mut workers)?;
+            self.server.workers = Some(workers);
+        }
+        if let Some(origins) = env_var("HEMS_CORS_ORIGINS") {
+            self.server.cors.allowed_origins = origins
+                .split(',')
+                .map(|origin| origin.trim().to_string())
+                .filter(|origin| !origin.is_empty())
+                .collect();
+        }
+        if let Some(emit) = env_var("HEMS_OPENAPI_EMIT") {
+            self.openapi.emit = emit.trim().parse().map_err(|_| {
+                ConfigError::Invalid(format!("HEMS_OPENAPI_EMIT must be true or false, got '{emit}'"))
+            })?;
+        }
+        if let Some(path) = env_var("HEMS_OPENAPI_PATH") {
+            self.openapi.path = path.into();
+        }
+
+        if let Some(url) = env_var("DEMKIT_URL") {
+            self.demkit.url = Some(url);
+        }
+        // DEMKIT_HOUSE_URLS="1=http://demkit-1:5000,2=http://demkit-2:5000"
+        if let Some(urls) = env_var("DEMKIT_HOUSE_URLS") {
+            for entry in urls.split(',').filter(|entry| !entry.trim().is_empty()) {
+                let (house_id, url) = entry.split_once('=').ok_or_else(|| {
+                    ConfigError::Invalid(format!("DEMKIT_HOUSE_URLS entries must be id=url, got '{entry}'"))
+                })?;
+                self.demkit
+                    .houses
+                    .insert(house_id.trim().to_string(), url.trim().to_string());
+            }
+        }
+        self.demkit.upstream.apply_env("DEMKIT")?;
+
+        if let Some(url) = env_var("HA_URL") {
+            self.home_assistant.url = Some(url);
+        }
+        if let Some(token) = env_var("HA_TOKEN") {
+            self.home_assistant.token = Some(token);
+            self.home_assistant.token_file = None;
+        }
+        if let Some(path) = env_var("HA_TOKEN_FILE") {
+            self.home_assistant.token_file = Some(path.into());
+        }
+        if let Some(path) = env_var("HA_FAKE_STATES") {
+            self.home_assistant.fake_states = Some(path.into());
+        }
+        self.home_assistant.upstream.apply_env("HA")?;
+
+        if let Some(dir) = env_var("HOUSE_SPEC_DIR") {
+            self.houses.spec_dir = dir.into();
+        }
+
+        if let Some(path) = env_var("HISTORY_PATH") {
+            self.history.path = path.into();
+        }
+        env_parse("HISTORY_INTERVAL", &mut self.history.interval)?;
+        env_parse("HISTORY_POLL_MS", &mut self.history.poll_ms)?;
+
+        Ok(())
+    }
+
+    /// Reads the Home Assistant token from `token_file`, which wins over an inline token
+    fn resolve_token(&mut self) -> Result<(), ConfigError> {
+        if let Some(path) = &self.home_assistant.token_file {
+            let token = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.clone(), e))?;
+            self.home_assistant.token = Some(token.trim().to_string());
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        match self.server.bind.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {}
+            _ => {
+                return Err(ConfigError::Invalid(format!(
+                    "server.bind must be host:port, got '{}'",
+                    self.server.bind
+                )))
+            }
+        }
+
+        if self.server.workers == Some(0) {
+            return Err(ConfigError::Invalid("server.workers must be at least 1".to_string()));
+        }
+
+        let origins = &self.server.cors.allowed_origins;
+        if origins.len() > 1 && origins.iter().any(|origin| origin == "*") {
+            return Err(ConfigError::Invalid(
+                "server.cors.allowed_origins cannot mix '*' with other origins".to_string(),
+            ));
+        }
+
+        match &self.demkit.url {
+            Some(url) => validate_url("demkit.url", url)?,
+            None => {
+                return Err(ConfigError::Invalid(
+                    "demkit.url is not set, set it in the config file or DEMKIT_URL".to_string(),
+                ))
+            }
+        }
+
+        for (house_id, url) in &self.demkit.houses {
+            if house_id.parse::<u32>().is_err() {
+                return Err(ConfigError::Invalid(format!(
+                    "demkit.houses keys must be house IDs, got '{house_id}'"
+                )));
+            }
+            validate_url(&format!("demkit.houses.{house_id}"), url)?;
+        }
+
+        self.demkit.upstream.validate("demkit")?;
+
+        match &self.home_assistant.url {
+            Some(url) => validate_url("home_assistant.url", url)?,
+            None => {
+                return Err(ConfigError::Invalid(
+                    "home_assistant.url is not set, set it in the config file or HA_URL".to_string(),
+                ))
+            }
+        }
+
+        let is_fake = self
+            .home_assistant
+            .url
+            .as_deref()
+            .is_some_and(|url| url.starts_with("memory://"));
+        let has_token = self
+            .home_assistant
+            .token
+            .as_deref()
+            .is_some_and(|token| !token.trim().is_empty());
+
+        if !is_fake && !has_token {
+            return Err(ConfigError::Invalid(
+                "home_assistant.token is not set, set token or token_file in the config file, HA_TOKEN or HA_TOKEN_FILE"
+                    .to_string(),
+            ));
+        }
+
+        self.home_assistant.upstream.validate("home_assistant")?;
+
+        if self.history.interval == 0 || self.history.poll_ms == 0 {
+            return Err(ConfigError::Invalid(
+                "history.interval and history.poll_ms must be positive".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Environment variables are shared by all tests, so the ones setting them take turns
+    static ENV: Mutex<()> = Mutex::new(());
+
+    /// Applies the environment overrides to the defaults, with the given variables set
+    fn with_env(vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let _guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+
+        for (key, value) in vars {
+            std::env::set_var(key, value);
+        }
+        let mut config = Config::default();
+        let result = config.apply_env().map(|_| config);
+        for (key, _) in vars {
+            std::env::remove_var(key);
+        }
+
+        result
+    }
+
+    #[test]
+    fn env_overrides_the_config() {
+        let config = with_env(&[
+            ("HEMS_BIND", "127.0.0.1:9000"),
+            ("HEMS_CORS_ORIGINS", "http://a.test, ,http://b.test"),
+            ("DEMKIT_URL", "memory://demkit"),
+            ("DEMKIT_HOUSE_URLS", "1=http://demkit-1:5000, 2=http://demkit-2:5000"),
+            ("DEMKIT_RETRIES", " 7 "),
+            ("HA_URL", "memory://ha"),
+            ("HA_BREAKER_COOLDOWN_MS", "1500"),
+            ("HISTORY_INTERVAL", "60"),
+        ])
+        .unwrap();
+
+        assert_eq!(config.server.bind, "127.0.0.1:9000");
+        assert_eq!(config.server.cors.allowed_origins, ["http://a.test", "http://b.test"]);
+        assert_eq!(config.demkit.url.as_deref(), Some("memory://demkit"));
+        let mut house_urls = config.demkit.house_urls();
+        house_urls.sort();
+        assert_eq!(house_urls, [(1, "http://demkit-1:5000"), (2, "http://demkit-2:5000")]);
+        assert_eq!(config.demkit.upstream.retries, 7);
+        assert_eq!(config.home_assistant.upstream.breaker_cooldown_ms, 1500);
+        assert_eq!(config.home_assistant.upstream.retries, UpstreamConfig::default().retries);
+        assert_eq!(config.history.interval, 60);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn blank_env_is_ignored() {
+        let config = with_env(&[("HEMS_BIND", "  "), ("DEMKIT_TIMEOUT_MS", "")]).unwrap();
+
+        assert_eq!(config.server.bind, ServerConfig::default().bind);
+        assert_eq!(config.demkit.upstream.timeout_ms, UpstreamConfig::default().timeout_ms);
+    }
+
+    #[test]
+    fn invalid_env_is_rejected() {
+        let error = with_env(&[("DEMKIT_TIMEOUT_MS", "soon")]).unwrap_err();
+        assert!(error.to_string().contains("DEMKIT_TIMEOUT_MS must be a number, got 'soon'"), "{error}");
+
+        let error = with_env(&[("DEMKIT_HOUSE_URLS", "http://demkit-1:5000")]).unwrap_err();
+        assert!(error.to_string().contains("entries must be id=url"), "{error}");
+
+        let error = with_env(&[("HEMS_OPENAPI_EMIT", "yes")]).unwrap_err();
+        assert!(error.to_string().contains("must be true or false"), "{error}");
+    }
+
+    #[test]
+    fn token_is_required_outside_the_fake() {
+        let config = with_env(&[("DEMKIT_URL", "memory://demkit"), ("HA_URL", "http://ha.test:8123")]).unwrap();
+
+        let error = config.validate().unwrap_err();
+        assert!(error.to_string().contains("home_assistant.token is not set"), "{error}");
+    }
+}
@@ -1,40 +1,130 @@
-use actix_web::middleware::Logger;
-use actix_web::{App, HttpServer};
+use std::sync::Once;
+use std::time::Duration;
+
+use actix_cors::Cors;
+use actix_web::middleware::{Condition, Logger};
+use actix_web::{web, App, HttpServer};
 use dotenv::dotenv;
 use env_logger::Env;
 use utoipa_actix_web::AppExt;
 use utoipa_swagger_ui::SwaggerUi;
 
 mod api;
+mod config;
 mod resources;
 
+use api::demkit::registry::Registry;
+use api::ha::backend::HomeAssistantBackend;
+use config::{Config, CorsConfig, OpenApiConfig};
 use resources::house;
 
+/// Every worker builds the app, the OpenAPI document is written by the first one
+static EMIT_OPENAPI: Once = Once::new();
+
+fn cors(config: &CorsConfig) -> Cors {
+    let mut cors = Cors::default().allow_any_method().allow_any_header().max_age(config.max_age);
+
+    for origin in &config.allowed_origins {
+        cors = match origin.as_str() {
+            "*" => cors.allow_any_origin(),
+            origin => cors.allowed_origin(origin),
+        };
+    }
+
+    cors
+}
+
+fn emit_openapi(config: &OpenApiConfig, api: &utoipa::openapi::OpenApi) {
+    let result = api
+        .to_pretty_json()
+        .map_err(std::io::Error::other)
+        .and_then(|api_doc| {
+            if let Some(dir) = config.path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(&config.path, api_doc)
+        });
+
+    if let Err(e) = result {
+        log::warn!("Failed to write the OpenAPI document to {}: {}", config.path.display(), e);
+    }
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
 
     env_logger::init_from_env(Env::default().default_filter_or("info"));
 
-    HttpServer::new(move || {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            log::error!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    let registry = web::Data::new(Registry::from_config(&config.demkit));
+    actix_web::rt::spawn(api::demkit::heating_schedule::run(registry.clone().into_inner()));
+
+    let ha = api::ha::from_config(&config.home_assistant);
+    match ha.validate().await {
+        Ok(_) => {}
+        Err(e @ api::ha::ApiError::Unauthorized(_)) => return Err(std::io::Error::other(e)),
+        Err(e) => log::warn!("Could not validate the Home Assistant token, Home Assistant is unreachable: {}", e),
+    }
+    let ha: web::Data<dyn HomeAssistantBackend> = web::Data::from(ha);
+
+    let history = match api::history::History::open(&config.history) {
+        Ok(history) => web::Data::new(history),
+        Err(e) => {
+            log::error!("Failed to open the history at {}: {}", config.history.path.display(), e);
+            std::process::exit(1);
+        }
+    };
+    actix_web::rt::spawn(api::history::run(
+        registry.clone().into_inner(),
+        ha.clone().into_inner(),
+        history.clone().into_inner(),
+        Duration::from_millis(config.history.poll_ms),
+    ));
+
+    let houses = web::Data::new(config.houses.clone());
+
+    let server_config = config.clone();
+    let mut server = HttpServer::new(move || {
+        let cors_config = &server_config.server.cors;
+
         let (app, api) = App::new()
+            .app_data(registry.clone())
+            .app_data(ha.clone())
+            .app_data(history.clone())
+            .app_data(houses.clone())
             .into_utoipa_app()
             .openapi(api::docs::get_openapi())
-            .map(|app| app.wrap(Logger::default()))
-            .service(api::health)
+            .map(|app| {
+                app.wrap(Condition::new(!cors_config.allowed_origins.is_empty(), cors(cors_config)))
+                    .wrap(Logger::default())
+            })
+            .service(api::health::health)
+            .service(api::health::ready)
+            .service(api::debug::home_assistant_calls)
             .configure(house::configure)
             .openapi_service(|api| {
                 SwaggerUi::new("/swagger-ui/{_:.*}").url("/api-docs/openapi.json", api)
             })
             .split_for_parts();
 
-        let api_doc = api.to_pretty_json().unwrap();
-        std::fs::create_dir_all("./api-docs").unwrap();
-        std::fs::write("./api-docs/api-doc.json", api_doc).unwrap();
+        if server_config.openapi.emit {
+            EMIT_OPENAPI.call_once(|| emit_openapi(&server_config.openapi, &api));
+        }
 
         app
-    })
-    .bind(("0.0.0.0", 8080))?
-    .run()
-    .await
+    });
+
+    if let Some(workers) = config.server.workers {
+        server = server.workers(workers);
+    }
+
+    server.bind(config.server.bind.as_str())?.run().await
 }
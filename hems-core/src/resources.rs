@@ -1 +1,3 @@
-pub mod house;
\ No newline at end of file
+pub mod history;
+pub mod house;
+pub mod spec;
\ No newline at end of file
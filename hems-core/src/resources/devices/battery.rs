@@ -1,9 +1,15 @@
-use actix_web::{delete, get, post, web, HttpResponse, Responder};
-use serde::Serialize;
+use actix_web::{delete, get, post, web, HttpResponse, Responder, ResponseError};
+use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use utoipa_actix_web::scope;
 
-use crate::api::demkit::{self, battery::BatteryProperties, env::BatteryEntityParams};
+use crate::api::demkit::{
+    self,
+    battery::{BatteryProperties, PowerSchedule},
+    env::BatteryEntityParams,
+    registry::Registry,
+};
+use crate::api::error::{ErrorBody, UpstreamErrors};
 
 pub fn configure(cfg: &mut utoipa_actix_web::service_config::ServiceConfig) {
     cfg.service(
@@ -12,7 +18,11 @@ pub fn configure(cfg: &mut utoipa_actix_web::service_config::ServiceConfig) {
             .service(add_by_id)
             .service(remove_by_id)
             .service(set_target_soc)
-            .service(set_target_soc_none),
+            .service(set_target_soc_none)
+            .service(set_power_setpoint)
+            .service(clear_power_setpoint)
+            .service(set_schedule)
+            .service(clear_schedule),
     );
 }
 
@@ -30,6 +40,16 @@ enum BatteryStatus {
 struct BatteryInfo {
     /// Current battery capacity in Wh
     capacity: f64,
+    /// Energy stored when the simulation started in Wh
+    initial_soc: f64,
+    /// Power levels the battery charges (positive) and discharges (negative) at in W
+    charging_powers: Vec<f64>,
+    /// Efficiency of each charging power
+    charging_efficiency: Vec<f64>,
+    /// Standby losses in W
+    self_consumption: f64,
+    /// Whether the battery only runs at its charging powers
+    discrete: bool,
     /// Maximum charging power in W
     max_charge: f64,
     /// Maximum discharging power in W
@@ -43,11 +63,33 @@ struct BatteryInfo {
     status: BatteryStatus,
     /// Current electricity consumption in W
     consumption: f64,
+    /// Power the battery is held at in W, positive when charging
+    #[schema(nullable)]
+    power_setpoint: Option<f64>,
+    /// Power schedule the battery follows
+    schedule: Vec<ScheduleEntry>,
+    /// Unix time the schedule ends, control returns to DEMKit afterwards
+    #[schema(nullable)]
+    schedule_end: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ScheduleEntry {
+    /// Unix time the power takes effect
+    time: u64,
+    /// Power in W, positive when charging
+    power: f64,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct PowerSetpoint {
+    /// Power in W, positive when charging, negative when discharging
+    power: f64,
 }
 
 impl From<BatteryProperties> for BatteryInfo {
     fn from(bp: BatteryProperties) -> Self {
-        let elec = bp.electricity_consumption.unwrap();
+        let elec = bp.electricity_consumption;
         let current_consumption = elec.norm() * elec.re.signum();
 
         let battery_status = if current_consumption > 1e2 {
@@ -62,10 +104,22 @@ impl From<BatteryProperties> for BatteryInfo {
             capacity: bp.capacity,
             max_charge: *bp.charging_powers.last().unwrap_or(&0.0),
             max_discharge: -*bp.charging_powers.first().unwrap_or(&0.0),
+            initial_soc: bp.initial_soc,
+            self_consumption: bp.self_consumption,
+            discrete: bp.discrete,
             state_of_charge: bp.soc,
             target_soc: bp.target_soc,
             status: battery_status,
-            consumption: bp.electricity_consumption.unwrap().norm(),
+            consumption: bp.electricity_consumption.norm(),
+            power_setpoint: bp.power_setpoint,
+            schedule: bp
+                .schedule
+                .iter()
+                .map(|(time, power)| ScheduleEntry { time: *time, power: *power })
+                .collect(),
+            schedule_end: bp.schedule_end,
+            charging_powers: bp.charging_powers,
+            charging_efficiency: bp.charging_efficiency,
         };
 
         battery_info
@@ -79,7 +133,8 @@ impl From<BatteryProperties> for BatteryInfo {
     responses(
         (status = 200, description = "Get battery properties", body = BatteryInfo),
         (status = 400, description = "Invalid battery ID"),
-        (status = 500, description = "Internal server error"),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
@@ -87,12 +142,13 @@ impl From<BatteryProperties> for BatteryInfo {
     )
 )]
 #[get("")]
-async fn get_by_id(id: web::Path<(u32, u32)>) -> impl Responder {
+async fn get_by_id(registry: web::Data<Registry>, id: web::Path<(u32, u32)>) -> impl Responder {
     let (house_id, _battery_id) = id.into_inner();
+    let backend = registry.backend(house_id);
 
-    let bp = match demkit::battery::get_battery_properties(house_id).await {
+    let bp = match demkit::battery::get_battery_properties(backend.as_ref(), house_id).await {
         Ok(properties) => properties,
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {:?}", e)),
+        Err(e) => return e.error_response(),
     };
 
     let battery_info = BatteryInfo::from(bp);
@@ -103,26 +159,35 @@ async fn get_by_id(id: web::Path<(u32, u32)>) -> impl Responder {
 #[utoipa::path(
     post,
     tag = "Battery",
-    description = "Add a new battery entity.",
+    description = "Add a new battery entity. Parameters that are not given are taken from DEMKit's \
+        battery settings for the house.",
     responses(
         (status = 200, description = "Battery added successfully"),
-        (status = 500, description = "Error adding battery"),
+        (status = 400, description = "Entity rejected by DEMKit", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
         ("entity_name" = String, description = "Name of the battery entity"),
-    )
+    ),
+    request_body = BatteryEntityParams,
 )]
 #[post("")]
 async fn add_by_id(
+    registry: web::Data<Registry>,
     id: web::Path<(u32, String)>,
-    _params: web::Json<BatteryEntityParams>,
+    params: web::Json<BatteryEntityParams>,
 ) -> impl Responder {
     let (house_id, entity_name) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
 
-    match demkit::env::add_battery(house_id).await {
+    match demkit::env::add_battery(backend.as_ref(), house_id, params.into_inner()).await {
         Ok(_) => HttpResponse::Ok().body(format!("{entity_name} added successfully")),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {:?}", e)),
+        Err(e) => return e.error_response(),
     };
 
     HttpResponse::Ok().body(format!("{entity_name} added successfully"))
@@ -134,20 +199,26 @@ async fn add_by_id(
     description = "Remove a battery entity from the house.",
     responses(
         (status = 200, description = "Battery removed successfully"),
-        (status = 500, description = "Error removing battery"),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
-        ("entity_name" = String, description = "Name of the battery entity"),
+        ("battery_id" = u32, description = "Battery ID"),
     )
 )]
 #[delete("")]
-async fn remove_by_id(id: web::Path<(u32, String)>) -> impl Responder {
-    let (house_id, entity_name) = id.into_inner();
+async fn remove_by_id(registry: web::Data<Registry>, id: web::Path<(u32, u32)>) -> impl Responder {
+    let (house_id, _battery_id) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
 
-    match demkit::env::remove_entity(house_id, entity_name.as_str()).await {
-        Ok(_) => HttpResponse::Ok().body(format!("{entity_name} removed successfully")),
-        Err(e) => HttpResponse::InternalServerError().body(format!("Error: {:?}", e)),
+    match demkit::env::remove_entity(backend.as_ref(), house_id, demkit::env::BATTERY_NAME).await {
+        Ok(_) => HttpResponse::Ok().body(format!("{} removed successfully", demkit::env::BATTERY_NAME)),
+        Err(e) => e.error_response(),
     }
 }
 
@@ -157,7 +228,9 @@ async fn remove_by_id(id: web::Path<(u32, String)>) -> impl Responder {
     description = "Set battery target SoC",
     responses(
         (status = 200, description = "Set target SOC", body = BatteryInfo),
-        (status = 500, description = "Error setting target SOC"),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
@@ -166,12 +239,19 @@ async fn remove_by_id(id: web::Path<(u32, String)>) -> impl Responder {
     )
 )]
 #[get("/target/{soc}")]
-async fn set_target_soc(id: web::Path<(u32, u32, u32)>) -> impl Responder {
+async fn set_target_soc(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, u32, u32)>,
+) -> impl Responder {
     let (house_id, _battery_id, target_soc) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
 
-    let bp = match demkit::battery::set_target_soc(house_id, Some(target_soc)).await {
+    let bp = match demkit::battery::set_target_soc(backend.as_ref(), house_id, Some(target_soc)).await {
         Ok(properties) => properties,
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {:?}", e)),
+        Err(e) => return e.error_response(),
     };
 
     let battery_info = BatteryInfo::from(bp);
@@ -185,7 +265,9 @@ async fn set_target_soc(id: web::Path<(u32, u32, u32)>) -> impl Responder {
     description = "Unset target SoC",
     responses(
         (status = 200, description = "Unset target SOC", body = BatteryInfo),
-        (status = 500, description = "Error unsetting target SOC"),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
@@ -193,15 +275,160 @@ async fn set_target_soc(id: web::Path<(u32, u32, u32)>) -> impl Responder {
     )
 )]
 #[get("/target")]
-async fn set_target_soc_none(id: web::Path<(u32, u32)>) -> impl Responder {
+async fn set_target_soc_none(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, u32)>,
+) -> impl Responder {
     let (house_id, _battery_id) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
 
-    let bp = match demkit::battery::set_target_soc(house_id, None).await {
+    let bp = match demkit::battery::set_target_soc(backend.as_ref(), house_id, None).await {
         Ok(properties) => properties,
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {:?}", e)),
+        Err(e) => return e.error_response(),
     };
 
     let battery_info = BatteryInfo::from(bp);
 
     HttpResponse::Ok().json(battery_info)
 }
+
+#[utoipa::path(
+    post,
+    tag = "Battery",
+    description = "Hold the battery at a charge or discharge power until the setpoint is cleared. \
+        Discrete batteries use the nearest of their charging powers. Replaces a running schedule.",
+    responses(
+        (status = 200, description = "Power setpoint set", body = BatteryInfo),
+        (status = 400, description = "Power outside the charging powers", body = ErrorBody),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("battery_id" = u32, description = "Battery ID"),
+    ),
+    request_body = PowerSetpoint,
+)]
+#[post("/setpoint")]
+async fn set_power_setpoint(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, u32)>,
+    body: web::Json<PowerSetpoint>,
+) -> impl Responder {
+    let (house_id, _battery_id) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::battery::set_power_setpoint(backend.as_ref(), house_id, Some(body.power)).await {
+        Ok(properties) => HttpResponse::Ok().json(BatteryInfo::from(properties)),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    tag = "Battery",
+    description = "Clear the power setpoint and any schedule, returning control to DEMKit.",
+    responses(
+        (status = 200, description = "Power setpoint cleared", body = BatteryInfo),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("battery_id" = u32, description = "Battery ID"),
+    ),
+)]
+#[delete("/setpoint")]
+async fn clear_power_setpoint(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, u32)>,
+) -> impl Responder {
+    let (house_id, _battery_id) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::battery::set_power_setpoint(backend.as_ref(), house_id, None).await {
+        Ok(properties) => HttpResponse::Ok().json(BatteryInfo::from(properties)),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    tag = "Battery",
+    description = "Upload powers for consecutive intervals, which the battery follows as the \
+        simulation advances. Discrete batteries use the nearest of their charging powers. The \
+        state of charge must stay within the capacity, starting from the current one. Replaces \
+        the power setpoint and any previous schedule.",
+    responses(
+        (status = 200, description = "Schedule set", body = BatteryInfo),
+        (status = 400, description = "Invalid schedule", body = ErrorBody),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("battery_id" = u32, description = "Battery ID"),
+    ),
+    request_body = PowerSchedule,
+)]
+#[post("/schedule")]
+async fn set_schedule(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, u32)>,
+    body: web::Json<PowerSchedule>,
+) -> impl Responder {
+    let (house_id, _battery_id) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::battery::set_schedule(backend.as_ref(), house_id, body.into_inner()).await {
+        Ok(properties) => HttpResponse::Ok().json(BatteryInfo::from(properties)),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    tag = "Battery",
+    description = "Clear the schedule and any power setpoint, returning control to DEMKit.",
+    responses(
+        (status = 200, description = "Schedule cleared", body = BatteryInfo),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("battery_id" = u32, description = "Battery ID"),
+    ),
+)]
+#[delete("/schedule")]
+async fn clear_schedule(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, u32)>,
+) -> impl Responder {
+    let (house_id, _battery_id) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::battery::set_power_setpoint(backend.as_ref(), house_id, None).await {
+        Ok(properties) => HttpResponse::Ok().json(BatteryInfo::from(properties)),
+        Err(e) => e.error_response(),
+    }
+}
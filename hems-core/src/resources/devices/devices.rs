@@ -1,6 +1,12 @@
 pub mod battery;
+pub mod ev;
+pub mod heatpump;
+pub mod dhw;
 pub mod meter;
 pub mod solar;
 pub mod thermal;
 pub mod ha_entity;
 pub mod timeshifters;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,113 @@
+use actix_web::{get, post, web, HttpResponse, Responder, ResponseError};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use utoipa_actix_web::scope;
+
+use super::heatpump::HeatSourceInfo;
+use crate::api::demkit::{self, backend::DemkitBackend, registry::Registry, ApiError};
+use crate::api::error::{ErrorBody, UpstreamErrors};
+
+pub fn configure(cfg: &mut utoipa_actix_web::service_config::ServiceConfig) {
+    cfg.service(
+        scope::scope("/dhw")
+            .service(get_by_id)
+            .service(set_setpoint),
+    );
+}
+
+#[derive(Serialize, ToSchema)]
+struct DhwInfo {
+    /// Temperature of the hot water delivered to the taps in Celsius
+    supply_temperature: f64,
+    /// Hot water setpoint in Celsius
+    setpoint: f64,
+    /// Current hot water demand of the taps in W
+    hot_water_demand: f64,
+    /// Heat currently delivered to the taps in W
+    heat_supply: f64,
+    /// Device heating the hot water
+    heater: HeatSourceInfo,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct DhwSetpoint {
+    /// Hot water temperature in Celsius
+    #[schema(minimum = 35.0, maximum = 80.0)]
+    temperature: f64,
+}
+
+#[utoipa::path(
+    get,
+    tag = "Domestic hot water",
+    description = "Get the hot water demand of the house and the state of the device heating it.",
+    responses(
+        (status = 200, description = "Get domestic hot water properties", body = DhwInfo),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+    )
+)]
+#[get("")]
+async fn get_by_id(registry: web::Data<Registry>, id: web::Path<u32>) -> impl Responder {
+    let house_id = id.into_inner();
+    let backend = registry.backend(house_id);
+
+    match dhw_info(backend.as_ref(), house_id).await {
+        Ok(dhw_info) => HttpResponse::Ok().json(dhw_info),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    tag = "Domestic hot water",
+    description = "Set the hot water temperature. Without a dedicated hot water boiler this sets \
+        the supply temperature of the heat source heating the house.",
+    responses(
+        (status = 200, description = "Setpoint set", body = DhwInfo),
+        (status = 400, description = "Invalid temperature", body = ErrorBody),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+    ),
+    request_body = DhwSetpoint,
+)]
+#[post("/setpoint")]
+async fn set_setpoint(
+    registry: web::Data<Registry>,
+    id: web::Path<u32>,
+    body: web::Json<DhwSetpoint>,
+) -> impl Responder {
+    let house_id = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    if let Err(e) = demkit::dhw::set_setpoint(backend.as_ref(), house_id, body.temperature).await {
+        return e.error_response();
+    }
+
+    match dhw_info(backend.as_ref(), house_id).await {
+        Ok(dhw_info) => HttpResponse::Ok().json(dhw_info),
+        Err(e) => e.error_response(),
+    }
+}
+
+async fn dhw_info(backend: &dyn DemkitBackend, house_id: u32) -> Result<DhwInfo, ApiError> {
+    let dhw = demkit::dhw::get_dhw_properties(backend, house_id).await?;
+    let heater = demkit::dhw::get_dhw_heater(backend, house_id).await?;
+
+    Ok(DhwInfo {
+        supply_temperature: dhw.supply_temperature,
+        setpoint: heater.temperature,
+        hot_water_demand: dhw.heat_demand.re,
+        heat_supply: dhw.heat_supply,
+        heater: HeatSourceInfo::from(heater),
+    })
+}
@@ -0,0 +1,340 @@
+use actix_web::{delete, get, post, web, HttpResponse, Responder, ResponseError};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use utoipa_actix_web::scope;
+
+use crate::api::demkit::{
+    self,
+    env::EvEntityParams,
+    ev::{EvProperties, Session, StartSession},
+    registry::Registry,
+    Measurement,
+};
+use crate::api::error::{ErrorBody, UpstreamErrors};
+
+pub fn configure(cfg: &mut utoipa_actix_web::service_config::ServiceConfig) {
+    cfg.service(
+        scope::scope("/ev/{entity_name}")
+            .service(get_by_id)
+            .service(add_by_id)
+            .service(remove_by_id)
+            .service(start_session)
+            .service(end_session)
+            .service(set_departure)
+            .service(set_charge_limit),
+    );
+}
+
+#[derive(Serialize, ToSchema)]
+enum ChargingStatus {
+    /// The vehicle is not plugged in
+    Unplugged,
+    /// The vehicle is plugged in and charging
+    Charging,
+    /// The vehicle is plugged in but not charging
+    Idle,
+}
+
+#[derive(Serialize, ToSchema)]
+struct EvInfo {
+    /// Name of the EV entity
+    entity_name: String,
+    /// Battery capacity of the vehicle in Wh
+    capacity: f64,
+    /// Maximum charging power in W
+    max_charge: f64,
+    /// Energy stored in the vehicle's battery in Wh
+    energy: f64,
+    /// Current state of charge in percentage
+    state_of_charge: f64,
+    /// Charging status (unplugged, charging, idle)
+    status: ChargingStatus,
+    /// The session the vehicle is plugged in for, if any
+    #[schema(nullable)]
+    session: Option<Session>,
+    /// Sessions that have not started yet
+    upcoming_sessions: Vec<Session>,
+    /// Current electricity consumption
+    consumption: Measurement,
+}
+
+impl From<EvProperties> for EvInfo {
+    fn from(ev: EvProperties) -> Self {
+        let consumption = ev.electricity_consumption.re;
+
+        let status = if !ev.available {
+            ChargingStatus::Unplugged
+        } else if consumption > 1.0 {
+            ChargingStatus::Charging
+        } else {
+            ChargingStatus::Idle
+        };
+
+        let state_of_charge = match ev.capacity {
+            capacity if capacity > 0.0 => ev.soc / capacity * 100.0,
+            _ => 0.0,
+        };
+
+        EvInfo {
+            capacity: ev.capacity,
+            max_charge: *ev.charging_powers.last().unwrap_or(&0.0),
+            energy: ev.soc,
+            state_of_charge,
+            status,
+            session: ev.current_session().cloned(),
+            upcoming_sessions: ev.upcoming_sessions(),
+            consumption: Measurement {
+                value: ev.electricity_consumption.norm(),
+                unit: "W".to_string(),
+            },
+            entity_name: ev.name,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct DepartureTarget {
+    /// Unix time the vehicle leaves
+    departure_time: u64,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ChargeLimit {
+    /// Maximum charging power in W
+    max_charge: f64,
+}
+
+#[utoipa::path(
+    get,
+    tag = "Electric vehicles",
+    description = "Get the charging session state of an electric vehicle.",
+    responses(
+        (status = 200, description = "Get EV properties", body = EvInfo),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("entity_name" = String, description = "Name of the EV entity"),
+    )
+)]
+#[get("")]
+async fn get_by_id(registry: web::Data<Registry>, id: web::Path<(u32, String)>) -> impl Responder {
+    let (house_id, entity_name) = id.into_inner();
+    let backend = registry.backend(house_id);
+
+    match demkit::ev::get_ev_properties(backend.as_ref(), house_id, &entity_name).await {
+        Ok(properties) => HttpResponse::Ok().json(EvInfo::from(properties)),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    tag = "Electric vehicles",
+    description = "Add an electric vehicle with its charging point, named after the path. \
+        A session is planned when the arrival time, departure time and required energy are given.",
+    responses(
+        (status = 200, description = "EV added successfully", body = String),
+        (status = 400, description = "Entity rejected by DEMKit", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("entity_name" = String, description = "Name of the EV entity"),
+    ),
+    request_body = EvEntityParams,
+)]
+#[post("")]
+async fn add_by_id(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, String)>,
+    params: web::Json<EvEntityParams>,
+) -> impl Responder {
+    let (house_id, entity_name) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    // the path names the EV in every other route, so it wins over the body
+    let mut params = params.into_inner();
+    params.name = entity_name.clone();
+
+    match demkit::env::add_ev(backend.as_ref(), house_id, params).await {
+        Ok(_) => HttpResponse::Ok().body(format!("{entity_name} added successfully")),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    tag = "Electric vehicles",
+    description = "Remove an electric vehicle from the house.",
+    responses(
+        (status = 200, description = "EV removed successfully", body = String),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("entity_name" = String, description = "Name of the EV entity"),
+    )
+)]
+#[delete("")]
+async fn remove_by_id(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, String)>,
+) -> impl Responder {
+    let (house_id, entity_name) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::env::remove_entity(backend.as_ref(), house_id, entity_name.as_str()).await {
+        Ok(_) => HttpResponse::Ok().body(format!("{entity_name} removed successfully")),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    tag = "Electric vehicles",
+    description = "Plug the vehicle in now, starting a session until the departure.",
+    responses(
+        (status = 200, description = "Session started", body = EvInfo),
+        (status = 400, description = "Session rejected by DEMKit", body = ErrorBody),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("entity_name" = String, description = "Name of the EV entity"),
+    ),
+    request_body = StartSession,
+)]
+#[post("/session")]
+async fn start_session(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, String)>,
+    body: web::Json<StartSession>,
+) -> impl Responder {
+    let (house_id, entity_name) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::ev::start_session(backend.as_ref(), house_id, &entity_name, body.into_inner()).await {
+        Ok(properties) => HttpResponse::Ok().json(EvInfo::from(properties)),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    tag = "Electric vehicles",
+    description = "Unplug the vehicle, ending the active session.",
+    responses(
+        (status = 200, description = "Session ended", body = EvInfo),
+        (status = 400, description = "No active session", body = ErrorBody),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("entity_name" = String, description = "Name of the EV entity"),
+    )
+)]
+#[delete("/session")]
+async fn end_session(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, String)>,
+) -> impl Responder {
+    let (house_id, entity_name) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::ev::end_session(backend.as_ref(), house_id, &entity_name).await {
+        Ok(properties) => HttpResponse::Ok().json(EvInfo::from(properties)),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    tag = "Electric vehicles",
+    description = "Set the departure time of the active session, or of the next one when the \
+        vehicle is not plugged in.",
+    responses(
+        (status = 200, description = "Departure set", body = EvInfo),
+        (status = 400, description = "Departure rejected by DEMKit", body = ErrorBody),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("entity_name" = String, description = "Name of the EV entity"),
+    ),
+    request_body = DepartureTarget,
+)]
+#[post("/departure")]
+async fn set_departure(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, String)>,
+    body: web::Json<DepartureTarget>,
+) -> impl Responder {
+    let (house_id, entity_name) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::ev::set_departure(backend.as_ref(), house_id, &entity_name, body.departure_time).await {
+        Ok(properties) => HttpResponse::Ok().json(EvInfo::from(properties)),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    tag = "Electric vehicles",
+    description = "Limit the charging power of the vehicle.",
+    responses(
+        (status = 200, description = "Charge power limit set", body = EvInfo),
+        (status = 400, description = "Limit rejected by DEMKit", body = ErrorBody),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("entity_name" = String, description = "Name of the EV entity"),
+    ),
+    request_body = ChargeLimit,
+)]
+#[post("/limit")]
+async fn set_charge_limit(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, String)>,
+    body: web::Json<ChargeLimit>,
+) -> impl Responder {
+    let (house_id, entity_name) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::ev::set_max_charge_power(backend.as_ref(), house_id, &entity_name, body.max_charge).await {
+        Ok(properties) => HttpResponse::Ok().json(EvInfo::from(properties)),
+        Err(e) => e.error_response(),
+    }
+}
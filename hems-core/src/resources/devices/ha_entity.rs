@@ -1,6 +1,10 @@
-use crate::api::demkit::ha_entity::{self, EntityRequest};
-use crate::api::ha::entity;
-use actix_web::{get, post, web, HttpResponse, Responder};
+use crate::api::demkit::{
+    ha_entity::{self, EntityRequest},
+    registry::Registry,
+};
+use crate::api::ha::{backend::HomeAssistantBackend, entity};
+use crate::api::error::{ErrorBody, UpstreamErrors};
+use actix_web::{get, post, web, HttpResponse, Responder, ResponseError};
 use serde_json::Value;
 use utoipa_actix_web::scope;
 
@@ -21,7 +25,8 @@ pub fn configure(cfg: &mut utoipa_actix_web::service_config::ServiceConfig) {
     description = "Get entity consumption.",
     responses(
         (status = 200, description = "Get entity consumption", body = EntityRequest),
-        (status = 500, description = "Failed to get entity consumption"),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
@@ -29,12 +34,14 @@ pub fn configure(cfg: &mut utoipa_actix_web::service_config::ServiceConfig) {
     ),
 )]
 #[get("/{entity_name}/consumption")]
-async fn get_entity_consumption(path: web::Path<(u32, String)>) -> impl Responder {
+async fn get_entity_consumption(
+    ha: web::Data<dyn HomeAssistantBackend>,
+    path: web::Path<(u32, String)>,
+) -> impl Responder {
     let entity_name = path.into_inner().1;
-    match entity::get_entity_consumption(&entity_name).await {
+    match entity::get_entity_consumption(ha.get_ref(), &entity_name).await {
         Ok(entity_state) => HttpResponse::Ok().json(entity_state),
-        Err(e) => HttpResponse::InternalServerError()
-            .body(format!("Failed to get device consumption: {}", e)),
+        Err(e) => e.error_response(),
     }
 }
 
@@ -44,7 +51,8 @@ async fn get_entity_consumption(path: web::Path<(u32, String)>) -> impl Responde
     description = "Get raw entity state from Home Assistant",
     responses(
         (status = 200, description = "Get entity state", body = EntityRequest),
-        (status = 500, description = "Failed to get entity state"),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
@@ -52,13 +60,14 @@ async fn get_entity_consumption(path: web::Path<(u32, String)>) -> impl Responde
     ),
 )]
 #[get("/{entity_name}/state")]
-async fn get_entity_state(path: web::Path<(u32, String)>) -> impl Responder {
+async fn get_entity_state(
+    ha: web::Data<dyn HomeAssistantBackend>,
+    path: web::Path<(u32, String)>,
+) -> impl Responder {
     let entity_name = path.into_inner().1;
-    match entity::get_entity_state(&entity_name).await {
+    match entity::get_entity_state(ha.get_ref(), &entity_name).await {
         Ok(entity_state) => HttpResponse::Ok().json(entity_state),
-        Err(e) => {
-            HttpResponse::InternalServerError().body(format!("Failed to get device state: {}", e))
-        }
+        Err(e) => e.error_response(),
     }
 }
 
@@ -69,7 +78,7 @@ async fn get_entity_state(path: web::Path<(u32, String)>) -> impl Responder {
     request_body = Value,
     responses(
         (status = 200, description = "Entity state set successfully"),
-        (status = 500, description = "Failed to set entity state"),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
@@ -78,15 +87,14 @@ async fn get_entity_state(path: web::Path<(u32, String)>) -> impl Responder {
 )]
 #[post("/{entity_name}/state")]
 async fn set_entity_state(
+    ha: web::Data<dyn HomeAssistantBackend>,
     path: web::Path<(u32, String)>,
     body: web::Json<Value>,
 ) -> impl Responder {
     let entity_name = path.into_inner().1;
-    match entity::set_entity_state(&entity_name, body.into_inner()).await {
+    match entity::set_entity_state(ha.get_ref(), &entity_name, body.into_inner()).await {
         Ok(entity_state) => HttpResponse::Ok().json(entity_state),
-        Err(e) => {
-            HttpResponse::InternalServerError().body(format!("Failed to set device state: {}", e))
-        }
+        Err(e) => e.error_response(),
     }
 }
 
@@ -97,7 +105,8 @@ async fn set_entity_state(
     request_body = EntityRequest,
     responses(
         (status = 200, description = "Entity added successfully"),
-        (status = 500, description = "Failed to add entity"),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     request_body = EntityRequest,
     params(
@@ -105,11 +114,20 @@ async fn set_entity_state(
     )
 )]
 #[post("")]
-async fn add_entity(request: web::Json<EntityRequest>) -> impl Responder {
+async fn add_entity(
+    registry: web::Data<Registry>,
+    path: web::Path<u32>,
+    request: web::Json<EntityRequest>,
+) -> impl Responder {
+    let house_id = path.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
     let entity = request.into_inner();
-    match ha_entity::add_entity(entity).await {
+    match ha_entity::add_entity(backend.as_ref(), house_id, entity).await {
         Ok(_) => HttpResponse::Ok().body("OK"),
-        Err(e) => HttpResponse::InternalServerError().body(format!("Failed to add entity, :{}", e)),
+        Err(e) => e.error_response(),
     }
 }
 
@@ -119,7 +137,7 @@ async fn add_entity(request: web::Json<EntityRequest>) -> impl Responder {
     description = "Set entity state",
     responses(
         (status = 200, description = "Entity state set successfully"),
-        (status = 500, description = "Failed to set entity state"),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
@@ -128,10 +146,13 @@ async fn add_entity(request: web::Json<EntityRequest>) -> impl Responder {
     ),
 )]
 #[get("/{entity_name}/toggle/{state}")]
-async fn toggle(id: web::Path<(u32, String, bool)>) -> impl Responder {
+async fn toggle(
+    ha: web::Data<dyn HomeAssistantBackend>,
+    id: web::Path<(u32, String, bool)>,
+) -> impl Responder {
     let (_house_id, entity_name, state) = id.into_inner();
-    match entity::toggle_entity_state(&entity_name, state).await {
+    match entity::toggle_entity_state(ha.get_ref(), &entity_name, state).await {
         Ok(entity_states) => HttpResponse::Ok().json(entity_states),
-        Err(e) => HttpResponse::InternalServerError().body(format!("Failed to toggle device state: {}", e)),
+        Err(e) => e.error_response(),
     }
 }
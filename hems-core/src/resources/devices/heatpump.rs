@@ -0,0 +1,174 @@
+use actix_web::{get, post, web, HttpResponse, Responder, ResponseError};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use utoipa_actix_web::scope;
+
+use crate::api::demkit::{self, heatpump::HeatSourceProperties, registry::Registry};
+use crate::api::error::{ErrorBody, UpstreamErrors};
+
+pub fn configure(cfg: &mut utoipa_actix_web::service_config::ServiceConfig) {
+    cfg.service(
+        scope::scope("/heatpump")
+            .service(get_by_id)
+            .service(turn_on)
+            .service(turn_off)
+            .service(set_power_level),
+    );
+}
+
+#[derive(Serialize, ToSchema)]
+pub(super) struct HeatSourceInfo {
+    /// Name of the DEMKit entity
+    entity_name: String,
+    /// Whether the device may produce heat
+    enabled: bool,
+    /// Share of the rated heat output the device may produce, in percentage
+    power_level: f64,
+    /// Maximum heat output at the current power level in W
+    max_heat_output: f64,
+    /// Current heat output in W
+    heat_output: f64,
+    /// Current electricity consumption in W
+    consumption: f64,
+    /// Heat produced per unit of electricity, absent for devices without an electricity input
+    #[schema(nullable)]
+    cop: Option<f64>,
+    /// Supply temperature in Celsius
+    supply_temperature: f64,
+}
+
+impl From<HeatSourceProperties> for HeatSourceInfo {
+    fn from(hp: HeatSourceProperties) -> Self {
+        HeatSourceInfo {
+            enabled: hp.enabled,
+            power_level: hp.power_level * 100.0,
+            max_heat_output: *hp.producing_powers.last().unwrap_or(&0.0),
+            heat_output: hp.heat_production,
+            consumption: hp.electricity_consumption.re,
+            cop: hp.cop.get("ELECTRICITY").copied(),
+            supply_temperature: hp.temperature,
+            entity_name: hp.name,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct PowerLevel {
+    /// Share of the rated heat output, in percentage
+    #[schema(minimum = 0.0, maximum = 100.0)]
+    level: f64,
+}
+
+#[utoipa::path(
+    get,
+    tag = "Heat pump",
+    description = "Get the state of the heat pump heating the house.",
+    responses(
+        (status = 200, description = "Get heat pump properties", body = HeatSourceInfo),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+    )
+)]
+#[get("")]
+async fn get_by_id(registry: web::Data<Registry>, id: web::Path<u32>) -> impl Responder {
+    let house_id = id.into_inner();
+    let backend = registry.backend(house_id);
+
+    match demkit::heatpump::get_heatpump_properties(backend.as_ref(), house_id).await {
+        Ok(properties) => HttpResponse::Ok().json(HeatSourceInfo::from(properties)),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    tag = "Heat pump",
+    description = "Turn the heat pump on, at its last power level.",
+    responses(
+        (status = 200, description = "Heat pump turned on", body = HeatSourceInfo),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+    )
+)]
+#[post("/on")]
+async fn turn_on(registry: web::Data<Registry>, id: web::Path<u32>) -> impl Responder {
+    let house_id = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::heatpump::set_enabled(backend.as_ref(), house_id, true).await {
+        Ok(properties) => HttpResponse::Ok().json(HeatSourceInfo::from(properties)),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    tag = "Heat pump",
+    description = "Turn the heat pump off, it produces no heat until turned on again.",
+    responses(
+        (status = 200, description = "Heat pump turned off", body = HeatSourceInfo),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+    )
+)]
+#[post("/off")]
+async fn turn_off(registry: web::Data<Registry>, id: web::Path<u32>) -> impl Responder {
+    let house_id = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::heatpump::set_enabled(backend.as_ref(), house_id, false).await {
+        Ok(properties) => HttpResponse::Ok().json(HeatSourceInfo::from(properties)),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    tag = "Heat pump",
+    description = "Limit the heat output of the heat pump to a share of its rated output.",
+    responses(
+        (status = 200, description = "Power level set", body = HeatSourceInfo),
+        (status = 400, description = "Invalid power level", body = ErrorBody),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+    ),
+    request_body = PowerLevel,
+)]
+#[post("/power")]
+async fn set_power_level(
+    registry: web::Data<Registry>,
+    id: web::Path<u32>,
+    body: web::Json<PowerLevel>,
+) -> impl Responder {
+    let house_id = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::heatpump::set_power_level(backend.as_ref(), house_id, body.level).await {
+        Ok(properties) => HttpResponse::Ok().json(HeatSourceInfo::from(properties)),
+        Err(e) => e.error_response(),
+    }
+}
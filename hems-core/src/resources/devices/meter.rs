@@ -1,11 +1,17 @@
-use actix_web::{get, web, HttpResponse, Responder};
-use serde::Serialize;
-use utoipa::ToSchema;
+use std::collections::BTreeMap;
+
+use actix_web::{get, web, HttpResponse, Responder, ResponseError};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use utoipa_actix_web::scope;
 
-use crate::api::demkit;
+use crate::api::demkit::{self, meter::MeterProperties, registry::Registry, Measurement};
+use crate::api::error::{ErrorBody, UpstreamErrors};
 
 pub fn configure(cfg: &mut utoipa_actix_web::service_config::ServiceConfig) {
+    cfg.service(list);
+    // before the scope, which would take `gas` for a meter ID
+    cfg.service(get_gas);
     cfg.service(
         scope::scope("/meters/{id}")
             .service(get_by_id)
@@ -30,39 +36,140 @@ struct MeterInfo {
     current_export: Option<f64>,
 }
 
+#[derive(Serialize, ToSchema)]
+struct MeterSummary {
+    /// Name of the meter in DEMKit
+    entity_name: String,
+    /// Commodities the meter measures
+    commodities: Vec<String>,
+    /// Current flow per commodity in W, negative when exporting
+    flows: BTreeMap<String, f64>,
+    /// Energy imported since the simulation started in Wh
+    total_import: f64,
+    /// Energy exported since the simulation started in Wh
+    total_export: f64,
+}
+
+impl From<MeterProperties> for MeterSummary {
+    fn from(meter: MeterProperties) -> Self {
+        MeterSummary {
+            total_import: meter.energy(meter.imported),
+            total_export: meter.energy(meter.exported),
+            entity_name: meter.name,
+            commodities: meter.commodities,
+            flows: meter.flows,
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+struct MetersQuery {
+    /// Only list meters measuring this commodity, e.g. ELECTRICITY or NATGAS
+    commodity: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct GasMeterInfo {
+    /// House ID
+    house_id: u32,
+    /// Current gas flow in m³/h
+    current_flow: Measurement,
+    /// Gas used since the simulation started in m³
+    total: Measurement,
+    /// Current gas consumption as chemical power in W
+    power: Measurement,
+}
+
+#[utoipa::path(
+    get,
+    tag = "Meter",
+    description = "List the meters of the house, optionally only those measuring a commodity",
+    path = "/meters",
+    responses(
+        (status = 200, description = "Meters of the house", body = Vec<MeterSummary>),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        MetersQuery,
+    ),
+)]
+#[get("/meters")]
+async fn list(
+    registry: web::Data<Registry>,
+    id: web::Path<u32>,
+    query: web::Query<MetersQuery>,
+) -> impl Responder {
+    let house_id = id.into_inner();
+    let backend = registry.backend(house_id);
+
+    match demkit::meter::list_meters(backend.as_ref(), house_id, query.commodity.as_deref()).await {
+        Ok(meters) => HttpResponse::Ok().json(meters.into_iter().map(MeterSummary::from).collect::<Vec<_>>()),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    tag = "Meter",
+    description = "Get the gas flow and cumulative gas use of the house's gas meter",
+    path = "/meters/gas",
+    responses(
+        (status = 200, description = "Get gas meter information", body = GasMeterInfo),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+    ),
+)]
+#[get("/meters/gas")]
+async fn get_gas(registry: web::Data<Registry>, id: web::Path<u32>) -> impl Responder {
+    let house_id = id.into_inner();
+    let backend = registry.backend(house_id);
+
+    match demkit::meter::get_gas_meter(backend.as_ref(), house_id).await {
+        Ok(reading) => HttpResponse::Ok().json(GasMeterInfo {
+            house_id,
+            current_flow: reading.flow,
+            total: reading.total,
+            power: reading.power,
+        }),
+        Err(e) => e.error_response(),
+    }
+}
+
 #[utoipa::path(
     get,
     tag = "Meter",
     description = "Get properties of a meter device",
     responses(
         (status = 200, description = "Get meter information", body = MeterInfo),
-        (status = 500, description = "Failed to get meter information"),
     ),
 )]
 #[get("")]
-async fn get_by_id(id: web::Path<(u32, u32)>) -> impl Responder {
+async fn get_by_id(registry: web::Data<Registry>, id: web::Path<(u32, u32)>) -> impl Responder {
     let (house_id, meter_id) = id.into_inner();
+    let backend = registry.backend(house_id);
 
     let device_name = format!("SmartMeter-House-{}", house_id);
 
-    let current_import = match demkit::meter::get_energy_import(house_id).await {
+    let current_import = match demkit::meter::get_energy_import(backend.as_ref(), house_id).await {
         Ok(measurement) => Some(measurement.value),
         Err(_) => None,
     };
-    let current_export = match demkit::meter::get_energy_export(house_id).await {
+    let current_export = match demkit::meter::get_energy_export(backend.as_ref(), house_id).await {
         Ok(measurement) => Some(measurement.value),
         Err(_) => None,
     };
 
-    let total_import = match demkit::devices::get_device_property(&device_name, "imported").await {
-        Ok(value) => value,
-        Err(_) => 0.0,
-    };
+    let total_import = demkit::devices::get_device_property(backend.as_ref(), &device_name, "imported")
+        .await
+        .unwrap_or(0.0);
 
-    let total_export = match demkit::devices::get_device_property(&device_name, "exported").await {
-        Ok(value) => value,
-        Err(_) => 0.0,
-    };
+    let total_export = demkit::devices::get_device_property(backend.as_ref(), &device_name, "exported")
+        .await
+        .unwrap_or(0.0);
 
     let meter_info = MeterInfo {
         house_id,
@@ -82,18 +189,20 @@ async fn get_by_id(id: web::Path<(u32, u32)>) -> impl Responder {
     description = "Get current energy import",
     responses(
         (status = 200, description = "Get energy import", body = f64),
-        (status = 500, description = "Failed to get energy import"),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        UpstreamErrors,
     ),
 )]
 #[get("/import")]
-async fn get_import(id: web::Path<(u32, u32)>) -> impl Responder {
+async fn get_import(registry: web::Data<Registry>, id: web::Path<(u32, u32)>) -> impl Responder {
     let (house_id, _meter_id) = id.into_inner();
+    let backend = registry.backend(house_id);
 
-    let import = demkit::meter::get_energy_import(house_id).await;
+    let import = demkit::meter::get_energy_import(backend.as_ref(), house_id).await;
 
     match import {
         Ok(measurement) => HttpResponse::Ok().json(measurement),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+        Err(e) => e.error_response(),
     }
 }
 
@@ -103,17 +212,19 @@ async fn get_import(id: web::Path<(u32, u32)>) -> impl Responder {
     description = "Get current energy export",
     responses(
         (status = 200, description = "Get energy export", body = f64),
-        (status = 500, description = "Failed to get energy export"),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        UpstreamErrors,
     ),
 )]
 #[get("/export")]
-async fn get_export(id: web::Path<(u32, u32)>) -> impl Responder {
+async fn get_export(registry: web::Data<Registry>, id: web::Path<(u32, u32)>) -> impl Responder {
     let (house_id, _meter_id) = id.into_inner();
+    let backend = registry.backend(house_id);
 
-    let import = demkit::meter::get_energy_export(house_id).await;
+    let import = demkit::meter::get_energy_export(backend.as_ref(), house_id).await;
 
     match import {
         Ok(measurement) => HttpResponse::Ok().json(measurement),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+        Err(e) => e.error_response(),
     }
 }
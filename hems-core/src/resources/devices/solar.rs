@@ -1,9 +1,15 @@
-use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use actix_web::{delete, get, post, web, HttpResponse, Responder, ResponseError};
 use serde::Serialize;
 use utoipa::ToSchema;
 use utoipa_actix_web::scope;
 
-use crate::api::demkit::{self, env::SolarEntityParams};
+use crate::api::demkit::{
+    self,
+    env::SolarEntityParams,
+    registry::Registry,
+    solar::{Curtailment, SolarProperties},
+};
+use crate::api::error::{ErrorBody, UpstreamErrors};
 
 pub fn configure(cfg: &mut utoipa_actix_web::service_config::ServiceConfig) {
     cfg.service(
@@ -11,23 +17,78 @@ pub fn configure(cfg: &mut utoipa_actix_web::service_config::ServiceConfig) {
             .service(get_by_id)
             .service(add_by_id)
             .service(remove_by_id)
-            .service(toggle),
+            .service(toggle)
+            .service(curtail)
+            .service(clear_curtailment),
     );
 }
 
 #[derive(Serialize, ToSchema)]
 struct SolarInfo {
-    /// Current electricity consumption (can be negative to indicate generation)
-    consumption: f64
+    /// Current electricity consumption in W (negative while producing)
+    consumption: f64,
+    /// Current production in W
+    production: f64,
+    /// Production without curtailment in W
+    potential_production: f64,
+    /// Whether the panels are switched on
+    on: bool,
+    /// Panel area in m²
+    size: f64,
+    /// Conversion efficiency in percentage
+    efficiency: f64,
+    /// Tilt in degrees from the horizontal plane
+    inclination: f64,
+    /// Orientation in degrees, 0 is north, 90 is east, 180 is south
+    azimuth: f64,
+    /// Energy produced today in kWh
+    energy_today: f64,
+    /// Energy produced since the simulation started in kWh
+    energy_total: f64,
+    /// Share of the potential production the panels may produce, in percentage
+    #[schema(nullable)]
+    curtail_percentage: Option<f64>,
+    /// Maximum production in W
+    #[schema(nullable)]
+    curtail_limit: Option<f64>,
+}
+
+/// Production in W from a consumption that is negative while producing
+fn production(consumption: f64) -> f64 {
+    if consumption < 0.0 {
+        -consumption
+    } else {
+        0.0
+    }
+}
+
+impl From<SolarProperties> for SolarInfo {
+    fn from(sp: SolarProperties) -> Self {
+        SolarInfo {
+            consumption: sp.electricity_consumption.re,
+            production: production(sp.electricity_consumption.re),
+            potential_production: production(sp.potential_consumption.re),
+            on: sp.on_off_device,
+            size: sp.size,
+            efficiency: sp.efficiency,
+            inclination: sp.inclination,
+            azimuth: sp.azimuth,
+            energy_today: sp.energy_today / 1000.0,
+            energy_total: sp.energy_total / 1000.0,
+            curtail_percentage: sp.curtail_level.map(|level| level * 100.0),
+            curtail_limit: sp.curtail_limit,
+        }
+    }
 }
 
 #[utoipa::path(
     get,
     tag = "Solar",
-    description = "Get solar information.",
+    description = "Get the production, panel geometry and produced energy of the solar panels.",
     responses(
         (status = 200, description = "Get solar information", body = SolarInfo),
-        (status = 500, description = "Failed to get solar information"),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
@@ -35,17 +96,16 @@ struct SolarInfo {
     )
 )]
 #[get("")]
-async fn get_by_id(id: web::Path<(u32, u32)>) -> impl Responder {
-    let (_house_id, solar_id) = id.into_inner();
+async fn get_by_id(registry: web::Data<Registry>, id: web::Path<(u32, u32)>) -> impl Responder {
+    let (house_id, _solar_id) = id.into_inner();
+    let backend = registry.backend(house_id);
 
-    let sp = match demkit::solar::get_solar_properties(solar_id).await {
+    let sp = match demkit::solar::get_solar_properties(backend.as_ref(), house_id).await {
         Ok(properties) => properties,
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {:?}", e)),
+        Err(e) => return e.error_response(),
     };
 
-    let solar_info = SolarInfo {
-        consumption: sp.electricity_consumption.unwrap().norm(),
-    };
+    let solar_info = SolarInfo::from(sp);
 
     HttpResponse::Ok().json(solar_info)
 }
@@ -53,11 +113,14 @@ async fn get_by_id(id: web::Path<(u32, u32)>) -> impl Responder {
 #[utoipa::path(
     post,
     tag = "Solar",
-    description = "Add a solar entity.",
+    description = "Add a solar entity. Parameters that are not given are taken from DEMKit's \
+        PV settings for the house.",
     request_body = SolarEntityParams,
     responses(
         (status = 200, description = "Add solar entity successfully"),
-        (status = 500, description = "Failed to add solar entity"),
+        (status = 400, description = "Entity rejected by DEMKit", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
@@ -66,14 +129,19 @@ async fn get_by_id(id: web::Path<(u32, u32)>) -> impl Responder {
 )]
 #[post("")]
 async fn add_by_id(
+    registry: web::Data<Registry>,
     id: web::Path<(u32, String)>,
-    _params: web::Json<SolarEntityParams>,
+    params: web::Json<SolarEntityParams>,
 ) -> impl Responder {
     let (house_id, entity_name) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
 
-    match demkit::env::add_solar(house_id).await {
+    match demkit::env::add_solar(backend.as_ref(), house_id, params.into_inner()).await {
         Ok(_) => HttpResponse::Ok().body(format!("{entity_name} added successfully")),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {:?}", e)),
+        Err(e) => return e.error_response(),
     };
 
     HttpResponse::Ok().body(format!("{entity_name} added successfully"))
@@ -85,19 +153,26 @@ async fn add_by_id(
     description = "Remove a solar entity.",
     responses(
         (status = 200, description = "Remove solar entity successfully"),
-        (status = 500, description = "Failed to remove solar entity"),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
-        ("entity_name" = String, description = "Name of the solar entity"))
+        ("solar_id" = u32, description = "Solar ID"),
+    )
 )]
 #[delete("")]
-async fn remove_by_id(id: web::Path<(u32, String)>) -> impl Responder {
-    let (house_id, entity_name) = id.into_inner();
+async fn remove_by_id(registry: web::Data<Registry>, id: web::Path<(u32, u32)>) -> impl Responder {
+    let (house_id, _solar_id) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
 
-    match demkit::env::remove_entity(house_id, entity_name.as_str()).await {
-        Ok(_) => HttpResponse::Ok().body(format!("{entity_name} removed successfully")),
-        Err(e) => HttpResponse::InternalServerError().body(format!("Error: {:?}", e)),
+    match demkit::env::remove_entity(backend.as_ref(), house_id, demkit::env::SOLAR_NAME).await {
+        Ok(_) => HttpResponse::Ok().body(format!("{} removed successfully", demkit::env::SOLAR_NAME)),
+        Err(e) => e.error_response(),
     }
 }
 
@@ -107,7 +182,8 @@ async fn remove_by_id(id: web::Path<(u32, String)>) -> impl Responder {
     description = "Get solar information.",
     responses(
         (status = 200, description = "Toggle solar state successfully"),
-        (status = 500, description = "Failed to toggle solar state"),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
@@ -116,8 +192,79 @@ async fn remove_by_id(id: web::Path<(u32, String)>) -> impl Responder {
     ),
 )]
 #[get("/toggle/{state}")]
-async fn toggle(id: web::Path<(u32, u32, bool)>) -> impl Responder {
+async fn toggle(registry: web::Data<Registry>, id: web::Path<(u32, u32, bool)>) -> impl Responder {
     let (house_id, _solar_id, state) = id.into_inner();
-    demkit::solar::set_solar_state(house_id, state).await.unwrap();
-    HttpResponse::Ok().body(format!("Toggled {state}"))
-}
\ No newline at end of file
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::solar::set_solar_state(backend.as_ref(), house_id, state).await {
+        Ok(_) => HttpResponse::Ok().body(format!("Toggled {state}")),
+        Err(e) => e.error_response(),
+    }
+}
+#[utoipa::path(
+    post,
+    tag = "Solar",
+    description = "Limit the production of the panels to a percentage of their potential \
+        production, to a maximum power, or to the lower of both. Replaces earlier limits.",
+    responses(
+        (status = 200, description = "Curtailment set", body = SolarInfo),
+        (status = 400, description = "Invalid curtailment", body = ErrorBody),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("solar_id" = u32, description = "Solar ID"),
+    ),
+    request_body = Curtailment,
+)]
+#[post("/curtail")]
+async fn curtail(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, u32)>,
+    body: web::Json<Curtailment>,
+) -> impl Responder {
+    let (house_id, _solar_id) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::solar::set_curtailment(backend.as_ref(), house_id, Some(body.into_inner())).await {
+        Ok(properties) => HttpResponse::Ok().json(SolarInfo::from(properties)),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    tag = "Solar",
+    description = "Remove the production limits of the panels.",
+    responses(
+        (status = 200, description = "Curtailment cleared", body = SolarInfo),
+        (status = 404, description = "Unknown entity", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("solar_id" = u32, description = "Solar ID"),
+    ),
+)]
+#[delete("/curtail")]
+async fn clear_curtailment(registry: web::Data<Registry>, id: web::Path<(u32, u32)>) -> impl Responder {
+    let (house_id, _solar_id) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::solar::set_curtailment(backend.as_ref(), house_id, None).await {
+        Ok(properties) => HttpResponse::Ok().json(SolarInfo::from(properties)),
+        Err(e) => e.error_response(),
+    }
+}
@@ -0,0 +1,516 @@
+//! Drives the device routes against the in-memory DEMKit and Home Assistant stand-ins.
+//!
+//! Every test composes the default house spec under its own house ID, as timeshifters,
+//! Home Assistant entities and heating schedules are tracked per house in global state.
+
+use actix_web::{http::StatusCode, test, web, App};
+use serde_json::{json, Value};
+use utoipa_actix_web::AppExt;
+
+use crate::api::{demkit::registry::Registry, ha::backend::HomeAssistantBackend, history::History};
+use crate::config::{HistoryConfig, HousesConfig, UpstreamConfig};
+use crate::resources::house;
+
+/// Registry whose default instance is a fresh in-memory DEMKit
+fn registry() -> web::Data<Registry> {
+    web::Data::new(Registry::new(UpstreamConfig::default().policy(), "memory://demkit"))
+}
+
+/// Builds the app on fresh in-memory stand-ins, or on the given registry to inspect the DEMKit
+/// stand-in afterwards, and composes and loads the default house
+macro_rules! app {
+    ($house_id:expr) => {
+        app!($house_id, registry())
+    };
+    ($house_id:expr, $registry:expr) => {{
+        let registry: web::Data<Registry> = $registry;
+        let ha: web::Data<dyn HomeAssistantBackend> =
+            web::Data::from(crate::api::ha::from_config(&crate::config::HomeAssistantConfig {
+                url: Some("memory://homeassistant".to_string()),
+                ..Default::default()
+            }));
+        let history = web::Data::new(
+            History::open(&HistoryConfig {
+                path: ":memory:".into(),
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let houses = web::Data::new(HousesConfig {
+            spec_dir: concat!(env!("CARGO_MANIFEST_DIR"), "/houses").into(),
+        });
+
+        let app = test::init_service(
+            App::new()
+                .app_data(registry)
+                .app_data(ha)
+                .app_data(history)
+                .app_data(houses)
+                .into_utoipa_app()
+                .service(crate::api::debug::home_assistant_calls)
+                .configure(house::configure)
+                .into_app(),
+        )
+        .await;
+
+        let (status, _) = call!(app, post, &format!("/houses/{}", $house_id));
+        assert_eq!(status, StatusCode::OK, "compose");
+        let (status, _) = call!(app, post, &format!("/houses/{}/load", $house_id));
+        assert_eq!(status, StatusCode::OK, "load");
+
+        app
+    }};
+}
+
+/// Sends a request, returning the status and the body as JSON, `Value::Null` if it is not JSON
+macro_rules! call {
+    ($app:expr, $method:ident, $uri:expr) => {
+        call!(@send $app, test::TestRequest::$method().uri($uri))
+    };
+    ($app:expr, $method:ident, $uri:expr, $body:expr) => {
+        call!(@send $app, test::TestRequest::$method().uri($uri).set_json($body))
+    };
+    (@send $app:expr, $request:expr) => {{
+        let response = test::call_service(&$app, $request.to_request()).await;
+        let status = response.status();
+        let body = test::read_body(response).await;
+
+        (status, serde_json::from_slice::<Value>(&body).unwrap_or(Value::Null))
+    }};
+}
+
+#[actix_web::test]
+async fn meters() {
+    let app = app!(101);
+
+    let (status, meters) = call!(app, get, "/houses/101/meters");
+    assert_eq!(status, StatusCode::OK);
+    let names: Vec<&str> = meters
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|m| m["entity_name"].as_str())
+        .collect();
+    assert_eq!(names, ["SmartMeter-House-101", "SmartGasMeter-House-101"], "{meters}");
+
+    let (status, meters) = call!(app, get, "/houses/101/meters?commodity=NATGAS");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(meters.as_array().map(Vec::len), Some(1), "{meters}");
+    assert_eq!(meters[0]["entity_name"], json!("SmartGasMeter-House-101"), "{meters}");
+    let (status, gas) = call!(app, get, "/houses/101/meters/gas");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(gas["house_id"], json!(101), "{gas}");
+    assert_eq!(gas["current_flow"]["unit"], json!("m³/h"), "{gas}");
+}
+
+#[actix_web::test]
+async fn battery() {
+    let registry = registry();
+    let app = app!(102, registry.clone());
+    let backend = registry.backend(102);
+
+    let (status, _) = call!(app, get, "/houses/102/battery/1");
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = call!(
+        app,
+        post,
+        "/houses/102/battery/1",
+        json!({"name": "Battery", "capacity": 10000.0})
+    );
+    assert_eq!(status, StatusCode::OK);
+    let (status, battery) = call!(app, get, "/houses/102/battery/1");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(battery["capacity"], json!(10000.0), "{battery}");
+    assert_eq!(battery["target_soc"], Value::Null, "{battery}");
+
+    let (status, battery) = call!(app, get, "/houses/102/battery/1/target/80");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(battery["target_soc"], json!(80.0), "{battery}");
+    let (status, battery) = call!(
+        app,
+        post,
+        "/houses/102/battery/1/setpoint",
+        json!({"power": 1000.0})
+    );
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(battery["power_setpoint"], json!(1000.0), "{battery}");
+    let properties = backend.properties("Battery-House-102").await.unwrap();
+    assert_eq!(properties["powerSetpoint"], json!(1000.0), "{properties}");
+    let (status, _) = call!(app, delete, "/houses/102/battery/1/setpoint");
+    assert_eq!(status, StatusCode::OK);
+    let properties = backend.properties("Battery-House-102").await.unwrap();
+    assert_eq!(properties["powerSetpoint"], Value::Null, "{properties}");
+
+    let schedule = json!({"interval": 900, "powers": [1000.0, -1000.0]});
+    let (status, battery) = call!(app, post, "/houses/102/battery/1/schedule", schedule);
+    assert_eq!(status, StatusCode::OK);
+    let expected = json!([{"time": 0, "power": 1000.0}, {"time": 900, "power": -1000.0}]);
+    assert_eq!(battery["schedule"], expected, "{battery}");
+    assert_eq!(battery["schedule_end"], json!(1800), "{battery}");
+    let properties = backend.properties("Battery-House-102").await.unwrap();
+    assert_eq!(properties["scheduleEnd"], json!(1800), "{properties}");
+    let schedule = json!({"start_time": u64::MAX - 900, "interval": 900, "powers": [1000.0, -1000.0]});
+    let (status, _) = call!(app, post, "/houses/102/battery/1/schedule", schedule);
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let schedule = json!({"interval": 86_401, "powers": [1000.0]});
+    let (status, _) = call!(app, post, "/houses/102/battery/1/schedule", schedule);
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = call!(app, delete, "/houses/102/battery/1/schedule");
+    assert_eq!(status, StatusCode::OK);
+    let properties = backend.properties("Battery-House-102").await.unwrap();
+    assert_eq!(properties["schedule"], json!([]), "{properties}");
+
+    let (status, _) = call!(app, delete, "/houses/102/battery/1");
+    assert_eq!(status, StatusCode::OK);
+    assert!(backend.properties("Battery-House-102").await.is_err());
+}
+
+#[actix_web::test]
+async fn solar() {
+    let registry = registry();
+    let app = app!(103, registry.clone());
+    let backend = registry.backend(103);
+
+    let (status, _) = call!(
+        app,
+        post,
+        "/houses/103/solar/1",
+        json!({"name": "PV", "size": 12.0})
+    );
+    assert_eq!(status, StatusCode::OK);
+    let (status, solar) = call!(app, get, "/houses/103/solar/1");
+    assert_eq!(status, StatusCode::OK, "{solar}");
+    assert_eq!(solar["size"], json!(12.0), "{solar}");
+    assert_eq!(solar["on"], json!(true), "{solar}");
+
+    let (status, solar) = call!(
+        app,
+        post,
+        "/houses/103/solar/1/curtail",
+        json!({"percentage": 50.0})
+    );
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(solar["curtail_percentage"], json!(50.0), "{solar}");
+    let properties = backend.properties("PV-House-103").await.unwrap();
+    assert!(!properties["curtailLevel"].is_null(), "{properties}");
+    let (status, _) = call!(
+        app,
+        post,
+        "/houses/103/solar/1/curtail",
+        json!({"percentage": 150.0})
+    );
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = call!(app, delete, "/houses/103/solar/1/curtail");
+    assert_eq!(status, StatusCode::OK);
+    let (status, solar) = call!(app, get, "/houses/103/solar/1");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(solar["curtail_percentage"], Value::Null, "{solar}");
+
+    let (status, _) = call!(app, get, "/houses/103/solar/1/toggle/false");
+    assert_eq!(status, StatusCode::OK);
+    let (status, solar) = call!(app, get, "/houses/103/solar/1");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(solar["on"], json!(false), "{solar}");
+    let (status, _) = call!(app, delete, "/houses/103/solar/1");
+    assert_eq!(status, StatusCode::OK);
+    assert!(backend.properties("PV-House-103").await.is_err());
+}
+
+#[actix_web::test]
+async fn ev() {
+    let registry = registry();
+    let app = app!(104, registry.clone());
+    let backend = registry.backend(104);
+
+    let (status, _) = call!(
+        app,
+        post,
+        "/houses/104/ev/Car",
+        json!({"name": "Car", "capacity": 40000.0})
+    );
+    assert_eq!(status, StatusCode::OK);
+
+    let session = json!({"departure_in": 3600, "required_energy": 10000.0});
+    let (status, ev) = call!(app, post, "/houses/104/ev/Car/session", session);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(ev["session"], json!({"startTime": 0, "endTime": 3600, "charge": 10000.0}), "{ev}");
+    let (status, ev) = call!(
+        app,
+        post,
+        "/houses/104/ev/Car/limit",
+        json!({"max_charge": 3700.0})
+    );
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(ev["max_charge"], json!(3700.0), "{ev}");
+    let properties = backend.properties("Car-House-104").await.unwrap();
+    assert_eq!(properties["chargingPowers"], json!([0.0, 3700.0]), "{properties}");
+    let (status, ev) = call!(app, get, "/houses/104/ev/Car");
+    assert_eq!(status, StatusCode::OK, "{ev}");
+    assert_eq!(ev["entity_name"], json!("Car-House-104"), "{ev}");
+
+    let (status, _) = call!(app, delete, "/houses/104/ev/Car/session");
+    assert_eq!(status, StatusCode::OK);
+    let properties = backend.properties("Car-House-104").await.unwrap();
+    assert_eq!(properties["available"], json!(false), "{properties}");
+    let (status, _) = call!(app, delete, "/houses/104/ev/Car");
+    assert_eq!(status, StatusCode::OK);
+    assert!(backend.properties("Car-House-104").await.is_err());
+}
+
+#[actix_web::test]
+async fn heat_pump_and_hot_water() {
+    let registry = registry();
+    let app = app!(105, registry.clone());
+    let backend = registry.backend(105);
+
+    let (status, heat_pump) = call!(app, get, "/houses/105/heatpump");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(heat_pump["enabled"], json!(true), "{heat_pump}");
+    let (status, heat_pump) = call!(app, post, "/houses/105/heatpump/off");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(heat_pump["enabled"], json!(false), "{heat_pump}");
+    assert_eq!(heat_pump["max_heat_output"], json!(0.0), "{heat_pump}");
+    let (status, heat_pump) = call!(app, post, "/houses/105/heatpump/on");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(heat_pump["enabled"], json!(true), "{heat_pump}");
+    let (status, heat_pump) = call!(app, post, "/houses/105/heatpump/power", json!({"level": 50.0}));
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(heat_pump["power_level"], json!(50.0), "{heat_pump}");
+    let properties = backend.properties("HeatPump-House-105").await.unwrap();
+    assert_eq!(properties["powerLevel"], json!(0.5), "{properties}");
+
+    let (status, dhw) = call!(app, get, "/houses/105/dhw");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(dhw["setpoint"], json!(60.0), "{dhw}");
+    let (status, dhw) = call!(
+        app,
+        post,
+        "/houses/105/dhw/setpoint",
+        json!({"temperature": 55.0})
+    );
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(dhw["setpoint"], json!(55.0), "{dhw}");
+    assert_eq!(dhw["heater"]["supply_temperature"], json!(55.0), "{dhw}");
+    let (status, _) = call!(
+        app,
+        post,
+        "/houses/105/dhw/setpoint",
+        json!({"temperature": 95.0})
+    );
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, dhw) = call!(app, get, "/houses/105/dhw");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(dhw["setpoint"], json!(55.0), "{dhw}");
+}
+
+#[actix_web::test]
+async fn thermal() {
+    let registry = registry();
+    let app = app!(106, registry.clone());
+    let backend = registry.backend(106);
+
+    let (status, thermal) = call!(app, get, "/houses/106/thermal");
+    assert_eq!(status, StatusCode::OK);
+    let zone = thermal["zones"][0]["zone"].as_str().unwrap().to_string();
+
+    let (status, _) = call!(app, get, &format!("/houses/106/thermal/{zone}"));
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = call!(app, get, "/houses/106/thermal/Nowhere");
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, before) = call!(app, get, &format!("/houses/106/thermal/{zone}/thermostat"));
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = call!(app, get, &format!("/houses/106/thermal/{zone}/target/21"));
+    assert_eq!(status, StatusCode::OK);
+    let (status, thermostat) = call!(app, get, &format!("/houses/106/thermal/{zone}/thermostat"));
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(thermostat["heating_setpoint"], json!(20.0), "{thermostat}");
+    assert_eq!(thermostat["min_temperature"], before["min_temperature"], "{thermostat}");
+    let update = json!({"heating_setpoint": 20.0, "cooling_setpoint": 24.0});
+    let (status, _) = call!(
+        app,
+        post,
+        &format!("/houses/106/thermal/{zone}/thermostat"),
+        update
+    );
+    assert_eq!(status, StatusCode::OK);
+    let (status, thermostat) = call!(app, get, &format!("/houses/106/thermal/{zone}/thermostat"));
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(thermostat["heating_setpoint"], json!(20.0), "{thermostat}");
+    let properties = backend.properties("Thermostat-House-106").await.unwrap();
+    assert_eq!(properties["min_target_temp"], json!(20.0), "{properties}");
+    assert_eq!(properties["max_target_temp"], json!(24.0), "{properties}");
+
+    let entry = json!({"day": "Monday", "time": "07:30", "setpoint": 20.0, "mode": "Comfort"});
+    let (status, _) = call!(app, post, "/houses/106/thermal/Nowhere/schedule", entry.clone());
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, comfort) = call!(app, post, &format!("/houses/106/thermal/{zone}/schedule"), entry);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(comfort["time"], json!("07:30"), "{comfort}");
+    let entry = json!({"day": "Monday", "time": "07:00", "setpoint": 16.0, "mode": "Away"});
+    let (status, away) = call!(app, post, &format!("/houses/106/thermal/{zone}/schedule"), entry);
+    assert_eq!(status, StatusCode::OK);
+    let (status, schedule) = call!(app, get, &format!("/houses/106/thermal/{zone}/schedule"));
+    assert_eq!(status, StatusCode::OK, "{schedule}");
+    assert_eq!(schedule, json!([away, comfort]));
+    let (status, _) = call!(app, delete, &format!("/houses/106/thermal/{zone}/schedule"));
+    assert_eq!(status, StatusCode::OK);
+    let (status, schedule) = call!(app, get, &format!("/houses/106/thermal/{zone}/schedule"));
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(schedule, json!([]));
+
+    // heating would only stop 2.5 degrees above its setpoint, past the cooling setpoint of a
+    // comfort entry
+    let update = json!({"heating_setpoint": 18.0, "cooling_setpoint": 24.0, "deadband": [-0.1, 2.5, 0.0, 0.6]});
+    let (status, error) = call!(app, post, &format!("/houses/106/thermal/{zone}/thermostat"), update);
+    assert_eq!(status, StatusCode::OK, "{error}");
+    let entry = json!({"day": "Monday", "time": "07:30", "setpoint": 20.0, "mode": "Comfort"});
+    let (status, error) = call!(app, post, &format!("/houses/106/thermal/{zone}/schedule"), entry);
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{error}");
+    let entry = json!({"day": "Monday", "time": "07:30", "setpoint": 18.0, "mode": "Away"});
+    let (status, _) = call!(app, post, &format!("/houses/106/thermal/{zone}/schedule"), entry);
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn timeshifters() {
+    let registry = registry();
+    let app = app!(107, registry.clone());
+
+    let washer = json!({"name": "Washer", "profile": [{"re": 2000.0, "im": 0.0}], "timeBase": 60});
+    let (status, _) = call!(app, post, "/houses/107/timeshifters/Washer", washer);
+    assert_eq!(status, StatusCode::OK);
+    let (status, list) = call!(app, get, "/houses/107/timeshifters");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(list, json!([{"entity_name": "Washer", "demkit_name": "Washer-House-107"}]));
+
+    let (status, job) = call!(
+        app,
+        post,
+        "/houses/107/timeshifters/Washer/job",
+        json!({"delay": 0, "duration": 3600})
+    );
+    assert_eq!(status, StatusCode::OK, "{job}");
+    assert_eq!(job["state"], json!("pending"), "{job}");
+    let (status, _) = call!(
+        app,
+        post,
+        "/houses/107/timeshifters/Washer/job",
+        json!({"delay": u64::MAX, "duration": 3600})
+    );
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let flexible = json!({"earliest_start": 0, "latest_end": 90_000});
+    let (status, _) = call!(app, post, "/houses/107/timeshifters/Washer/jobs", flexible);
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    // the one minute profile would run past the latest end
+    let flexible = json!({"earliest_start": 3600, "latest_end": 7200, "start_time": 7170});
+    let (status, _) = call!(app, post, "/houses/107/timeshifters/Washer/jobs", flexible);
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let flexible = json!({"earliest_start": 3600, "latest_end": 7200, "start_time": 7140});
+    let (status, flexible) = call!(app, post, "/houses/107/timeshifters/Washer/jobs", flexible);
+    assert_eq!(status, StatusCode::OK, "{flexible}");
+    assert_eq!(flexible["start_time"], json!(7140), "{flexible}");
+    let uri = format!("/houses/107/timeshifters/Washer/jobs/{}/start", flexible["id"]);
+    let (status, _) = call!(app, post, &uri, json!({"start_time": 7170}));
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, jobs) = call!(app, get, "/houses/107/timeshifters/Washer/jobs");
+    assert_eq!(status, StatusCode::OK, "{jobs}");
+    let ids: Vec<&Value> = jobs.as_array().unwrap().iter().map(|job| &job["id"]).collect();
+    assert_eq!(ids, [&job["id"], &flexible["id"]], "{jobs}");
+    let properties = registry.backend(107).properties("Washer-House-107").await.unwrap();
+    assert_eq!(properties["jobs"].as_array().map(Vec::len), Some(2), "{properties}");
+    let (status, washer) = call!(app, get, "/houses/107/timeshifters/Washer");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(washer["scheduled_jobs"], jobs, "{washer}");
+
+    let (status, _) = call!(
+        app,
+        delete,
+        &format!("/houses/107/timeshifters/Washer/job/{}", job["id"])
+    );
+    assert_eq!(status, StatusCode::OK);
+    let (status, jobs) = call!(app, get, "/houses/107/timeshifters/Washer/jobs");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(jobs[0]["state"], json!("cancelled"), "{jobs}");
+    assert_eq!(jobs[1]["state"], json!("pending"), "{jobs}");
+    // the load of the house is no timeshifter, so it stays
+    let (status, _) = call!(app, delete, "/houses/107/timeshifters/Load");
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(registry.backend(107).properties("Load-House-107").await.is_ok());
+    let (status, _) = call!(app, delete, "/houses/107/timeshifters/Washer");
+    assert_eq!(status, StatusCode::OK);
+    let (status, list) = call!(app, get, "/houses/107/timeshifters");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(list, json!([]));
+}
+
+#[actix_web::test]
+async fn ha_entity() {
+    let app = app!(108);
+
+    let entity = json!({"entity_id": "sensor.test_fridge_108", "consumption": "85"});
+    let (status, _) = call!(app, post, "/houses/108/entity", entity);
+    assert_eq!(status, StatusCode::OK);
+    let entities = crate::api::demkit::ha_entity::list_entities(108);
+    assert_eq!(entities, ["sensor.test_fridge_108"]);
+
+    // not among the canned states of the stand-in
+    let (status, _) = call!(app, get, "/houses/108/entity/sensor.test_fridge_108/consumption");
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn ha_calls_are_recorded() {
+    let app = app!(109);
+
+    let (status, _) = call!(app, get, "/houses/109/entity/switch.test_kettle_109/toggle/true");
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, calls) = call!(app, get, "/debug/home-assistant/calls");
+    assert_eq!(status, StatusCode::OK);
+    let expected = json!({
+        "call": "call_service",
+        "domain": "switch",
+        "service": "turn_on",
+        "data": {"entity_id": "switch.test_kettle_109"},
+    });
+    assert!(calls.as_array().unwrap().contains(&expected), "{calls}");
+}
+
+#[actix_web::test]
+async fn composing_again_keeps_the_instance() {
+    let app = app!(110);
+
+    // the host is already composed, so DEMKit refuses it
+    let (status, report) = call!(app, post, "/houses/110");
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR, "{report}");
+    assert_eq!(report["added"], json!([]), "{report}");
+
+    let (status, _) = call!(app, post, "/houses/111");
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[actix_web::test]
+async fn load_reads_the_column_of_the_house() {
+    let registry = registry();
+    let _app = app!(112, registry.clone());
+
+    let load = registry.backend(112).properties("Load-House-112").await.unwrap();
+    assert_eq!(load["column"], json!(112), "{load}");
+}
+
+#[actix_web::test]
+async fn devices_of_another_house_are_refused() {
+    let app = app!(113);
+
+    let (status, _) = call!(app, delete, "/houses/114/battery/1");
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = call!(app, delete, "/houses/114/thermal/Zone/schedule");
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = call!(app, delete, "/houses/113/thermal/Zone/schedule");
+    assert_eq!(status, StatusCode::OK);
+}
@@ -1,21 +1,39 @@
-use actix_web::{get, web, HttpResponse, Responder};
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder, ResponseError};
 use serde::Serialize;
 use serde_json::json;
 use utoipa::ToSchema;
 use utoipa_actix_web::scope;
 
-use crate::api::demkit;
+use crate::api::demkit::{
+    self,
+    backend::DemkitBackend,
+    heating_schedule::{ScheduleEntry, ScheduleEntryParams},
+    registry::Registry,
+    thermal::{ThermostatProperties, ThermostatUpdate, ZoneProperties},
+};
+use crate::api::error::{ErrorBody, UpstreamErrors};
 
 pub fn configure(cfg: &mut utoipa_actix_web::service_config::ServiceConfig) {
+    cfg.service(list);
     cfg.service(
-        scope::scope("/thermal/{id}")
+        scope::scope("/thermal/{zone}")
             .service(get_by_id)
-            .service(set_target_temp),
+            .service(set_target_temp)
+            .service(get_thermostat)
+            .service(update_thermostat)
+            .service(list_schedule)
+            .service(add_schedule_entry)
+            .service(clear_schedule)
+            .service(get_schedule_entry)
+            .service(update_schedule_entry)
+            .service(remove_schedule_entry),
     );
 }
 
 #[derive(Serialize, ToSchema)]
 struct ThermalInfo {
+    /// Zone name
+    zone: String,
     /// Current temperature in the zone in Celsius
     current_temperature: f64,
     /// Target temperature set for the zone in Celsius
@@ -24,45 +42,124 @@ struct ThermalInfo {
     heating_power: f64,
     /// Heat consumption
     consumption: f64,
+    /// Maximum heating power of the heat emitter in the zone
+    max_heating_power: f64,
+}
+
+impl ThermalInfo {
+    fn new(zone: String, zone_info: ZoneProperties, therm_info: &ThermostatProperties) -> Self {
+        ThermalInfo {
+            zone,
+            consumption: zone_info.heat_consumption.norm(),
+            current_temperature: zone_info.temperature,
+            target_temperature: (therm_info.min_target_temp + therm_info.max_target_temp) / 2.0,
+            heating_power: zone_info.valve_heat,
+            max_heating_power: zone_info.max_heat,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct HouseThermalInfo {
+    /// Zones of the house
+    zones: Vec<ThermalInfo>,
+    /// Heating power of all zones together
+    heating_power: f64,
+    /// Heat consumption of all zones together
+    consumption: f64,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ThermostatInfo {
+    /// Temperature below which the zone is heated, in Celsius
+    heating_setpoint: f64,
+    /// Temperature above which the zone is cooled, in Celsius
+    cooling_setpoint: f64,
+    /// Heating setpoint outside the heating intervals, in Celsius
+    min_temperature: f64,
+    /// Cooling setpoint outside the heating intervals, in Celsius
+    max_temperature: f64,
+    /// Offsets from the setpoints at which heating and cooling start and stop, in Celsius
+    deadband: Vec<f64>,
+}
+
+impl From<ThermostatProperties> for ThermostatInfo {
+    fn from(tp: ThermostatProperties) -> Self {
+        ThermostatInfo {
+            heating_setpoint: tp.min_target_temp,
+            cooling_setpoint: tp.max_target_temp,
+            min_temperature: tp.temperature_min,
+            max_temperature: tp.temperature_max,
+            deadband: tp.temperature_deadband,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    tag = "Thermal",
+    description = "List the zones of the house with the heat demand of the whole house.",
+    path = "/thermal",
+    responses(
+        (status = 200, description = "Zones and total heat demand", body = HouseThermalInfo),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+    ),
+)]
+#[get("/thermal")]
+async fn list(registry: web::Data<Registry>, id: web::Path<u32>) -> impl Responder {
+    let house_id = id.into_inner();
+    let backend = registry.backend(house_id);
+
+    let zones = match demkit::thermal::list_zones(backend.as_ref(), house_id).await {
+        Ok(zones) => zones,
+        Err(e) => return e.error_response(),
+    };
+
+    let zones: Vec<ThermalInfo> = zones
+        .into_iter()
+        .map(|(zone, zone_info, therm_info)| ThermalInfo::new(zone, zone_info, &therm_info))
+        .collect();
+
+    HttpResponse::Ok().json(HouseThermalInfo {
+        heating_power: zones.iter().map(|zone| zone.heating_power).sum(),
+        consumption: zones.iter().map(|zone| zone.consumption).sum(),
+        zones,
+    })
 }
 
 #[utoipa::path(
     get,
     tag = "Thermal",
-    description = "Get properties of a thermal device",
+    description = "Get properties of a zone",
     responses(
         (status = 200, description = "Get thermal information", body = ThermalInfo),
-        (status = 500, description = "Failed to get thermal information"),
+        (status = 404, description = "Unknown zone", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
-        ("thermal_id" = u32, description = "Thermal ID"),
+        ("zone" = String, description = "Zone name", example = "Zone"),
     ),
 )]
 #[get("")]
-async fn get_by_id(id: web::Path<(u32, u32)>) -> impl Responder {
-    let (_house_id, thermal_id) = id.into_inner();
+async fn get_by_id(registry: web::Data<Registry>, id: web::Path<(u32, String)>) -> impl Responder {
+    let (house_id, zone) = id.into_inner();
+    let backend = registry.backend(house_id);
 
-    let zone_info = match demkit::thermal::get_current_zone_temp(thermal_id).await {
+    let zone_info = match demkit::thermal::get_current_zone_temp(backend.as_ref(), house_id, &zone).await {
         Ok(properties) => properties,
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {:?}", e)),
+        Err(e) => return e.error_response(),
     };
 
-    let therm_info = match demkit::thermal::get_thermostat_properties(thermal_id).await {
+    let therm_info = match demkit::thermal::get_thermostat_properties(backend.as_ref(), house_id, &zone).await {
         Ok(properties) => properties,
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {:?}", e)),
-    };
-
-    let target_temp = (therm_info.min_target_temp + therm_info.max_target_temp) / 2.0;
-
-    let thermal_info = ThermalInfo {
-        consumption: zone_info.heat_consumption.unwrap().norm(),
-        current_temperature: zone_info.temperature,
-        target_temperature: target_temp,
-        heating_power: zone_info.valve_heat,
+        Err(e) => return e.error_response(),
     };
 
-    HttpResponse::Ok().json(thermal_info)
+    HttpResponse::Ok().json(ThermalInfo::new(zone, zone_info, &therm_info))
 }
 
 #[utoipa::path(
@@ -71,21 +168,293 @@ async fn get_by_id(id: web::Path<(u32, u32)>) -> impl Responder {
     description = "Set target temperature for a thermal device",
     responses(
         (status = 200, description = "Set target temperature successfully"),
-        (status = 500, description = "Failed to set target temperature"),
+        (status = 400, description = "Invalid temperature", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
-        ("thermal_id" = u32, description = "Thermal ID"),
+        ("zone" = String, description = "Zone name", example = "Zone"),
         ("temp" = f64, description = "Target temperature"),
     ),
 )]
 #[get("/target/{temp}")]
-async fn set_target_temp(id: web::Path<(u32, u32, f64)>) -> impl Responder {
-    let (house_id, _thermal_id, temp) = id.into_inner();
-    match demkit::thermal::set_target_temp(house_id, temp).await {
+async fn set_target_temp(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, String, f64)>,
+) -> impl Responder {
+    let (house_id, zone, temp) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+    match demkit::thermal::set_target_temp(backend.as_ref(), house_id, &zone, temp).await {
         Ok(_) => {},
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {:?}", e)),
+        Err(e) => return e.error_response(),
     };
 
     HttpResponse::Ok().json(json!({"target_temperature": temp}))
-}
\ No newline at end of file
+}
+
+#[utoipa::path(
+    get,
+    tag = "Thermal",
+    description = "Get the setpoints and deadband of the thermostat.",
+    responses(
+        (status = 200, description = "Thermostat settings", body = ThermostatInfo),
+        (status = 404, description = "Unknown zone", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("zone" = String, description = "Zone name", example = "Zone"),
+    ),
+)]
+#[get("/thermostat")]
+async fn get_thermostat(registry: web::Data<Registry>, id: web::Path<(u32, String)>) -> impl Responder {
+    let (house_id, zone) = id.into_inner();
+    let backend = registry.backend(house_id);
+
+    match demkit::thermal::get_thermostat_properties(backend.as_ref(), house_id, &zone).await {
+        Ok(properties) => HttpResponse::Ok().json(ThermostatInfo::from(properties)),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    tag = "Thermal",
+    description = "Change the heating and cooling setpoints, the setpoints outside the heating \
+        intervals and the deadband independently. Settings that are not given keep their \
+        current value, the result must keep the heating setpoint at or below the cooling setpoint.",
+    responses(
+        (status = 200, description = "Thermostat updated", body = ThermostatInfo),
+        (status = 400, description = "Invalid setpoints or deadband", body = ErrorBody),
+        (status = 404, description = "Unknown zone", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("zone" = String, description = "Zone name", example = "Zone"),
+    ),
+    request_body = ThermostatUpdate,
+)]
+#[post("/thermostat")]
+async fn update_thermostat(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, String)>,
+    body: web::Json<ThermostatUpdate>,
+) -> impl Responder {
+    let (house_id, zone) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::thermal::update_thermostat(backend.as_ref(), house_id, &zone, body.into_inner()).await {
+        Ok(properties) => HttpResponse::Ok().json(ThermostatInfo::from(properties)),
+        Err(e) => e.error_response(),
+    }
+}
+
+/// Applies a changed schedule right away instead of on the next check; a house that is not
+/// loaded yet gets it once it is
+async fn apply_schedule(backend: &dyn DemkitBackend, house_id: u32, zone: &str) {
+    if let Err(e) = demkit::heating_schedule::apply(backend, house_id, zone).await {
+        log::debug!("Heating schedule of zone {zone} of house {house_id} not applied yet: {e}");
+    }
+}
+
+#[utoipa::path(
+    get,
+    tag = "Thermal",
+    description = "List the weekly heating schedule, in the order the entries take effect.",
+    responses(
+        (status = 200, description = "Heating schedule", body = Vec<ScheduleEntry>),
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("zone" = String, description = "Zone name", example = "Zone"),
+    ),
+)]
+#[get("/schedule")]
+async fn list_schedule(id: web::Path<(u32, String)>) -> impl Responder {
+    let (house_id, zone) = id.into_inner();
+
+    HttpResponse::Ok().json(demkit::heating_schedule::list(house_id, &zone))
+}
+
+#[utoipa::path(
+    post,
+    tag = "Thermal",
+    description = "Add an entry to the weekly heating schedule. The thermostat follows the \
+        schedule as simulation time passes, each entry stays in effect until the next one.",
+    responses(
+        (status = 200, description = "Schedule entry added", body = ScheduleEntry),
+        (status = 400, description = "Invalid schedule entry", body = ErrorBody),
+        (status = 404, description = "Unknown zone", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("zone" = String, description = "Zone name", example = "Zone"),
+    ),
+    request_body = ScheduleEntryParams,
+)]
+#[post("/schedule")]
+async fn add_schedule_entry(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, String)>,
+    body: web::Json<ScheduleEntryParams>,
+) -> impl Responder {
+    let (house_id, zone) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    // entries of a zone without a thermostat would never take effect
+    let thermostat = match demkit::thermal::get_thermostat_properties(backend.as_ref(), house_id, &zone).await {
+        Ok(thermostat) => thermostat,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::heating_schedule::add(house_id, &zone, body.into_inner(), &thermostat) {
+        Ok(entry) => {
+            apply_schedule(backend.as_ref(), house_id, &zone).await;
+            HttpResponse::Ok().json(entry)
+        }
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    tag = "Thermal",
+    description = "Remove the weekly heating schedule, the thermostat keeps its current setpoint.",
+    responses(
+        (status = 200, description = "Schedule removed"),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("zone" = String, description = "Zone name", example = "Zone"),
+    ),
+)]
+#[delete("/schedule")]
+async fn clear_schedule(registry: web::Data<Registry>, id: web::Path<(u32, String)>) -> impl Responder {
+    let (house_id, zone) = id.into_inner();
+    if let Err(e) = registry.check_owner(house_id) {
+        return e.error_response();
+    }
+
+    demkit::heating_schedule::clear(house_id, &zone);
+
+    HttpResponse::Ok().body("Heating schedule removed successfully")
+}
+
+#[utoipa::path(
+    get,
+    tag = "Thermal",
+    description = "Get an entry of the weekly heating schedule.",
+    responses(
+        (status = 200, description = "Schedule entry", body = ScheduleEntry),
+        (status = 404, description = "Unknown schedule entry", body = ErrorBody),
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("zone" = String, description = "Zone name", example = "Zone"),
+        ("entry_id" = u32, description = "Schedule entry ID"),
+    ),
+)]
+#[get("/schedule/{entry_id}")]
+async fn get_schedule_entry(id: web::Path<(u32, String, u32)>) -> impl Responder {
+    let (house_id, zone, entry_id) = id.into_inner();
+
+    match demkit::heating_schedule::get(house_id, &zone, entry_id) {
+        Ok(entry) => HttpResponse::Ok().json(entry),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    tag = "Thermal",
+    description = "Replace an entry of the weekly heating schedule.",
+    responses(
+        (status = 200, description = "Schedule entry updated", body = ScheduleEntry),
+        (status = 400, description = "Invalid schedule entry", body = ErrorBody),
+        (status = 404, description = "Unknown zone or schedule entry", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("zone" = String, description = "Zone name", example = "Zone"),
+        ("entry_id" = u32, description = "Schedule entry ID"),
+    ),
+    request_body = ScheduleEntryParams,
+)]
+#[put("/schedule/{entry_id}")]
+async fn update_schedule_entry(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, String, u32)>,
+    body: web::Json<ScheduleEntryParams>,
+) -> impl Responder {
+    let (house_id, zone, entry_id) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    // entries of a zone without a thermostat would never take effect
+    let thermostat = match demkit::thermal::get_thermostat_properties(backend.as_ref(), house_id, &zone).await {
+        Ok(thermostat) => thermostat,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::heating_schedule::update(house_id, &zone, entry_id, body.into_inner(), &thermostat) {
+        Ok(entry) => {
+            apply_schedule(backend.as_ref(), house_id, &zone).await;
+            HttpResponse::Ok().json(entry)
+        }
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    tag = "Thermal",
+    description = "Remove an entry from the weekly heating schedule.",
+    responses(
+        (status = 200, description = "Schedule entry removed"),
+        (status = 404, description = "Unknown schedule entry", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("zone" = String, description = "Zone name", example = "Zone"),
+        ("entry_id" = u32, description = "Schedule entry ID"),
+    ),
+)]
+#[delete("/schedule/{entry_id}")]
+async fn remove_schedule_entry(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, String, u32)>,
+) -> impl Responder {
+    let (house_id, zone, entry_id) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::heating_schedule::remove(house_id, &zone, entry_id) {
+        Ok(_) => {
+            apply_schedule(backend.as_ref(), house_id, &zone).await;
+            HttpResponse::Ok().body(format!("Schedule entry {entry_id} removed successfully"))
+        }
+        Err(e) => e.error_response(),
+    }
+}
@@ -1,11 +1,19 @@
-use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use actix_web::{delete, get, post, web, HttpResponse, Responder, ResponseError};
 use serde::Serialize;
 use utoipa::ToSchema;
 use utoipa_actix_web::scope;
 
-use crate::api::demkit::{self, env::{InternalComplex, TimeShifterEntityParams}, timeshifters::{Job, ScheduleJob, TimeShifters}, Measurement};
+use crate::api::demkit::{
+    self,
+    env::{InternalComplex, TimeShifterEntityParams},
+    registry::Registry,
+    timeshifters::{FlexibleJobParams, Job, JobStart, ScheduleJob},
+    Measurement,
+};
+use crate::api::error::{ErrorBody, UpstreamErrors};
 
 pub fn configure(cfg: &mut utoipa_actix_web::service_config::ServiceConfig) {
+    cfg.service(list);
     cfg.service(
         scope::scope("/timeshifters/{entity_name}")
             .service(get_by_id)
@@ -13,10 +21,48 @@ pub fn configure(cfg: &mut utoipa_actix_web::service_config::ServiceConfig) {
             .service(remove_by_id)
             .service(schedule_job)
             .service(cancel_job)
+            .service(list_jobs)
+            .service(add_flexible_job)
+            .service(set_job_start)
             .service(force_shutdown),
     );
 }
 
+#[derive(Serialize, ToSchema)]
+struct TimeShifterSummary {
+    /// Name of the timeshifter entity, used in the timeshifter routes
+    entity_name: String,
+    /// Name of the entity in DEMKit
+    demkit_name: String,
+}
+
+#[utoipa::path(
+    get,
+    tag = "Timeshifters",
+    description = "List the timeshifters added to the house through this service.",
+    path = "/timeshifters",
+    responses(
+        (status = 200, description = "Timeshifters of the house", body = Vec<TimeShifterSummary>),
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+    )
+)]
+#[get("/timeshifters")]
+async fn list(id: web::Path<u32>) -> impl Responder {
+    let house_id = id.into_inner();
+
+    let timeshifters: Vec<TimeShifterSummary> = demkit::timeshifters::list(house_id)
+        .into_iter()
+        .map(|entity_name| TimeShifterSummary {
+            demkit_name: format!("{entity_name}-House-{house_id}"),
+            entity_name,
+        })
+        .collect();
+
+    HttpResponse::Ok().json(timeshifters)
+}
+
 #[derive(Serialize, ToSchema)]
 struct DeviceStatus {
     /// House ID
@@ -33,7 +79,7 @@ struct DeviceStatus {
     progress: f64,
     /// The index of the currently active job
     active_job_idx: i32,
-    /// The list of scheduled jobs
+    /// All jobs of the timeshifter with their IDs, states and energy, cancelled ones included
     scheduled_jobs: Vec<Job>,
     /// The current electricity consumption of the timeshifter
     consumption: Measurement,
@@ -46,8 +92,8 @@ struct DeviceStatus {
     description = "Get timeshifter properties.",
     responses(
         (status = 200, description = "Get timeshifter properties", body = DeviceStatus),
-        (status = 400, description = "Invalid entity name"),
-        (status = 500, description = "Internal server error"),
+        (status = 404, description = "Unknown timeshifter", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
@@ -55,21 +101,20 @@ struct DeviceStatus {
     )
 )]
 #[get("")]
-async fn get_by_id(id: web::Path<(u32, String)>) -> impl Responder {
+async fn get_by_id(registry: web::Data<Registry>, id: web::Path<(u32, String)>) -> impl Responder {
     let (house_id, entity_name) = id.into_inner();
+    let backend = registry.backend(house_id);
 
-    let timeshifter = match TimeShifters::try_from(entity_name.as_str()) {
-        Ok(ts) => ts,
-        Err(e) => return HttpResponse::BadRequest().body(format!("Error: {:?}", e)),
-    };
-
-    let device_properties = match demkit::timeshifters::get_properties(house_id, timeshifter).await {
+    let device_properties = match demkit::timeshifters::get_properties(backend.as_ref(), house_id, &entity_name).await {
         Ok(properties) => properties,
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {:?}", e)),
+        Err(e) => return e.error_response(),
     };
 
-    let device_profile = device_properties.device_profile.unwrap();
-    let profile_length = device_profile.len();
+    let device_profile = device_properties.device_profile;
+    let progress = match device_profile.len() {
+        0 => 0.0,
+        profile_length => device_properties.job_progress / (profile_length as f64) * 100.0,
+    };
 
     let device_status = DeviceStatus {
         house_id,
@@ -77,10 +122,10 @@ async fn get_by_id(id: web::Path<(u32, String)>) -> impl Responder {
         is_active: device_properties.available,
         active_job: if device_properties.available { device_properties.current_job } else { None },
         active_job_idx: device_properties.current_job_idx,
-        progress: device_properties.job_progress / (profile_length as f64) * 100.0,
+        progress,
         scheduled_jobs: device_properties.jobs,
         consumption: Measurement {
-            value: device_properties.electricity_consumption.unwrap().norm(),
+            value: device_properties.electricity_consumption.norm(),
             unit: "W".to_string(),
         },
         profile: device_profile
@@ -96,10 +141,12 @@ async fn get_by_id(id: web::Path<(u32, String)>) -> impl Responder {
 #[utoipa::path(
     post,
     tag = "Timeshifters",
-    description = "Add a new timeshifter entity.",
+    description = "Add a new timeshifter entity, named after the path.",
     responses(
         (status = 200, description = "Successfully added timeshifter entity", body = String),
-        (status = 500, description = "Internal server error"),
+        (status = 400, description = "Entity rejected by DEMKit", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
@@ -108,12 +155,24 @@ async fn get_by_id(id: web::Path<(u32, String)>) -> impl Responder {
     request_body = TimeShifterEntityParams,
 )]
 #[post("")]
-async fn add_by_id(id: web::Path<(u32, String)>, params: web::Json<TimeShifterEntityParams>) -> impl Responder {
+async fn add_by_id(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, String)>,
+    params: web::Json<TimeShifterEntityParams>,
+) -> impl Responder {
     let (house_id, entity_name) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    // the path names the timeshifter in every other route, so it wins over the body
+    let mut params = params.into_inner();
+    params.name = entity_name.clone();
 
-    match demkit::env::add_timeshifter(house_id, params.into_inner()).await {
+    match demkit::env::add_timeshifter(backend.as_ref(), house_id, params).await {
         Ok(_) => HttpResponse::Ok().body(format!("{entity_name} added successfully")),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {:?}", e)),
+        Err(e) => return e.error_response(),
     };
 
     HttpResponse::Ok().body(format!("{entity_name} added successfully"))
@@ -125,7 +184,9 @@ async fn add_by_id(id: web::Path<(u32, String)>, params: web::Json<TimeShifterEn
     description = "Remove a timeshifter entity from the house.",
     responses(
         (status = 200, description = "Successfully removed timeshifter entity", body = String),
-        (status = 500, description = "Internal server error"),
+        (status = 404, description = "Unknown timeshifter", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
@@ -133,12 +194,19 @@ async fn add_by_id(id: web::Path<(u32, String)>, params: web::Json<TimeShifterEn
     ),
 )]
 #[delete("")]
-async fn remove_by_id(id: web::Path<(u32, String)>) -> impl Responder {
+async fn remove_by_id(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, String)>,
+) -> impl Responder {
     let (house_id, entity_name) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
 
-    match demkit::env::remove_entity(house_id, entity_name.as_str()).await {
+    match demkit::timeshifters::remove(backend.as_ref(), house_id, &entity_name).await {
         Ok(_) => HttpResponse::Ok().body(format!("{entity_name} removed successfully")),
-        Err(e) => HttpResponse::InternalServerError().body(format!("Error: {:?}", e)),
+        Err(e) => e.error_response(),
     }
 }
 
@@ -148,7 +216,10 @@ async fn remove_by_id(id: web::Path<(u32, String)>) -> impl Responder {
     description = "Schedule a job for a timeshifter entity",
     responses(
         (status = 200, description = "Successfully scheduled job for timeshifter entity", body = Job),
-        (status = 500, description = "Internal server error"),
+        (status = 400, description = "Job rejected by DEMKit", body = ErrorBody),
+        (status = 404, description = "Unknown timeshifter", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
@@ -157,17 +228,20 @@ async fn remove_by_id(id: web::Path<(u32, String)>) -> impl Responder {
     request_body = ScheduleJob,
 )]
 #[post("/job")]
-async fn schedule_job(id: web::Path<(u32, String)>, body: web::Json<ScheduleJob>) -> impl Responder {
+async fn schedule_job(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, String)>,
+    body: web::Json<ScheduleJob>,
+) -> impl Responder {
     let (house_id, entity_name) = id.into_inner();
-
-    let timeshifter = match TimeShifters::try_from(entity_name.as_str()) {
-        Ok(ts) => ts,
-        Err(e) => return HttpResponse::BadRequest().body(format!("Error: {:?}", e)),
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
     };
 
-    match demkit::timeshifters::schedule_job(house_id, timeshifter, body.into_inner()).await {
+    match demkit::timeshifters::schedule_job(backend.as_ref(), house_id, &entity_name, body.into_inner()).await {
         Ok(job) => HttpResponse::Ok().json(job),
-        Err(e) => HttpResponse::InternalServerError().body(format!("Error: {:?}", e)),
+        Err(e) => e.error_response(),
     }
 }
 
@@ -177,7 +251,10 @@ async fn schedule_job(id: web::Path<(u32, String)>, body: web::Json<ScheduleJob>
     description = "Cancel a scheduled job for a timeshifter entity",
     responses(
         (status = 200, description = "Successfully scheduled job for timeshifter entity", body = String),
-        (status = 500, description = "Internal server error"),
+        (status = 400, description = "Job not found", body = ErrorBody),
+        (status = 404, description = "Unknown timeshifter", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
@@ -186,17 +263,117 @@ async fn schedule_job(id: web::Path<(u32, String)>, body: web::Json<ScheduleJob>
     ),
 )]
 #[delete("/job/{id}")]
-async fn cancel_job(id: web::Path<(u32, String, u32)>) -> impl Responder {
+async fn cancel_job(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, String, u32)>,
+) -> impl Responder {
     let (house_id, entity_name, job_id) = id.into_inner();
-
-    let timeshifter = match TimeShifters::try_from(entity_name.as_str()) {
-        Ok(ts) => ts,
-        Err(e) => return HttpResponse::BadRequest().body(format!("Error: {:?}", e)),
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
     };
 
-    match demkit::timeshifters::cancel_job(house_id, timeshifter, job_id).await {
+    match demkit::timeshifters::cancel_job(backend.as_ref(), house_id, &entity_name, job_id).await {
         Ok(_) => HttpResponse::Ok().body(format!("Job {job_id} cancelled for {entity_name}")),
-        Err(e) => HttpResponse::InternalServerError().body(format!("Error: {:?}", e)),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    tag = "Timeshifters",
+    description = "List the jobs of a timeshifter entity with their windows and start times.",
+    responses(
+        (status = 200, description = "Jobs of the timeshifter", body = Vec<Job>),
+        (status = 404, description = "Unknown timeshifter", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("entity_name" = String, description = "Name of the timeshifter entity"),
+    ),
+)]
+#[get("/jobs")]
+async fn list_jobs(registry: web::Data<Registry>, id: web::Path<(u32, String)>) -> impl Responder {
+    let (house_id, entity_name) = id.into_inner();
+    let backend = registry.backend(house_id);
+
+    match demkit::timeshifters::list_jobs(backend.as_ref(), house_id, &entity_name).await {
+        Ok(jobs) => HttpResponse::Ok().json(jobs),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    tag = "Timeshifters",
+    description = "Submit a job that may run anywhere between its earliest start and latest end. \
+        Without a start time DEMKit plans the start, or starts the job right away when the \
+        timeshifter has no planner.",
+    responses(
+        (status = 200, description = "Job added", body = Job),
+        (status = 400, description = "Job rejected", body = ErrorBody),
+        (status = 404, description = "Unknown timeshifter", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("entity_name" = String, description = "Name of the timeshifter entity"),
+    ),
+    request_body = FlexibleJobParams,
+)]
+#[post("/jobs")]
+async fn add_flexible_job(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, String)>,
+    body: web::Json<FlexibleJobParams>,
+) -> impl Responder {
+    let (house_id, entity_name) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::timeshifters::add_flexible_job(backend.as_ref(), house_id, &entity_name, body.into_inner()).await {
+        Ok(job) => HttpResponse::Ok().json(job),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    tag = "Timeshifters",
+    description = "Pick the start of a job that has not started yet, within its window.",
+    responses(
+        (status = 200, description = "Start time set", body = Job),
+        (status = 400, description = "Start time outside the window or job already started", body = ErrorBody),
+        (status = 404, description = "Unknown timeshifter or job", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
+    ),
+    params(
+        ("house_id" = u32, description = "House ID"),
+        ("entity_name" = String, description = "Name of the timeshifter entity"),
+        ("job_id" = u32, description = "Job ID"),
+    ),
+    request_body = JobStart,
+)]
+#[post("/jobs/{job_id}/start")]
+async fn set_job_start(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, String, u32)>,
+    body: web::Json<JobStart>,
+) -> impl Responder {
+    let (house_id, entity_name, job_id) = id.into_inner();
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
+    };
+
+    match demkit::timeshifters::set_job_start(backend.as_ref(), house_id, &entity_name, job_id, body.into_inner()).await {
+        Ok(job) => HttpResponse::Ok().json(job),
+        Err(e) => e.error_response(),
     }
 }
 
@@ -206,7 +383,9 @@ async fn cancel_job(id: web::Path<(u32, String, u32)>) -> impl Responder {
     description = "Force shutdown timeshifter entity immediately, potentially canceling and discarding any active jobs.",
     responses(
         (status = 200, description = "Successfully shut down timeshifter entity", body = String),
-        (status = 500, description = "Internal server error"),
+        (status = 404, description = "Unknown timeshifter", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("house_id" = u32, description = "House ID"),
@@ -214,16 +393,18 @@ async fn cancel_job(id: web::Path<(u32, String, u32)>) -> impl Responder {
     ),
 )]
 #[get("/shutdown")]
-async fn force_shutdown(id: web::Path<(u32, String)>) -> impl Responder {
+async fn force_shutdown(
+    registry: web::Data<Registry>,
+    id: web::Path<(u32, String)>,
+) -> impl Responder {
     let (house_id, entity_name) = id.into_inner();
-
-    let timeshifter = match TimeShifters::try_from(entity_name.as_str()) {
-        Ok(ts) => ts,
-        Err(e) => return HttpResponse::BadRequest().body(format!("Error: {:?}", e)),
+    let backend = match registry.owned_backend(house_id) {
+        Ok(backend) => backend,
+        Err(e) => return e.error_response(),
     };
 
-    match demkit::timeshifters::force_shutdown(house_id, timeshifter).await {
+    match demkit::timeshifters::force_shutdown(backend.as_ref(), house_id, &entity_name).await {
         Ok(_) => HttpResponse::Ok().body(format!("Shutdown successful for {entity_name}")),
-        Err(e) => HttpResponse::InternalServerError().body(format!("Error: {:?}", e)),
+        Err(e) => e.error_response(),
     }
 }
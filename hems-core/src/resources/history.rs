@@ -0,0 +1,118 @@
+use std::sync::Arc;
+
+use actix_web::{get, http::header, web, HttpResponse, Responder, ResponseError};
+
+use crate::api::error::ErrorBody;
+use crate::api::export::{self, ExportFormat, ExportQuery};
+use crate::api::history::{History, HistoryQuery, Series, SeriesHistory};
+
+pub fn configure(cfg: &mut utoipa_actix_web::service_config::ServiceConfig) {
+    cfg.service(get_history);
+    cfg.service(list_series);
+    cfg.service(get_export);
+}
+
+#[utoipa::path(
+    get,
+    tag = "History",
+    description = "Get the sampled history of the house devices, aggregated into buckets of `resolution` seconds of simulation time with the mean, minimum and maximum of each bucket.",
+    path = "/history",
+    responses(
+        (status = 200, description = "History of every matching series", body = Vec<SeriesHistory>),
+        (status = 400, description = "Invalid range or resolution", body = ErrorBody),
+        (status = 500, description = "History database error", body = ErrorBody),
+    ),
+    params(
+        ("id", description = "House ID", example = 1),
+        HistoryQuery,
+    ),
+)]
+#[get("/history")]
+async fn get_history(
+    history: web::Data<History>,
+    path: web::Path<u32>,
+    query: web::Query<HistoryQuery>,
+) -> impl Responder {
+    let house_id = path.into_inner();
+    let query = query.into_inner();
+
+    match history.into_inner().blocking(move |history| history.query(house_id, &query)).await {
+        Ok(history) => HttpResponse::Ok().json(history),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    tag = "History",
+    description = "List the series sampled for the house",
+    path = "/history/series",
+    responses(
+        (status = 200, description = "Sampled series", body = Vec<Series>),
+        (status = 500, description = "History database error", body = ErrorBody),
+    ),
+    params(
+        ("id", description = "House ID", example = 1),
+    ),
+)]
+#[get("/history/series")]
+async fn list_series(history: web::Data<History>, path: web::Path<u32>) -> impl Responder {
+    let house_id = path.into_inner();
+
+    match history.into_inner().blocking(move |history| history.list_series(house_id)).await {
+        Ok(series) => HttpResponse::Ok().json(series),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    tag = "History",
+    description = "Export every sampled series of the house as a wide table with a row per simulation timestamp and a column per `{device}.{quantity}`, together with the simulation config and house spec the house was last configured and composed with. CSV carries them in `#` comment lines above the header, Parquet in the file metadata.",
+    path = "/export",
+    responses(
+        (status = 200, description = "CSV export", content_type = "text/csv"),
+        (status = 200, description = "Parquet export", content_type = "application/vnd.apache.parquet"),
+        (status = 400, description = "Invalid range or format", body = ErrorBody),
+        (status = 500, description = "History database or encoding error", body = ErrorBody),
+    ),
+    params(
+        ("id", description = "House ID", example = 1),
+        ExportQuery,
+    ),
+)]
+#[get("/export")]
+async fn get_export(
+    history: web::Data<History>,
+    path: web::Path<u32>,
+    query: web::Query<ExportQuery>,
+) -> impl Responder {
+    let house_id = path.into_inner();
+    let (from, to) = (query.from, query.to);
+    let history = history.into_inner();
+
+    let (run, columns) = match history
+        .clone()
+        .blocking(move |history| Ok((history.run_info(house_id)?, history.wide_columns(house_id, from, to)?)))
+        .await
+    {
+        Ok(export) => export,
+        Err(e) => return e.error_response(),
+    };
+    let columns = Arc::new(columns);
+    let pages = export::pages(history, house_id, columns.clone(), from, to);
+
+    let mut response = HttpResponse::Ok();
+    response.content_type(query.format.content_type()).insert_header((
+        header::CONTENT_DISPOSITION,
+        format!("attachment; filename=\"house-{house_id}.{}\"", query.format.extension()),
+    ));
+
+    match query.format {
+        ExportFormat::Csv => response.streaming(export::csv_stream(house_id, &run, &columns, pages)),
+        ExportFormat::Parquet => match export::parquet_stream(house_id, &run, &columns, pages) {
+            Ok(body) => response.streaming(body),
+            Err(e) => e.error_response(),
+        },
+    }
+}
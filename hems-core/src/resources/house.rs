@@ -1,18 +1,32 @@
-use std::vec;
-
-use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use actix_web::{
+    delete, get, http::StatusCode, post, put, web, HttpMessage, HttpRequest, HttpResponse, Responder,
+    ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use utoipa_actix_web::scope;
 
 #[path = "devices/devices.rs"]
 pub mod devices;
-pub use devices::{battery, ha_entity, meter, solar, thermal, timeshifters};
-
-use crate::api::demkit;
+pub use devices::{battery, dhw, ev, ha_entity, heatpump, meter, solar, thermal, timeshifters};
+
+use super::history;
+use super::spec::{self, HouseSpec, SpecFormat};
+use crate::api::demkit::{
+    self,
+    backend::DemkitBackend,
+    registry::{HouseBackend, Registry},
+};
+use crate::api::error::{ErrorBody, UpstreamErrors};
+use crate::api::history::History;
+use crate::config::HousesConfig;
 
 pub fn configure(cfg: &mut utoipa_actix_web::service_config::ServiceConfig) {
+    cfg.service(list_houses);
     cfg.service(
         scope::scope("/houses/{id}")
             .service(get_by_id)
+            .service(set_backend)
             .service(get_time)
             .service(compose)
             .service(reset)
@@ -24,11 +38,15 @@ pub fn configure(cfg: &mut utoipa_actix_web::service_config::ServiceConfig) {
             .service(stop_simulation)
             .service(set_time)
             .configure(battery::configure)
+            .configure(ev::configure)
+            .configure(heatpump::configure)
+            .configure(dhw::configure)
             .configure(meter::configure)
             .configure(solar::configure)
             .configure(thermal::configure)
             .configure(timeshifters::configure)
-            .configure(ha_entity::configure),
+            .configure(ha_entity::configure)
+            .configure(history::configure),
     );
 }
 
@@ -52,132 +70,212 @@ async fn get_by_id(path: web::Path<u32>) -> impl Responder {
 }
 
 #[utoipa::path(
-    post,
+    get,
     tag = "House",
-    description = "Compose a house",
-    path = "",
+    description = "List the houses with a registered or composed DEMKit backend",
+    path = "/houses",
+    responses(
+        (status = 200, description = "Houses and their DEMKit backends", body = Vec<HouseBackend>),
+    ),
+)]
+#[get("/houses")]
+async fn list_houses(registry: web::Data<Registry>) -> impl Responder {
+    HttpResponse::Ok().json(registry.list())
+}
+
+#[derive(Deserialize, ToSchema)]
+struct BackendRequest {
+    /// Base URL of the DEMKit instance, e.g. `http://demkit-2:5000`
+    url: String,
+}
+
+#[utoipa::path(
+    put,
+    tag = "House",
+    description = "Register the DEMKit instance that simulates the house",
+    path = "/backend",
+    request_body = BackendRequest,
     responses(
-        (status = 200, description = "House composed successfully"),
-        (status = 500, description = "Error composing house"),
+        (status = 200, description = "Backend registered successfully"),
+        (status = 409, description = "House is composed on its current backend", body = ErrorBody),
     ),
     params(
         ("id", description = "House ID", example = 1),
     ),
 )]
-#[post("")]
-async fn compose(path: web::Path<u32>) -> impl Responder {
+#[put("/backend")]
+async fn set_backend(
+    registry: web::Data<Registry>,
+    path: web::Path<u32>,
+    body: web::Json<BackendRequest>,
+) -> impl Responder {
     let house_id = path.into_inner();
-    match demkit::env::add_host(house_id).await {
-        Ok(_) => println!("Host added successfully"),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
-    };
 
-    match demkit::env::add_weather(house_id).await {
-        Ok(_) => println!("Weather added successfully"),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
-    };
-
-    match demkit::env::add_sun(house_id).await {
-        Ok(_) => println!("Sun added successfully"),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
-    };
-
-    let sm_params = demkit::env::MeterEntityParams {
-        name: "SmartMeter".to_string(),
-        commodities: vec!["ELECTRICITY".to_string()],
-        weights: vec![("ELECTRICITY".to_string(), 1.0)],
-    };
-
-    match demkit::env::add_meter(house_id, sm_params).await {
-        Ok(_) => println!("Meter added successfully"),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
-    };
-
-    let gm_params = demkit::env::MeterEntityParams {
-        name: "SmartGasMeter".to_string(),
-        commodities: vec!["NATGAS".to_string()],
-        weights: vec![("NATGAS".to_string(), 1.0)],
-    };
-
-    match demkit::env::add_meter(house_id, gm_params).await {
-        Ok(_) => println!("Meter added successfully"),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
-    };
+    match registry.register(house_id, &body.url) {
+        Ok(_) => HttpResponse::Ok().body(format!("Backend of house {house_id} registered successfully")),
+        Err(e) => e.error_response(),
+    }
+}
 
-    let curt_params = demkit::env::CurtEntityParams {
-        name: "Load".to_string(),
-        filename: "sampledata/singlehouse/Electricity_Profile.csv".to_string(),
-        filename_reactive: "sampledata/singlehouse/Reactive_Electricity_Profile.csv".to_string(),
-        column: house_id as u64,
-        time_base: 60,
-    };
+#[derive(Serialize, ToSchema)]
+struct EntityFailure {
+    /// Name of the entity in DEMKit
+    entity: String,
+    /// Error reported while adding or removing the entity
+    error: String,
+}
 
-    match demkit::env::add_curt(house_id, curt_params).await {
-        Ok(_) => println!("Curt added successfully"),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
-    };
+#[derive(Serialize, ToSchema, Default)]
+struct ComposeReport {
+    /// Entities added to the house, in order
+    added: Vec<String>,
+    /// The entity that could not be added, if composition failed
+    #[schema(nullable)]
+    failed: Option<EntityFailure>,
+    /// Entities removed again after the failure
+    rolled_back: Vec<String>,
+    /// Entities that could not be removed after the failure
+    rollback_failed: Vec<EntityFailure>,
+    /// Whether the composer was reset because the rollback was incomplete
+    reset: bool,
+    /// Why that reset failed; the house keeps its DEMKit instance until it is reset
+    #[schema(nullable)]
+    reset_error: Option<String>,
+}
 
-    let zone_params = demkit::env::ZoneEntityParams {
-        name: "Zone".to_string(),
-        r_floor: 0.001,
-        r_envelope: 0.0064,
-        c_floor: 5100.0 * 3600.0,
-        c_zone: 21100.0 * 3600.0,
-        initial_temperature: 18.5,
-    };
+#[derive(Deserialize, IntoParams)]
+struct ComposeQuery {
+    /// Name of a house spec in the spec directory, used when no spec is posted
+    spec: Option<String>,
+}
 
-    match demkit::env::add_zone(house_id, zone_params).await {
-        Ok(_) => println!("Zone added successfully"),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
-    };
+#[utoipa::path(
+    post,
+    tag = "House",
+    description = "Compose a house from a house spec (JSON, or TOML with a toml content type). Without a body the named spec is loaded from the spec directory.",
+    path = "",
+    request_body(content = HouseSpec, description = "House spec"),
+    responses(
+        (status = 200, description = "House composed successfully", body = ComposeReport),
+        (status = 400, description = "Invalid house spec", body = ErrorBody),
+        (status = 404, description = "Unknown house spec", body = ErrorBody),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        (status = 500, description = "Error composing house, added entities were rolled back", body = ComposeReport),
+    ),
+    params(
+        ("id", description = "House ID", example = 1),
+        ComposeQuery,
+    ),
+)]
+#[post("")]
+async fn compose(
+    registry: web::Data<Registry>,
+    history: web::Data<History>,
+    houses: web::Data<HousesConfig>,
+    path: web::Path<u32>,
+    query: web::Query<ComposeQuery>,
+    request: HttpRequest,
+    body: web::Bytes,
+) -> impl Responder {
+    let house_id = path.into_inner();
 
-    let thermostat_params = demkit::env::ThermostatEntityParams {
-        name: "Thermostat".to_string(),
-        temperature_setpoint_heating: 21.0,
-        temperature_setpoint_cooling: 23.0,
-        temperature_min: 21.0,
-        temperature_max: 23.0,
-        temperature_deadband: vec![-0.1, 0.0, 0.5, 0.6],
-        preheating_time: 3600.0,
+    let spec = if body.is_empty() {
+        HouseSpec::load(&houses.spec_dir, query.spec.as_deref().unwrap_or(spec::DEFAULT_SPEC))
+    } else {
+        match std::str::from_utf8(&body) {
+            Ok(input) => HouseSpec::parse(input, SpecFormat::from_content_type(request.content_type())),
+            Err(e) => {
+                return ErrorBody::new("invalid_spec", "House spec is not valid UTF-8", Some(e.to_string()))
+                    .response(StatusCode::BAD_REQUEST)
+            }
+        }
     };
 
-    match demkit::env::add_thermostat(house_id, thermostat_params).await {
-        Ok(_) => println!("Thermostat added successfully"),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
+    let spec = match spec {
+        Ok(spec) => spec,
+        Err(e) => return e.error_response(),
     };
 
-    let dhw_params = demkit::env::DhwEntityParams {
-        name: "DomesticHotWater".to_string(),
+    let claimed = match registry.claim(house_id) {
+        Ok(claimed) => claimed,
+        Err(e) => return e.error_response(),
     };
 
-    match demkit::env::add_dhw(house_id, dhw_params).await {
-        Ok(_) => println!("DHW added successfully"),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
-    };
+    let backend = registry.backend(house_id);
+    let composition = serde_json::to_value(&spec).unwrap_or_default();
+    let report = compose_spec(backend.as_ref(), house_id, spec).await;
+
+    match report.failed {
+        None => {
+            let record = move |history: &History| history.record_composition(house_id, &composition);
+            if let Err(e) = history.into_inner().blocking(record).await {
+                log::warn!("Failed to record the composition of house {house_id}: {e}");
+            }
+            HttpResponse::Ok().json(report)
+        }
+        Some(_) => {
+            // a composer still holding entities of this house must not be handed to another one,
+            // nor one the house already held before this request
+            if report.reset || (claimed && report.rollback_failed.is_empty()) {
+                registry.release(house_id);
+            }
+            HttpResponse::InternalServerError().json(report)
+        }
+    }
+}
 
-    let heat_source_params = demkit::env::HeatSourceEntityParams {
-        name: "HeatPump".to_string(),
-        // producing_temperatures: vec![0.0, 35.0],
-        // producing_powers: vec![0.0, 4500.0]
-    };
+/// Adds all entities of the spec, removing the ones already added again when one of them fails
+async fn compose_spec(backend: &dyn DemkitBackend, house_id: u32, spec: HouseSpec) -> ComposeReport {
+    let mut report = ComposeReport::default();
+
+    for params in spec.entities {
+        let entity = params.house_entity_name(house_id);
+
+        match demkit::env::add_entity(backend, house_id, params).await {
+            Ok(name) => {
+                log::info!("{name} added successfully");
+                report.added.push(name);
+            }
+            Err(e) => {
+                report.failed = Some(EntityFailure {
+                    entity,
+                    error: e.to_string(),
+                });
+                break;
+            }
+        };
+    }
 
-    match demkit::env::add_heat_source(house_id, heat_source_params).await {
-        Ok(_) => println!("Heat Source added successfully"),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
-    };
+    if report.failed.is_none() {
+        return report;
+    }
 
-    let heat_pump_params = demkit::env::HeatPumpEntityParams {
-        name: "DomesticHotWaterControllerBoiler".to_string(),
-        producing_temperatures: vec![0.0, 60.0],
-        producing_powers: vec![0.0, 25000.0],
-    };
+    for name in report.added.iter().rev() {
+        match demkit::env::remove_entity_by_name(backend, name).await {
+            Ok(_) => report.rolled_back.push(name.clone()),
+            Err(e) => report.rollback_failed.push(EntityFailure {
+                entity: name.clone(),
+                error: e.to_string(),
+            }),
+        }
+    }
 
-    match demkit::env::add_heat_pump(house_id, heat_pump_params).await {
-        Ok(_) => println!("Heat Pump added successfully"),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
-    };
+    // the composer still holds entities of this house, start from a clean slate instead
+    if !report.rollback_failed.is_empty() {
+        match demkit::env::reset(backend).await {
+            Ok(_) => {
+                report.reset = true;
+                demkit::timeshifters::unregister_house(house_id);
+                demkit::ha_entity::unregister_house(house_id);
+            }
+            Err(e) => {
+                log::warn!("Failed to reset house {house_id} after an incomplete rollback: {e}");
+                report.reset_error = Some(e.to_string());
+            }
+        }
+    }
 
-    HttpResponse::Ok().body("House composed successfully")
+    report
 }
 
 #[utoipa::path(
@@ -187,18 +285,24 @@ async fn compose(path: web::Path<u32>) -> impl Responder {
     path = "/pause",
     responses(
         (status = 200, description = "House paused successfully"),
-        (status = 500, description = "Error pausing house"),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("id", description = "House ID", example = 1),
     ),
 )]
 #[post("/pause")]
-async fn pause_simulation(path: web::Path<u32>) -> impl Responder {
-    let _house_id = path.into_inner();
-    match demkit::sim::pause_simulation().await {
-        Ok(_) => return HttpResponse::Ok().body(format!("House {} paused successfully", _house_id)),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
+async fn pause_simulation(registry: web::Data<Registry>, path: web::Path<u32>) -> impl Responder {
+    let house_id = path.into_inner();
+
+    if let Err(e) = registry.check_owner(house_id) {
+        return e.error_response();
+    }
+
+    match demkit::sim::pause_simulation(registry.backend(house_id).as_ref()).await {
+        Ok(_) => HttpResponse::Ok().body(format!("House {} paused successfully", house_id)),
+        Err(e) => e.error_response(),
     }
 }
 
@@ -209,18 +313,24 @@ async fn pause_simulation(path: web::Path<u32>) -> impl Responder {
     path = "/resume",
     responses(
         (status = 200, description = "House resumed successfully"),
-        (status = 500, description = "Error resuming house"),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("id", description = "House ID", example = 1),
     ),
 )]
 #[post("/resume")]
-async fn resume_simulation(path: web::Path<u32>) -> impl Responder {
-    let _house_id = path.into_inner();
-    match demkit::sim::resume_simulation().await {
-        Ok(_) => return HttpResponse::Ok().body(format!("House {} resumed successfully", _house_id)),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
+async fn resume_simulation(registry: web::Data<Registry>, path: web::Path<u32>) -> impl Responder {
+    let house_id = path.into_inner();
+
+    if let Err(e) = registry.check_owner(house_id) {
+        return e.error_response();
+    }
+
+    match demkit::sim::resume_simulation(registry.backend(house_id).as_ref()).await {
+        Ok(_) => HttpResponse::Ok().body(format!("House {} resumed successfully", house_id)),
+        Err(e) => e.error_response(),
     }
 }
 
@@ -231,18 +341,24 @@ async fn resume_simulation(path: web::Path<u32>) -> impl Responder {
     path = "/stop",
     responses(
         (status = 200, description = "House stopped successfully"),
-        (status = 500, description = "Error stopping house"),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("id", description = "House ID", example = 1),
     ),
 )]
 #[post("/stop")]
-async fn stop_simulation(path: web::Path<u32>) -> impl Responder {
-    let _house_id = path.into_inner();
-    match demkit::sim::stop_simulation().await {
-        Ok(_) => return HttpResponse::Ok().body(format!("House {} stopped successfully", _house_id)),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
+async fn stop_simulation(registry: web::Data<Registry>, path: web::Path<u32>) -> impl Responder {
+    let house_id = path.into_inner();
+
+    if let Err(e) = registry.check_owner(house_id) {
+        return e.error_response();
+    }
+
+    match demkit::sim::stop_simulation(registry.backend(house_id).as_ref()).await {
+        Ok(_) => HttpResponse::Ok().body(format!("House {} stopped successfully", house_id)),
+        Err(e) => e.error_response(),
     }
 }
 
@@ -253,18 +369,29 @@ async fn stop_simulation(path: web::Path<u32>) -> impl Responder {
     path = "/time",
     responses(
         (status = 200, description = "House time set successfully"),
-        (status = 500, description = "Error setting house time"),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        (status = 400, description = "Time rejected by DEMKit", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("id", description = "House ID", example = 1),
     ),
 )]
 #[post("/time")]
-async fn set_time(path: web::Path<u32>, time: web::Json<demkit::sim::Time>) -> impl Responder {
-    let _house_id = path.into_inner();
-    match demkit::sim::set_time(time.into_inner()).await {
-        Ok(_) => println!("House time set successfully"),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
+async fn set_time(
+    registry: web::Data<Registry>,
+    path: web::Path<u32>,
+    time: web::Json<demkit::sim::Time>,
+) -> impl Responder {
+    let house_id = path.into_inner();
+
+    if let Err(e) = registry.check_owner(house_id) {
+        return e.error_response();
+    }
+
+    match demkit::sim::set_time(registry.backend(house_id).as_ref(), time.into_inner()).await {
+        Ok(_) => log::debug!("House {house_id} time set successfully"),
+        Err(e) => return e.error_response(),
     }
 
     HttpResponse::Ok().body("House time set successfully")
@@ -277,20 +404,30 @@ async fn set_time(path: web::Path<u32>, time: web::Json<demkit::sim::Time>) -> i
     description = "Reset the house simulation",
     responses(
         (status = 200, description = "House reset successfully"),
-        (status = 500, description = "Error resetting house"),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("id", description = "House ID", example = 1),
     ),
 )]
 #[delete("")]
-async fn reset(path: web::Path<u32>) -> impl Responder {
-    let _house_id = path.into_inner();
-    match demkit::env::reset().await {
-        Ok(_) => println!("House reset successfully"),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
+async fn reset(registry: web::Data<Registry>, path: web::Path<u32>) -> impl Responder {
+    let house_id = path.into_inner();
+
+    if let Err(e) = registry.check_owner(house_id) {
+        return e.error_response();
     }
 
+    match demkit::env::reset(registry.backend(house_id).as_ref()).await {
+        Ok(_) => log::info!("House {house_id} reset successfully"),
+        Err(e) => return e.error_response(),
+    }
+
+    registry.release(house_id);
+    demkit::timeshifters::unregister_house(house_id);
+    demkit::ha_entity::unregister_house(house_id);
+
     HttpResponse::Ok().body("House simulation reset successfully")
 }
 
@@ -301,25 +438,36 @@ async fn reset(path: web::Path<u32>) -> impl Responder {
     path = "/load",
     responses(
         (status = 200, description = "House loaded successfully"),
-        (status = 500, description = "Error loading house"),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        UpstreamErrors,
     ),
     params(
         ("id", description = "House ID", example = 1),
     ),
 )]
 #[post("/load")]
-async fn load(path: web::Path<u32>) -> impl Responder {
-    let _house_id = path.into_inner();
-    match demkit::env::load().await {
-        Ok(_) => println!("House loaded successfully"),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
+async fn load(registry: web::Data<Registry>, path: web::Path<u32>) -> impl Responder {
+    let house_id = path.into_inner();
+
+    if let Err(e) = registry.check_owner(house_id) {
+        return e.error_response();
+    }
+
+    let backend = registry.backend(house_id);
+
+    match demkit::env::load(backend.as_ref()).await {
+        Ok(_) => log::info!("House {house_id} loaded successfully"),
+        Err(e) => return e.error_response(),
     }
 
-    match demkit::env::start().await {
-        Ok(_) => println!("House started successfully"),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
+    match demkit::env::start(backend.as_ref()).await {
+        Ok(_) => log::info!("House {house_id} started successfully"),
+        Err(e) => return e.error_response(),
     }
 
+    // the thermostat starts with the jobs from DEMKit's files again
+    demkit::heating_schedule::resend(house_id);
+
     HttpResponse::Ok().body("House simulation loaded successfully and currently running")
 }
 
@@ -330,7 +478,9 @@ async fn load(path: web::Path<u32>) -> impl Responder {
     path = "/config",
     responses(
         (status = 200, description = "House config set successfully"),
-        (status = 500, description = "Error setting house config"),
+        (status = 409, description = "DEMKit instance is in use by another house", body = ErrorBody),
+        (status = 400, description = "Config rejected by DEMKit", body = ErrorBody),
+        UpstreamErrors,
     ),
     request_body = demkit::env::SimConfig,
     params(
@@ -339,14 +489,28 @@ async fn load(path: web::Path<u32>) -> impl Responder {
 )]
 #[post("/config")]
 async fn set_config(
-    _path: web::Path<u32>,
+    registry: web::Data<Registry>,
+    history: web::Data<History>,
+    path: web::Path<u32>,
     config: web::Json<demkit::env::SimConfig>,
 ) -> impl Responder {
-    match demkit::env::set_config(config.into_inner()).await {
-        Ok(_) => println!("House config set successfully"),
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Error: {}", e)),
+    let house_id = path.into_inner();
+
+    if let Err(e) = registry.check_owner(house_id) {
+        return e.error_response();
+    }
+
+    let sim_config = serde_json::to_value(&*config).unwrap_or_default();
+    match demkit::env::set_config(registry.backend(house_id).as_ref(), config.into_inner()).await {
+        Ok(_) => log::info!("House {house_id} config set successfully"),
+        Err(e) => return e.error_response(),
     };
 
+    let record = move |history: &History| history.record_config(house_id, &sim_config);
+    if let Err(e) = history.into_inner().blocking(record).await {
+        log::warn!("Failed to record the simulation config of house {house_id}: {e}");
+    }
+
     HttpResponse::Ok().body("House config set successfully")
 }
 
@@ -357,33 +521,80 @@ async fn set_config(
     path = "/time",
     responses(
         (status = 200, description = "Current time", body = String),
-        (status = 500, description = "Error getting time"),
+        UpstreamErrors,
     ),
     params(
         ("id", description = "House ID", example = 1),
     ),
 )]
 #[get("/time")]
-async fn get_time() -> impl Responder {
-    let current_time = demkit::get_time().await;
-    HttpResponse::Ok().body(current_time.to_string())
+async fn get_time(registry: web::Data<Registry>, path: web::Path<u32>) -> impl Responder {
+    let backend = registry.backend(path.into_inner());
+    match demkit::get_time(backend.as_ref()).await {
+        Ok(current_time) => HttpResponse::Ok().body(current_time.to_string()),
+        Err(e) => e.error_response(),
+    }
 }
 
 #[utoipa::path(
     get,
     tag = "House",
-    description = "List all entities in the house",
+    description = "List all entities belonging to the house",
     path = "/entities",
     responses(
         (status = 200, description = "List of entities", body = String),
-        (status = 500, description = "Error getting entities"),
+        UpstreamErrors,
     ),
     params(
         ("id", description = "House ID", example = 1),
     ),
 )]
 #[get("/entities")]
-async fn list_entities() -> impl Responder {
-    let entities = demkit::list_entities().await;
-    HttpResponse::Ok().json(entities)
+async fn list_entities(registry: web::Data<Registry>, path: web::Path<u32>) -> impl Responder {
+    let house_id = path.into_inner();
+    match demkit::list_entities(registry.backend(house_id).as_ref(), house_id).await {
+        Ok(entities) => HttpResponse::Ok().json(entities),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::api::demkit::fake::FakeBackend;
+
+    const SUN_SPEC: &str = r#"
+        [[entities]]
+        type = "host"
+        name = "House"
+
+        [[entities]]
+        type = "sun"
+        name = "Sun"
+    "#;
+
+    #[actix_web::test]
+    async fn failed_composition_is_rolled_back() {
+        let backend = FakeBackend::new("memory://rollback");
+        let spec_dir = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/houses"));
+        let spec = HouseSpec::load(spec_dir, spec::DEFAULT_SPEC).unwrap();
+
+        // the sun of the house is already there, so composing stops at it
+        let sun = HouseSpec::parse(SUN_SPEC, SpecFormat::Toml).unwrap().entities.pop().unwrap();
+        demkit::env::add_entity(&backend, 201, sun).await.unwrap();
+
+        let report = compose_spec(&backend, 201, spec).await;
+
+        assert_eq!(report.added, ["House-201", "Weather-House-201"]);
+        let failed = report.failed.as_ref().map(|failure| failure.entity.as_str());
+        assert_eq!(failed, Some("Sun-House-201"));
+        assert_eq!(report.rolled_back, ["Weather-House-201", "House-201"]);
+        assert!(report.rollback_failed.is_empty());
+        assert!(!report.reset);
+        assert!(backend.properties("House-201").await.is_err());
+        assert!(backend.properties("Weather-House-201").await.is_err());
+        assert!(backend.properties("Sun-House-201").await.is_ok());
+    }
 }
@@ -0,0 +1,219 @@
+use std::{collections::HashSet, path::Path};
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::api::{
+    demkit::env::{self, EntityParams},
+    error::ErrorBody,
+};
+
+pub const DEFAULT_SPEC: &str = "default";
+
+#[derive(thiserror::Error, Debug)]
+pub enum SpecError {
+    #[error("Failed to read house spec: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid JSON house spec: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid TOML house spec: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Unknown house spec: {0}")]
+    NotFound(String),
+    #[error("Invalid house spec: {0}")]
+    Invalid(String),
+}
+
+impl ResponseError for SpecError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SpecError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            SpecError::NotFound(_) => StatusCode::NOT_FOUND,
+            SpecError::Json(_) | SpecError::Toml(_) | SpecError::Invalid(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let code = match self {
+            SpecError::Io(_) => "spec_unreadable",
+            SpecError::NotFound(_) => "unknown_spec",
+            SpecError::Json(_) | SpecError::Toml(_) | SpecError::Invalid(_) => "invalid_spec",
+        };
+
+        ErrorBody::new(code, self.to_string(), None).response(self.status_code())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum SpecFormat {
+    Json,
+    Toml,
+}
+
+impl SpecFormat {
+    /// Picks the format from a request content type, defaulting to JSON
+    pub fn from_content_type(content_type: &str) -> Self {
+        if content_type.contains("toml") {
+            SpecFormat::Toml
+        } else {
+            SpecFormat::Json
+        }
+    }
+}
+
+/// Declarative description of a house, composed entity by entity in the listed order.
+///
+/// Entity names are given without the house suffix; hems-core appends `-House-{id}`
+/// when composing, so the same spec can be reused for any house.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct HouseSpec {
+    /// Entities of the house, in the order they are added to DEMKit
+    pub entities: Vec<EntityParams>,
+}
+
+impl HouseSpec {
+    pub fn parse(input: &str, format: SpecFormat) -> Result<Self, SpecError> {
+        let spec: HouseSpec = match format {
+            SpecFormat::Json => serde_json::from_str(input)?,
+            SpecFormat::Toml => toml::from_str(input)?,
+        };
+
+        spec.validate()?;
+
+        Ok(spec)
+    }
+
+    /// Loads a named spec from `dir`, trying `{name}.toml` before `{name}.json`
+    pub fn load(dir: &Path, name: &str) -> Result<Self, SpecError> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(SpecError::Invalid(format!("invalid spec name '{name}'")));
+        }
+
+        for (extension, format) in [("toml", SpecFormat::Toml), ("json", SpecFormat::Json)] {
+            let path = dir.join(format!("{name}.{extension}"));
+
+            if path.is_file() {
+                let input = std::fs::read_to_string(path)?;
+                return Self::parse(&input, format);
+            }
+        }
+
+        Err(SpecError::NotFound(name.to_string()))
+    }
+
+    fn validate(&self) -> Result<(), SpecError> {
+        if !matches!(self.entities.first(), Some(EntityParams::Host(_))) {
+            return Err(SpecError::Invalid(
+                "the first entity must be the host".to_string(),
+            ));
+        }
+
+        let mut names = HashSet::new();
+        for entity in &self.entities {
+            if !names.insert(entity.name()) {
+                return Err(SpecError::Invalid(format!(
+                    "duplicate entity name '{}'",
+                    entity.name()
+                )));
+            }
+
+            // the battery and solar routes address these entities by a fixed name
+            let fixed_name = match entity {
+                EntityParams::Battery(_) => Some(env::BATTERY_NAME),
+                EntityParams::Solar(_) => Some(env::SOLAR_NAME),
+                _ => None,
+            };
+            if let Some(fixed_name) = fixed_name.filter(|fixed_name| entity.name() != *fixed_name) {
+                return Err(SpecError::Invalid(format!(
+                    "entity '{}' must be named '{fixed_name}'",
+                    entity.name()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(entities: &str) -> Result<HouseSpec, SpecError> {
+        HouseSpec::parse(entities, SpecFormat::Toml)
+    }
+
+    fn assert_invalid(result: Result<HouseSpec, SpecError>, message: &str) {
+        match result {
+            Err(SpecError::Invalid(e)) => assert!(e.contains(message), "{e}"),
+            other => panic!("expected an invalid spec, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn default_spec_is_valid() {
+        let dir = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/houses"));
+        let spec = HouseSpec::load(dir, DEFAULT_SPEC).unwrap();
+
+        assert_eq!(spec.entities.first().map(EntityParams::name), Some("House"));
+    }
+
+    #[test]
+    fn host_comes_first() {
+        let spec = r#"
+            [[entities]]
+            type = "sun"
+            name = "Sun"
+
+            [[entities]]
+            type = "host"
+            name = "House"
+        "#;
+
+        assert_invalid(parse(spec), "the first entity must be the host");
+        assert_invalid(parse("entities = []"), "the first entity must be the host");
+    }
+
+    #[test]
+    fn entity_names_are_unique() {
+        let spec = r#"
+            [[entities]]
+            type = "host"
+            name = "House"
+
+            [[entities]]
+            type = "sun"
+            name = "Sun"
+
+            [[entities]]
+            type = "weather"
+            name = "Sun"
+        "#;
+
+        assert_invalid(parse(spec), "duplicate entity name 'Sun'");
+    }
+
+    #[test]
+    fn batteries_and_solar_have_fixed_names() {
+        let spec = r#"
+            [[entities]]
+            type = "host"
+            name = "House"
+
+            [[entities]]
+            type = "solar_panel"
+            name = "Roof"
+        "#;
+
+        assert_invalid(parse(spec), &format!("entity 'Roof' must be named '{}'", env::SOLAR_NAME));
+    }
+
+    #[test]
+    fn spec_names_stay_in_the_spec_dir() {
+        let dir = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/houses"));
+
+        assert_invalid(HouseSpec::load(dir, "../Cargo"), "invalid spec name '../Cargo'");
+        assert!(matches!(HouseSpec::load(dir, "nowhere"), Err(SpecError::NotFound(name)) if name == "nowhere"));
+    }
+}